@@ -0,0 +1,294 @@
+//! The binding model and the entries of a keybindings.json file.
+
+use std::sync::Arc;
+
+use serde::Serializer;
+use serde_derive::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::key::{KeyRule, parse_key_sequence};
+
+
+/// An entry of a keybindings.json file as it is read and written.
+#[derive(Serialize,Deserialize)]
+pub struct ConfigItem {
+    pub key: String,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Value>
+}
+
+impl From<&KeyBinding> for ConfigItem {
+    fn from(kb: &KeyBinding) -> Self {
+        ConfigItem { 
+            key:  format!("{}", kb.keys), 
+            command: kb.action.to_string(), 
+            when: kb.when.clone(), 
+            args: match &kb.action {
+                BindingAction::Macro(steps) => Some(serde_json::json!({ "commands": steps })),
+                _ => kb.args.clone()
+            }
+        }
+    }
+}
+
+
+/// The VS Code command running a list of commands in order.
+pub const RUN_COMMANDS: &str = "runCommands";
+
+/// A command of a macro, with its args.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MacroStep {
+    pub command: String,
+    pub args: Option<Value>
+}
+
+impl MacroStep {
+    /// A step as `runCommands` takes it: a command id, or `{ "command": ..., "args": ... }`.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(c) => Some(MacroStep { command: c.clone(), args: None }),
+            Value::Object(o) => Some(MacroStep {
+                command: o.get("command")?.as_str()?.to_string(),
+                args: o.get("args").cloned()
+            }),
+            _ => None
+        }
+    }
+}
+
+impl serde::Serialize for MacroStep {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match &self.args {
+            None => serializer.serialize_str(&self.command),
+            Some(args) => serde_json::json!({ "command": self.command, "args": args }).serialize(serializer)
+        }
+    }
+}
+
+
+/// What a binding does: run its command, remove the bindings of the command
+/// (written `-command` in the file) or run a macro of commands one after the other
+/// (written as `runCommands` with the steps in its args).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BindingAction {
+    Run(String),
+    Disable(String),
+    Macro(Vec<MacroStep>)
+}
+
+impl BindingAction {
+    /// The command run or removed.
+    pub fn command(&self) -> &str {
+        match self {
+            BindingAction::Run(c) | BindingAction::Disable(c) => c,
+            BindingAction::Macro(_) => RUN_COMMANDS
+        }
+    }
+
+    pub fn is_disable(&self) -> bool {
+        matches!(self, BindingAction::Disable(_))
+    }
+
+    pub fn is_macro(&self) -> bool {
+        matches!(self, BindingAction::Macro(_))
+    }
+
+    /// The command a disable removes, None for a binding that runs one.
+    pub fn disabled(&self) -> Option<&str> {
+        match self {
+            BindingAction::Disable(c) => Some(c),
+            BindingAction::Run(_) | BindingAction::Macro(_) => None
+        }
+    }
+
+    /// Whether this disable removes the bindings of `action`, `-runCommands` removes macros.
+    pub fn removes(&self, action: &BindingAction) -> bool {
+        match self {
+            BindingAction::Disable(d) => !action.is_disable() && action.command() == d,
+            _ => false
+        }
+    }
+}
+
+impl From<&str> for BindingAction {
+    fn from(command: &str) -> Self {
+        match command.strip_prefix('-') {
+            Some(c) => BindingAction::Disable(c.to_string()),
+            None => BindingAction::Run(command.to_string())
+        }
+    }
+}
+
+impl From<String> for BindingAction {
+    fn from(command: String) -> Self {
+        match command.starts_with('-') {
+            true => BindingAction::from(command.as_str()),
+            false => BindingAction::Run(command)
+        }
+    }
+}
+
+impl std::fmt::Display for BindingAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindingAction::Run(c) => write!(f, "{}", c),
+            BindingAction::Disable(c) => write!(f, "-{}", c),
+            BindingAction::Macro(_) => write!(f, "{}", RUN_COMMANDS)
+        }
+    }
+}
+
+impl serde::Serialize for BindingAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Where a binding comes from.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum BindingSource {
+    /// read from a keybindings file, `-` for stdin
+    File { path: String },
+    /// the user keybindings merged on top of the generated ones
+    User { path: String },
+    /// contributed by an installed extension, `publisher.name`
+    Extension { id: String },
+    /// made by a transformation, from the binding it transformed if there was one
+    Generated {
+        by: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from: Option<Arc<BindingSource>>
+    }
+}
+
+impl BindingSource {
+    /// Whether the binding was read as it is from a keybindings file, which listings leave unsaid.
+    pub fn is_file(&self) -> bool {
+        matches!(self, BindingSource::File { .. })
+    }
+}
+
+impl std::fmt::Display for BindingSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindingSource::File { path } => write!(f, "{}", path),
+            BindingSource::User { path } => write!(f, "user {}", path),
+            BindingSource::Extension { id } => write!(f, "extension {}", id),
+            BindingSource::Generated { by, from: None } => write!(f, "{}", by),
+            BindingSource::Generated { by, from: Some(from) } => write!(f, "{} of {}", by, from)
+        }
+    }
+}
+
+
+/// A parsed keybinding.
+#[derive(Clone)]
+pub struct KeyBinding {
+    pub keys: KeyRule,
+    pub action: BindingAction,
+    pub when: Option<String>,
+    pub args: Option<Value>,
+    /// the `{ "$file": ... }` reference `args` was inlined from
+    pub args_file: Option<String>,
+    /// the entry as it was read, with its key spelling, field order and unknown fields,
+    /// shared with the bindings made from it
+    pub original: Option<Arc<Map<String, Value>>>,
+    /// shared by all the bindings of a file or a transformation
+    pub source: Option<Arc<BindingSource>>
+}
+
+
+impl KeyBinding {
+    /// Whether the first key of the chord has the modifier, or is that modifier pressed alone.
+    pub fn has_modifier(&self, modifier: usize) -> bool {
+        self.keys.first.modifiers & modifier != 0 || self.keys.first.modifier_key() == Some(modifier)
+    }
+
+    /// The command the binding runs or removes.
+    pub fn command(&self) -> &str {
+        self.action.command()
+    }
+
+    pub fn is_disable(&self) -> bool {
+        self.action.is_disable()
+    }
+
+    /// The `-command` entry removing this binding.
+    pub fn copy_disabled(&self) -> Self {
+        KeyBinding {
+            action: BindingAction::Disable(self.command().to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// The binding marked as made by the transformation `by` from what it was.
+    pub fn generated(self, by: &str) -> Self {
+        let from = self.source.clone();
+        KeyBinding { source: Some(Arc::new(BindingSource::Generated { by: by.to_string(), from })), ..self }
+    }
+
+}
+
+
+impl From<ConfigItem> for KeyBinding {
+    fn from(ci: ConfigItem) -> Self {
+        // `runCommands` with nothing but a list of steps is a macro
+        let steps = match (ci.command.as_str(), ci.args.as_ref().and_then(Value::as_object)) {
+            (RUN_COMMANDS, Some(args)) if args.len() == 1 => args.get("commands")
+                .and_then(Value::as_array)
+                .and_then(|c| c.iter().map(MacroStep::from_value).collect::<Option<Vec<_>>>()),
+            _ => None
+        };
+        let (action, args) = match steps {
+            Some(steps) => (BindingAction::Macro(steps), None),
+            None => (BindingAction::from(ci.command), ci.args)
+        };
+        KeyBinding {
+            keys: parse_key_sequence(&ci.key),
+            action,
+            when: ci.when,
+            args,
+            args_file: None,
+            original: None,
+            source: None
+        }
+    }
+}
+
+/// The entries to write for `bindings`. With `keep_refs` inlined args are written back
+/// as the `{ "$file": ... }` reference they came from.
+pub fn to_config_items(bindings: &[KeyBinding], keep_refs: bool) -> Vec<ConfigItem> {
+    bindings.iter().map(|kb| {
+        let mut item = ConfigItem::from(kb);
+        if let Some(file) = kb.args_file.as_ref().filter(|_| keep_refs) {
+            item.args = Some(serde_json::json!({ "$file": file }));
+        }
+        item
+    }).collect()
+}
+
+/// The entries to write for `bindings` in the shape they were read: fields keep their original
+/// order, unknown fields are kept and the key keeps its spelling unless the keys were changed.
+pub fn to_preserved_entries(bindings: &[KeyBinding], keep_refs: bool) -> Vec<Value> {
+    bindings.iter().zip(to_config_items(bindings, keep_refs)).map(|(kb, item)| {
+        let mut entry = kb.original.as_deref().cloned().unwrap_or_default();
+        let key = match entry.get("key").and_then(Value::as_str) {
+            Some(k) if parse_key_sequence(k) == kb.keys => k.to_string(),
+            _ => item.key
+        };
+        // inserting an existing field keeps its position
+        entry.insert("key".to_string(), Value::String(key));
+        entry.insert("command".to_string(), Value::String(item.command));
+        for (field, value) in [("when", item.when.map(Value::String)), ("args", item.args)] {
+            match value {
+                Some(v) => { entry.insert(field.to_string(), v); },
+                None => { entry.shift_remove(field); }
+            }
+        }
+        Value::Object(entry)
+    }).collect()
+}
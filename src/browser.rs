@@ -0,0 +1,116 @@
+//! The state behind `codekeys tui`: a fuzzy search over the commands, the bindings picked
+//! for export and what a rules file would make of them. Drawing is left to the binary.
+
+use crate::{
+    Result,
+    binding::KeyBinding,
+    map::RemapCounts,
+    rules::{Rule, apply_rules}
+};
+
+
+/// How well `pattern` matches `text` as a case-insensitive subsequence, lower is better:
+/// the characters skipped before and between the matched ones. None if it does not match.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<usize> {
+
+    let mut chars = text.chars().map(|c| c.to_ascii_lowercase()).enumerate();
+    let mut score = 0;
+    let mut next = 0;
+    for p in pattern.chars().map(|c| c.to_ascii_lowercase()) {
+        let (i, _) = chars.find(|(_, c)| *c == p)?;
+        score += i - next;
+        next = i + 1;
+    }
+    Some(score)
+}
+
+/// The bindings being browsed, the search and the selection.
+pub struct Browser {
+    pub bindings: Vec<KeyBinding>,
+    /// whether each binding is exported
+    pub included: Vec<bool>,
+    pub search: String,
+    /// indices of the bindings whose command matches the search, best match first
+    pub visible: Vec<usize>,
+    /// position of the selected binding in `visible`
+    pub selected: usize,
+    rules: Option<Vec<Rule>>
+}
+
+impl Browser {
+    /// A browser with every binding included, `rules` are previewed and applied on export.
+    pub fn new(bindings: Vec<KeyBinding>, rules: Option<Vec<Rule>>) -> Browser {
+        let included = vec![true; bindings.len()];
+        let mut browser = Browser { bindings, included, search: String::new(), visible: vec!(), selected: 0, rules };
+        browser.refilter();
+        browser
+    }
+
+    pub fn has_rules(&self) -> bool {
+        self.rules.is_some()
+    }
+
+    pub fn push_search(&mut self, c: char) {
+        self.search.push(c);
+        self.refilter();
+    }
+
+    pub fn pop_search(&mut self) {
+        self.search.pop();
+        self.refilter();
+    }
+
+    fn refilter(&mut self) {
+        let mut scored: Vec<(usize, usize)> = self.bindings.iter().enumerate()
+            .filter_map(|(i, kb)| fuzzy_score(&self.search, kb.command()).map(|s| (s, i)))
+            .collect();
+        scored.sort();
+        self.visible = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    /// The index of the selected binding.
+    pub fn current(&self) -> Option<usize> {
+        self.visible.get(self.selected).copied()
+    }
+
+    /// Moves the selection by `delta` rows, stopping at the first and last one.
+    pub fn move_by(&mut self, delta: isize) {
+        let last = self.visible.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Includes the selected binding in the export or leaves it out.
+    pub fn toggle(&mut self) {
+        if let Some(i) = self.current() {
+            self.included[i] = !self.included[i];
+        }
+    }
+
+    /// Every binding of the command of binding `i`, disables left out.
+    pub fn chords_of(&self, i: usize) -> Vec<&KeyBinding> {
+        let command = self.bindings[i].command();
+        self.bindings.iter()
+            .filter(|kb| !kb.is_disable() && kb.command() == command)
+            .collect()
+    }
+
+    /// What the rules make of binding `i`, None without rules.
+    pub fn preview(&self, i: usize) -> Option<Result<Vec<KeyBinding>>> {
+        let rules = self.rules.as_ref()?;
+        Some(apply_rules(&self.bindings[i..=i], rules, true, &mut RemapCounts::default()))
+    }
+
+    /// The included bindings, or with rules what `map --rules` generates from them.
+    pub fn export(&self) -> Result<Vec<KeyBinding>> {
+        let picked: Vec<KeyBinding> = self.bindings.iter()
+            .zip(self.included.iter())
+            .filter(|(_, inc)| **inc)
+            .map(|(kb, _)| kb.clone())
+            .collect();
+        match &self.rules {
+            Some(rules) => apply_rules(&picked, rules, true, &mut RemapCounts::default()),
+            None => Ok(picked)
+        }
+    }
+}
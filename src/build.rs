@@ -0,0 +1,89 @@
+//! One source keymap for every platform: a `platform` field limits an entry to some platforms,
+//! and the `primary` modifier is cmd on macOS and ctrl elsewhere.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+use crate::{
+    Result,
+    binding::KeyBinding,
+    fetch::Platform,
+    key::{Key, KeyRule, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION, parse_one_key}
+};
+
+
+/// The modifier standing for cmd on macOS and ctrl on Linux and Windows.
+pub const PRIMARY: &str = "primary";
+
+/// A key in the spelling VS Code uses on `platform`: `cmd` on macOS, `win` on Windows and
+/// `meta` on Linux, in the order ctrl, shift, alt, cmd.
+pub fn spell_key(key: &Key, platform: Platform) -> String {
+    let command = match platform {
+        Platform::Macos => "cmd",
+        Platform::Linux => "meta",
+        Platform::Windows => "win"
+    };
+    let mut out = String::new();
+    for (m, name) in [(MODIFIER_CONTROL, "ctrl"), (MODIFIER_SHIFT, "shift"), (MODIFIER_OPTION, "alt"), (MODIFIER_COMMAND, command)] {
+        if key.modifiers & m != 0 {
+            out += name;
+            out += "+";
+        }
+    }
+    out + &key.code.to_string()
+}
+
+/// Parses a key string of the source, with `primary` resolved for `platform`.
+pub fn parse_source_key(code: &str, platform: Platform) -> KeyRule {
+    let primary = if platform == Platform::Macos { "cmd" } else { "ctrl" };
+    let mut keys = code.split_ascii_whitespace().map(|k| {
+        let resolved: Vec<&str> = k.split('+')
+            .map(|part| if part.eq_ignore_ascii_case(PRIMARY) { primary } else { part })
+            .collect();
+        parse_one_key(&resolved.join("+"))
+    });
+    KeyRule { first: keys.next().unwrap_or_else(crate::key::anykey), rest: keys.collect() }
+}
+
+/// The platforms named by the `platform` field of an entry, all of them without one.
+fn entry_platforms(kb: &KeyBinding) -> Result<Vec<Platform>> {
+    let all = vec!(Platform::Macos, Platform::Linux, Platform::Windows);
+    match kb.original.as_ref().and_then(|o| o.get("platform")) {
+        None => Ok(all),
+        Some(Value::String(p)) => Ok(vec!(Platform::from_name(p)?)),
+        Some(Value::Array(ps)) => ps.iter()
+            .map(|p| p.as_str().ok_or_else(|| anyhow!("platform {} is not a string", p)).and_then(Platform::from_name))
+            .collect(),
+        Some(other) => bail!("platform {} is neither a name nor a list of names", other)
+    }
+}
+
+/// The entries of a source keymap for `platform`: those not limited to other platforms, with
+/// `primary` resolved and the keys spelled the platform's way. The key spelling is kept in
+/// `original`, so the bindings are to be written preserved, and the `platform` field is dropped.
+pub fn build(bindings: &[KeyBinding], platform: Platform) -> Result<Vec<KeyBinding>> {
+
+    let mut built = vec!();
+
+    for (i, kb) in bindings.iter().enumerate() {
+        let platforms = entry_platforms(kb).map_err(|e| anyhow!("[{}] {} {}: {}", i, kb.keys, kb.action, e))?;
+        if !platforms.contains(&platform) {
+            continue;
+        }
+        let source = kb.original.as_ref()
+            .and_then(|o| o.get("key"))
+            .and_then(Value::as_str)
+            .map_or_else(|| kb.keys.to_string(), String::from);
+        let keys = parse_source_key(&source, platform);
+        let spelled: Vec<String> = keys.keys().map(|k| spell_key(k, platform)).collect();
+
+        let mut original = kb.original.as_deref().cloned().unwrap_or_default();
+        original.shift_remove("platform");
+        original.insert("key".to_string(), Value::String(spelled.join(" ")));
+        built.push(KeyBinding { keys, original: Some(Arc::new(original)), ..kb.clone() });
+    }
+
+    Ok(built)
+}
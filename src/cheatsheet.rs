@@ -0,0 +1,117 @@
+//! Printable tables of the bindings, grouped by command category or when clause.
+
+use serde_derive::Serialize;
+
+use crate::{binding::KeyBinding, key::KeyRule, resolve::removed_indices, rules::wildcard_match, taxonomy::Taxonomy};
+
+
+/// What the sections of a cheat sheet are.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum GroupBy {
+    /// The first part of the command, `editor` for `editor.action.commentLine`
+    Category,
+    /// The when clause, bindings without one are `global`
+    When,
+    /// The functional category of the taxonomy, `navigation` or `editing`
+    Taxonomy
+}
+
+/// The markup a cheat sheet is rendered in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SheetFormat {
+    Markdown,
+    Html
+}
+
+/// A binding as it is listed on the sheet.
+#[derive(Serialize)]
+pub struct SheetRow {
+    #[serde(rename = "key")]
+    pub keys: KeyRule,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>
+}
+
+/// The bindings of one category or when clause, in file order.
+#[derive(Serialize)]
+pub struct SheetSection {
+    pub title: String,
+    pub rows: Vec<SheetRow>
+}
+
+/// The active bindings grouped into sections sorted by title. Disables and the bindings they
+/// remove are left out; `filter` keeps only commands matching a pattern like `editor.action.*`,
+/// a pattern without `*` matches as a prefix, and `category` those of a taxonomy category.
+pub fn cheatsheet(bindings: &[KeyBinding], group_by: GroupBy, filter: Option<&str>, category: Option<&str>, taxonomy: &Taxonomy) -> Vec<SheetSection> {
+
+    let removed = removed_indices(bindings);
+    let mut sections: Vec<SheetSection> = vec!();
+
+    for (i, kb) in bindings.iter().enumerate() {
+        if kb.is_disable() || removed.contains(&i) {
+            continue;
+        }
+        let keep = match filter {
+            Some(p) if p.contains('*') => wildcard_match(p, kb.command()),
+            Some(p) => kb.command().starts_with(p),
+            None => true
+        };
+        if !keep || category.is_some_and(|c| taxonomy.category(kb.command()) != c) {
+            continue;
+        }
+
+        let title = match group_by {
+            GroupBy::Category => kb.command().split('.').next().unwrap_or_default().to_string(),
+            GroupBy::When => kb.when.clone().unwrap_or_else(|| "global".to_string()),
+            GroupBy::Taxonomy => taxonomy.category(kb.command()).to_string()
+        };
+        let row = SheetRow { keys: kb.keys.clone(), command: kb.command().to_string(), when: kb.when.clone() };
+        match sections.iter_mut().find(|s| s.title == title) {
+            Some(s) => s.rows.push(row),
+            None => sections.push(SheetSection { title, rows: vec!(row) })
+        }
+    }
+
+    sections.sort_by(|a, b| a.title.cmp(&b.title));
+    sections
+}
+
+/// The sheet as Markdown, a `##` heading and a table per section.
+pub fn render_markdown(sections: &[SheetSection]) -> String {
+
+    let cell = |s: &str| s.replace('|', "\\|");
+    let mut out = String::new();
+
+    for s in sections.iter() {
+        out += &format!("## {}\n\n| Key | Command | When |\n| --- | --- | --- |\n", cell(&s.title));
+        for r in s.rows.iter() {
+            let when = r.when.as_deref().map(|w| format!("`{}`", cell(w))).unwrap_or_default();
+            out += &format!("| `{}` | {} | {} |\n", cell(&r.keys.to_string()), cell(&r.command), when);
+        }
+        out += "\n";
+    }
+    out
+}
+
+/// The sheet as a standalone HTML page, a heading and a table per section.
+pub fn render_html(sections: &[SheetSection]) -> String {
+
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Keybindings</title>\n</head>\n<body>\n");
+
+    for s in sections.iter() {
+        out += &format!("<h2>{}</h2>\n<table>\n<tr><th>Key</th><th>Command</th><th>When</th></tr>\n", escape_html(&s.title));
+        for r in s.rows.iter() {
+            out += &format!("<tr><td><kbd>{}</kbd></td><td>{}</td><td><code>{}</code></td></tr>\n",
+                escape_html(&r.keys.to_string()), escape_html(&r.command), escape_html(r.when.as_deref().unwrap_or_default()));
+        }
+        out += "</table>\n";
+    }
+    out + "</body>\n</html>\n"
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,131 @@
+//! Checks for entries VS Code cannot use.
+
+use std::{collections::HashSet, sync::Arc};
+
+use serde_derive::Serialize;
+
+use crate::{binding::{BindingSource, KeyBinding}, conflict::{Conflict, find_conflicts}, key::Key};
+
+
+/// An entry of a keybindings file that VS Code would ignore or misread.
+#[derive(Serialize)]
+pub struct Problem {
+    pub index: usize,
+    pub key: String,
+    pub command: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Arc<BindingSource>>
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:>4}] {} {}: {}", self.index, self.key, self.command, self.message)?;
+        if let Some(s) = self.source.as_ref().filter(|s| !s.is_file()) {
+            write!(f, " (from {})", s)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Everything `codekeys check` finds in a keybindings file.
+#[derive(Serialize)]
+pub struct CheckReport {
+    pub problems: Vec<Problem>,
+    pub conflicts: Vec<Conflict>
+}
+
+impl std::fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.problems.iter().try_for_each(|p| p.fmt(f))?;
+        if !self.conflicts.is_empty() {
+            if !self.problems.is_empty() {
+                writeln!(f)?;
+            }
+            writeln!(f, "{} chords are bound to different commands in overlapping contexts:", self.conflicts.len())?;
+            self.conflicts.iter().try_for_each(|c| c.fmt(f))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn check(bindings: &[KeyBinding]) -> CheckReport {
+    CheckReport { problems: check_bindings(bindings), conflicts: find_conflicts(bindings) }
+}
+
+/// Entries without a key or without a command.
+pub fn check_bindings(bindings: &[KeyBinding]) -> Vec<Problem> {
+
+    let mut problems = vec!();
+
+    for (index, kb) in bindings.iter().enumerate() {
+        let mut problem = |message: &str| problems.push(Problem {
+            index, key: kb.keys.to_string(), command: kb.action.to_string(), message: message.to_string(), source: kb.source.clone()
+        });
+        if kb.keys.keys().any(Key::is_empty) {
+            problem("key has no key besides the modifiers");
+        }
+        if kb.command().is_empty() {
+            problem("command is empty");
+        }
+    }
+
+    problems
+}
+
+
+/// The indices of disable entries whose positive command never appears in `bindings`,
+/// including a bare `-`.
+pub fn orphaned_disables(bindings: &[KeyBinding]) -> Vec<usize> {
+    orphaned_disables_with(bindings, &[])
+}
+
+/// The indices of the disable entries of `bindings` whose positive command is bound neither
+/// in `bindings` nor in `others`, e.g. the disables a remap generated against its input.
+pub fn orphaned_disables_with(bindings: &[KeyBinding], others: &[KeyBinding]) -> Vec<usize> {
+    let bound: HashSet<&str> = bindings.iter().chain(others)
+        .filter(|kb| !kb.is_disable())
+        .map(|kb| kb.command())
+        .collect();
+
+    bindings.iter().enumerate()
+        .filter(|(_, kb)| kb.action.disabled().is_some_and(|c| c.is_empty() || !bound.contains(c)))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// The disables of [`orphaned_disables`] as problems.
+pub fn orphaned_disable_problems(bindings: &[KeyBinding]) -> Vec<Problem> {
+    orphaned_disables(bindings).into_iter().map(|index| {
+        let kb = &bindings[index];
+        Problem {
+            index, key: kb.keys.to_string(), command: kb.action.to_string(),
+            message: "disables a command that is never bound".to_string(), source: kb.source.clone()
+        }
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFormat, parse_config_items};
+
+    fn bindings(json: &str) -> Vec<KeyBinding> {
+        parse_config_items(json, InputFormat::Json).unwrap().into_iter().map(KeyBinding::from).collect()
+    }
+
+    #[test]
+    fn a_generated_disable_of_a_command_bound_nowhere_is_reported() {
+        let input = bindings(r#"[{ "key": "ctrl+s", "command": "save" }]"#);
+        let generated = bindings(r#"[
+            { "key": "ctrl+s", "command": "-save" },
+            { "key": "cmd+s", "command": "save" },
+            { "key": "ctrl+q", "command": "-quit" },
+            { "key": "ctrl+w", "command": "-" }
+        ]"#);
+        assert_eq!(orphaned_disables_with(&generated, &input), vec!(2, 3));
+        assert!(orphaned_disables_with(&generated[..1], &input).is_empty());
+        assert_eq!(orphaned_disables(&generated[..1]), vec!(0));
+    }
+}
@@ -0,0 +1,95 @@
+//! The commands VS Code knows, for catching bindings of commands that do not exist.
+
+use std::{collections::HashSet, path::Path};
+
+use anyhow::anyhow;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{Result, binding::KeyBinding, check::Problem, input::strip_jsonc};
+
+
+/// The command ids in a commands file: a JSON array of ids or of keybinding entries, the
+/// defaults exported with "Open Default Keyboard Shortcuts (JSON)" including the commands
+/// listed in its trailing comments, or plain text with one id per line.
+pub fn parse_commands(content: &str) -> HashSet<String> {
+
+    let mut commands = HashSet::new();
+
+    // the export lists the commands without a default binding as `// - command.id`
+    let listed = Regex::new(r"(?m)^\s*//\s*-\s*([^\s,]+)").expect("valid regex");
+    commands.extend(listed.captures_iter(content).map(|c| c[1].to_string()));
+
+    match serde_json::from_str::<Value>(&strip_jsonc(content)) {
+        Ok(Value::Array(items)) => {
+            for item in items {
+                let id = match &item {
+                    Value::String(s) => Some(s.as_str()),
+                    Value::Object(o) => o.get("command").and_then(Value::as_str),
+                    _ => None
+                };
+                if let Some(id) = id.map(|id| id.trim_start_matches('-')).filter(|id| !id.is_empty()) {
+                    commands.insert(id.to_string());
+                }
+            }
+        },
+        _ if commands.is_empty() => {
+            commands.extend(content.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(String::from));
+        },
+        _ => ()
+    }
+
+    commands
+}
+
+pub fn load_commands(path: &Path) -> Result<HashSet<String>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("cannot read commands file {}: {}", path.display(), e))?;
+    Ok(parse_commands(&content))
+}
+
+/// The edit distance between two ids.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (row[j + 1] + 1).min(row[j] + 1).min(diagonal + usize::from(ca != *cb));
+            diagonal = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// The known command nearest to `command`, if it is close enough to be a typo.
+pub fn suggest<'a>(command: &str, known: &'a HashSet<String>) -> Option<&'a str> {
+    let limit = (command.len() / 10).clamp(1, 3);
+    known.iter()
+        .map(|k| (distance(command, k), k))
+        .filter(|(d, _)| *d <= limit)
+        .min()
+        .map(|(_, k)| k.as_str())
+}
+
+/// A problem for every binding (or disable) of a command that is not in `known`.
+pub fn unknown_commands(bindings: &[KeyBinding], known: &HashSet<String>) -> Vec<Problem> {
+    bindings.iter().enumerate()
+        .filter(|(_, kb)| !kb.command().is_empty() && !known.contains(kb.command()))
+        .map(|(index, kb)| Problem {
+            index,
+            key: kb.keys.to_string(),
+            command: kb.action.to_string(),
+            message: match suggest(kb.command(), known) {
+                Some(s) => format!("unknown command, did you mean {}?", s),
+                None => "unknown command".to_string()
+            },
+            source: kb.source.clone()
+        })
+        .collect()
+}
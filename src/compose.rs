@@ -0,0 +1,75 @@
+//! Composing a keymap from layers, a base file and overlays applied on top of it in order.
+//!
+//! A binding of a later layer replaces the bindings of earlier layers on the same keys and
+//! when clause, whatever their command. A disable removes the bindings of its command in
+//! earlier layers, on its keys or on any keys when it has none, and stays in the keymap so it
+//! still removes the VS Code default. Disables of earlier layers are left alone, and entries
+//! of the same layer never override each other, VS Code settles those as usual.
+
+use std::collections::HashMap;
+
+use serde_derive::Serialize;
+
+use crate::{binding::KeyBinding, key::KeyRule};
+
+
+/// What a layer did to the layers before it.
+#[derive(Serialize, Default, Clone, Copy)]
+pub struct LayerOverrides {
+    /// bindings on keys and a when clause the layer binds itself
+    pub replaced: usize,
+    /// bindings of a command the layer disables
+    pub removed: usize
+}
+
+/// The composed keymap and what each layer overrode, in layer order.
+pub struct Composed {
+    pub bindings: Vec<KeyBinding>,
+    pub overrides: Vec<LayerOverrides>
+}
+
+/// The bindings of `layers` composed in order, later layers overriding earlier ones.
+pub fn compose(layers: Vec<Vec<KeyBinding>>) -> Composed {
+
+    let mut composed: Vec<Option<KeyBinding>> = vec!();
+    let mut overrides = vec!();
+
+    for layer in layers {
+        let mut counts = LayerOverrides::default();
+        let mut chords: HashMap<(&KeyRule, &Option<String>), Vec<usize>> = HashMap::new();
+        let mut commands: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, kb) in composed.iter().enumerate().filter_map(|(i, kb)| Some((i, kb.as_ref()?))) {
+            if !kb.is_disable() {
+                chords.entry((&kb.keys, &kb.when)).or_default().push(i);
+                commands.entry(kb.command()).or_default().push(i);
+            }
+        }
+
+        let mut dropped = vec![false; composed.len()];
+        for kb in layer.iter() {
+            if kb.is_disable() {
+                for &i in commands.get(kb.command()).into_iter().flatten() {
+                    let earlier = composed[i].as_ref().map(|e| &e.keys);
+                    if !dropped[i] && (kb.keys.first.is_empty() || earlier == Some(&kb.keys)) {
+                        dropped[i] = true;
+                        counts.removed += 1;
+                    }
+                }
+            } else {
+                for &i in chords.get(&(&kb.keys, &kb.when)).into_iter().flatten() {
+                    if !dropped[i] {
+                        dropped[i] = true;
+                        counts.replaced += 1;
+                    }
+                }
+            }
+        }
+        for (slot, _) in composed.iter_mut().zip(dropped).filter(|(_, d)| *d) {
+            *slot = None;
+        }
+        composed.extend(layer.into_iter().map(Some));
+        overrides.push(counts);
+    }
+
+    Composed { bindings: composed.into_iter().flatten().collect(), overrides }
+}
@@ -0,0 +1,133 @@
+//! `codekeys.toml`, the settings `codekeys apply` runs with instead of command line flags, and
+//! the severities of `codekeys lint`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use serde_derive::Deserialize;
+
+use crate::{Result, install::write_atomic, lint::LintConfig, paths::{config_dir, home_dir}};
+
+
+/// The settings of a config file, each one the value of the command line flag of the same name.
+/// Relative paths are relative to the directory of the file, `~/` is the home directory.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub input: Option<PathBuf>,
+    pub input_format: Option<String>,
+    pub direction: Option<String>,
+    pub rules: Option<PathBuf>,
+    pub pipeline: Option<String>,
+    pub no_disable: bool,
+    pub layout: Option<String>,
+    pub when: Option<String>,
+    pub not_when: Option<String>,
+    /// a user keybindings file to merge, `apply` runs `merge` instead of `map` with it
+    pub user: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub format: Option<String>,
+    pub sort: Option<String>,
+    pub sections: Option<String>,
+    pub install: bool,
+    pub editor: Option<String>,
+    /// the `[lint]` table, the profile and rule severities of `lint`
+    pub lint: LintConfig,
+}
+
+impl Config {
+    /// The files the generated keybindings are made from, the input defaulting like `map` does.
+    pub fn sources(&self) -> Vec<PathBuf> {
+        let input = self.input.clone().unwrap_or_else(|| PathBuf::from("keys/default.json"));
+        let pipeline = self.pipeline.as_ref()
+            .filter(|p| !p.trim_start().starts_with('['))
+            .map(PathBuf::from);
+        [Some(input), self.rules.clone(), pipeline, self.user.clone()].into_iter().flatten().collect()
+    }
+}
+
+/// What `codekeys config init` writes.
+pub const CONFIG_TEMPLATE: &str = r#"# Settings for `codekeys apply`, each one is the command line flag of the same name.
+# Relative paths are relative to this file, `~/` is the home directory.
+
+# the defaults to transform, exported with "Open Default Keyboard Shortcuts (JSON)"
+# input = "default-keybindings.json"
+
+# ctrl-to-cmd for a Linux/Windows keymap on macOS, cmd-to-ctrl the other way
+direction = "ctrl-to-cmd"
+
+# transform with a rules file (or a pipeline) instead of the plain remap
+# rules = "rules.toml"
+
+# only transform the bindings scoped within a when expression, or leave those alone
+# not_when = "terminalFocus"
+
+# rewrite the generated keys for a keyboard layout: de, fr
+# layout = "de"
+
+# merge with your own keybindings, they come last and win
+# user = "~/.config/Code/User/keybindings.json"
+
+# where the result goes: a file, or install it into VS Code with a backup
+# output = "keybindings.json"
+# sort = "key"
+# sections = "kind"
+install = false
+# editor = "code"
+
+# the checks of `codekeys lint`: a profile (minimal, default, strict) and the severity
+# (off, warn, error) of single rules
+[lint]
+# profile = "strict"
+# rules = { "disabled-never-rebound" = "off", "terminal-without-when" = "warn" }
+"#;
+
+/// The config file to use: `explicit`, otherwise `codekeys.toml` in the config directory.
+pub fn config_path(explicit: Option<&Path>) -> Result<PathBuf> {
+    match explicit {
+        Some(path) => Ok(path.to_path_buf()),
+        None => Ok(config_dir()?.join("codekeys.toml"))
+    }
+}
+
+/// `path` with `~/` expanded and, if relative, taken relative to `base`.
+fn resolve(path: &Path, base: &Path) -> Result<PathBuf> {
+    match path.strip_prefix("~") {
+        Ok(rest) => Ok(home_dir()?.join(rest)),
+        Err(_) => Ok(base.join(path))
+    }
+}
+
+/// Reads the config file at `path` with its paths resolved.
+pub fn load_config(path: &Path) -> Result<Config> {
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            bail!("no config file at {}, create one with `codekeys config init`", path.display())
+        },
+        Err(e) => bail!("cannot read {}: {}", path.display(), e)
+    };
+    let mut config: Config = toml::from_str(&content).map_err(|e| anyhow!("{} is not a valid config file: {}", path.display(), e))?;
+
+    let base = path.parent().unwrap_or(Path::new("."));
+    for p in [&mut config.input, &mut config.rules, &mut config.user, &mut config.output].into_iter().flatten() {
+        *p = resolve(p, base)?;
+    }
+    // a pipeline is a path unless it is the JSON array itself
+    if let Some(p) = config.pipeline.as_mut().filter(|p| !p.trim_start().starts_with('[')) {
+        *p = resolve(Path::new(p.as_str()), base)?.to_string_lossy().into_owned();
+    }
+    Ok(config)
+}
+
+/// Writes the commented template to `path`, an existing file is only replaced with `force`.
+pub fn init_config(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        bail!("{} exists already, use --force to replace it", path.display());
+    }
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_atomic(path, CONFIG_TEMPLATE)
+}
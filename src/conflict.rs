@@ -0,0 +1,282 @@
+//! Chords bound to more than one command in overlapping contexts.
+
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+
+use serde_derive::Serialize;
+
+use anyhow::bail;
+
+use crate::{
+    Result,
+    binding::{BindingSource, KeyBinding},
+    key::KeyRule,
+    resolution::Resolution,
+    resolve::removed_indices,
+    when::{covers, overlaps}
+};
+
+
+/// A binding taking part in a conflict.
+#[derive(Serialize)]
+pub struct ConflictEntry {
+    pub index: usize,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    /// a later entry that applies whenever this one does, so this one never runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shadowed_by: Option<usize>,
+    /// later entries that win where both contexts apply
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overlapped_by: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Arc<BindingSource>>
+}
+
+impl ConflictEntry {
+    /// The entry runs in every context it is bound for.
+    pub fn wins(&self) -> bool {
+        self.shadowed_by.is_none() && self.overlapped_by.is_empty()
+    }
+}
+
+/// All conflicting entries of one chord, in file order.
+#[derive(Serialize)]
+pub struct Conflict {
+    #[serde(rename = "key")]
+    pub keys: KeyRule,
+    pub entries: Vec<ConflictEntry>
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.keys)?;
+        for e in self.entries.iter() {
+            let marker = if e.wins() { "->" } else { "  " };
+            write!(f, "  {} [{:>4}] {}", marker, e.index, e.command)?;
+            if let Some(w) = &e.when {
+                write!(f, "  when {}", w)?;
+            }
+            if let Some(by) = e.shadowed_by {
+                write!(f, "  (shadowed by [{}])", by)?;
+            } else if !e.overlapped_by.is_empty() {
+                let by: Vec<String> = e.overlapped_by.iter().map(|i| format!("[{}]", i)).collect();
+                write!(f, "  (loses to {} where both apply)", by.join(", "))?;
+            }
+            if let Some(s) = e.source.as_ref().filter(|s| !s.is_file()) {
+                write!(f, "  from {}", s)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Chords with different commands bound in overlapping contexts. Disables and the bindings
+/// they remove are left out; of two overlapping entries the later one wins.
+pub fn find_conflicts(bindings: &[KeyBinding]) -> Vec<Conflict> {
+
+    let removed = removed_indices(bindings);
+
+    let mut chords: Vec<(&KeyRule, Vec<usize>)> = vec!();
+    let mut lookup: HashMap<&KeyRule, usize> = HashMap::new();
+    for (i, kb) in bindings.iter().enumerate() {
+        if kb.is_disable() || removed.contains(&i) {
+            continue;
+        }
+        let ci = *lookup.entry(&kb.keys).or_insert_with(|| {
+            chords.push((&kb.keys, vec!()));
+            chords.len() - 1
+        });
+        chords[ci].1.push(i);
+    }
+
+    let mut conflicts = vec!();
+
+    for (keys, indexes) in chords.into_iter().filter(|(_, ix)| ix.len() > 1) {
+
+        let mut entries: Vec<ConflictEntry> = vec!();
+        for (n, &i) in indexes.iter().enumerate() {
+            let kb = &bindings[i];
+            let later = indexes[n + 1..].iter()
+                .filter(|&&j| bindings[j].action != kb.action && overlaps(&bindings[j].when, &kb.when));
+            let mut entry = ConflictEntry {
+                index: i, command: kb.action.to_string(), when: kb.when.clone(), shadowed_by: None, overlapped_by: vec!(),
+                source: kb.source.clone()
+            };
+            for &j in later {
+                if covers(&bindings[j].when, &kb.when) {
+                    entry.shadowed_by = Some(j);
+                } else {
+                    entry.overlapped_by.push(j);
+                }
+            }
+            entries.push(entry);
+        }
+
+        // keep the entries that lose somewhere and the ones they lose to
+        let involved: HashSet<usize> = entries.iter()
+            .filter(|e| !e.wins())
+            .flat_map(|e| e.shadowed_by.iter().chain(e.overlapped_by.iter()).copied().chain([e.index]))
+            .collect();
+        entries.retain(|e| involved.contains(&e.index));
+
+        if !entries.is_empty() {
+            conflicts.push(Conflict { keys: keys.clone(), entries });
+        }
+    }
+
+    conflicts
+}
+
+
+/// What to do when a generated binding lands on a chord and when clause a default still uses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ConflictPolicy {
+    /// Leave the default alone and drop the generated binding with its disable
+    KeepOld,
+    /// Keep the generated binding and disable the default explicitly
+    KeepNew,
+    /// Fail
+    Error
+}
+
+impl ConflictPolicy {
+    /// The resolution the policy settles every conflict with, none for `Error`.
+    pub fn resolution(self) -> Option<Resolution> {
+        match self {
+            ConflictPolicy::KeepOld => Some(Resolution::Keep),
+            ConflictPolicy::KeepNew => Some(Resolution::Replace),
+            ConflictPolicy::Error => None
+        }
+    }
+}
+
+/// A generated binding on the same chord and when clause as a default it does not disable.
+#[derive(Serialize)]
+pub struct Collision {
+    #[serde(rename = "key")]
+    pub keys: KeyRule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    /// index in the defaults
+    pub default: usize,
+    pub default_command: String,
+    /// index in the generated bindings
+    pub generated: usize,
+    pub generated_command: String
+}
+
+impl std::fmt::Display for Collision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.keys)?;
+        if let Some(w) = &self.when {
+            write!(f, "  when {}", w)?;
+        }
+        write!(f, ": {} shadows default [{}] {}", self.generated_command, self.default, self.default_command)
+    }
+}
+
+/// The generated bindings that shadow a default with the same chord and when clause, defaults
+/// the generated disables remove do not count.
+pub fn find_collisions(defaults: &[KeyBinding], generated: &[KeyBinding]) -> Vec<Collision> {
+
+    let all: Vec<KeyBinding> = defaults.iter().chain(generated.iter()).cloned().collect();
+    let removed = removed_indices(&all);
+
+    let mut lookup: HashMap<(&KeyRule, &Option<String>), Vec<usize>> = HashMap::new();
+    for (i, d) in defaults.iter().enumerate().filter(|(i, d)| !d.is_disable() && !removed.contains(i)) {
+        lookup.entry((&d.keys, &d.when)).or_default().push(i);
+    }
+
+    let mut collisions = vec!();
+    for (j, g) in generated.iter().enumerate().filter(|(_, g)| !g.is_disable()) {
+        for &i in lookup.get(&(&g.keys, &g.when)).into_iter().flatten() {
+            if defaults[i].action != g.action {
+                collisions.push(Collision {
+                    keys: g.keys.clone(),
+                    when: g.when.clone(),
+                    default: i,
+                    default_command: defaults[i].action.to_string(),
+                    generated: j,
+                    generated_command: g.action.to_string()
+                });
+            }
+        }
+    }
+    collisions
+}
+
+/// The generated bindings with `collisions` settled by `policy`.
+pub fn resolve_collisions(defaults: &[KeyBinding], generated: Vec<KeyBinding>, collisions: &[Collision], policy: ConflictPolicy) -> Result<Vec<KeyBinding>> {
+
+    let Some(resolution) = policy.resolution() else {
+        return match collisions.first() {
+            Some(c) => bail!("{} generated bindings collide with defaults, the first is {}", collisions.len(), c),
+            None => Ok(generated)
+        }
+    };
+    Ok(settle_collisions(defaults, generated, collisions, &vec![resolution; collisions.len()]))
+}
+
+/// The generated bindings with each of `collisions` settled by the resolution at the same position.
+pub fn settle_collisions(defaults: &[KeyBinding], generated: Vec<KeyBinding>, collisions: &[Collision], resolutions: &[Resolution]) -> Vec<KeyBinding> {
+
+    let mut dropped: HashSet<usize> = HashSet::new();
+    let mut disabled: HashMap<usize, Vec<(usize, &str)>> = HashMap::new();
+    for (c, r) in collisions.iter().zip(resolutions.iter()) {
+        if matches!(r, Resolution::Keep | Resolution::Disable) {
+            dropped.insert(c.generated);
+            // the disable map_binding() put right before it
+            let pair = c.generated.checked_sub(1).filter(|&p| {
+                generated[p].action.removes(&generated[c.generated].action)
+            });
+            dropped.extend(pair);
+        }
+        match r {
+            Resolution::Replace => disabled.entry(c.generated).or_default().push((c.default, "conflict keep-new")),
+            Resolution::Disable => disabled.entry(c.generated).or_default().push((c.default, "conflict disable")),
+            _ => ()
+        }
+    }
+
+    let mut bneu = vec!();
+    for (j, g) in generated.into_iter().enumerate() {
+        for &(i, reason) in disabled.get(&j).into_iter().flatten() {
+            bneu.push(defaults[i].copy_disabled().generated(reason));
+        }
+        if !dropped.contains(&j) {
+            bneu.push(g);
+        }
+    }
+    bneu
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFormat, parse_config_items};
+
+    fn bindings(json: &str) -> Vec<KeyBinding> {
+        parse_config_items(json, InputFormat::Json).unwrap().into_iter().map(KeyBinding::from).collect()
+    }
+
+    #[test]
+    fn conflicts_serialize_with_the_key_and_their_entries() {
+        let bindings = bindings(r#"[
+            { "key": "ctrl+k", "command": "first" },
+            { "key": "ctrl+k", "command": "second" },
+            { "key": "ctrl+j", "command": "alone" }
+        ]"#);
+        let json = serde_json::to_value(find_conflicts(&bindings)).unwrap();
+        assert_eq!(json, serde_json::json!([{
+            "key": "ctrl+k",
+            "entries": [
+                { "index": 0, "command": "first", "shadowed_by": 1 },
+                { "index": 1, "command": "second" }
+            ]
+        }]));
+    }
+}
@@ -0,0 +1,153 @@
+//! The parsed model of a keybindings file for other tools: every chord taken apart into key
+//! presses with their modifier flags and every when clause as an expression tree. The JSON
+//! shape only changes with [`DESCRIBE_VERSION`].
+
+use std::sync::Arc;
+
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use crate::{
+    binding::{BindingSource, ConfigItem, KeyBinding},
+    key::{Key, KeyRule, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION},
+    when::{When, parse_when}
+};
+
+
+/// The version of the JSON written by `describe`, raised whenever its shape changes.
+pub const DESCRIBE_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    /// cmd on macOS, win on Windows, meta or super on Linux
+    pub meta: bool
+}
+
+/// A key press of a chord.
+#[derive(Serialize)]
+pub struct KeyPress {
+    /// the key without modifiers, a `[KeyCode]` for a physical key
+    pub key: String,
+    pub modifiers: Modifiers
+}
+
+impl From<&Key> for KeyPress {
+    fn from(k: &Key) -> Self {
+        KeyPress {
+            key: k.code.to_string(),
+            modifiers: Modifiers {
+                ctrl: k.modifiers & MODIFIER_CONTROL != 0,
+                shift: k.modifiers & MODIFIER_SHIFT != 0,
+                alt: k.modifiers & MODIFIER_OPTION != 0,
+                meta: k.modifiers & MODIFIER_COMMAND != 0
+            }
+        }
+    }
+}
+
+/// A when clause as a tree, each node with its `type`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WhenNode {
+    True,
+    False,
+    Key { key: String },
+    Not { operand: Box<WhenNode> },
+    And { operands: Vec<WhenNode> },
+    Or { operands: Vec<WhenNode> },
+    Equals { key: String, value: String },
+    NotEquals { key: String, value: String },
+    /// `op` is `<`, `<=`, `>` or `>=`
+    Compare { key: String, op: String, value: String },
+    Matches { key: String, pattern: String, flags: String },
+    In { key: String, collection: String },
+    NotIn { key: String, collection: String }
+}
+
+impl From<&When> for WhenNode {
+    fn from(w: &When) -> Self {
+        match w {
+            When::True => WhenNode::True,
+            When::False => WhenNode::False,
+            When::Key(key) => WhenNode::Key { key: key.clone() },
+            When::Not(inner) => WhenNode::Not { operand: Box::new(inner.as_ref().into()) },
+            When::And(terms) => WhenNode::And { operands: terms.iter().map(WhenNode::from).collect() },
+            When::Or(terms) => WhenNode::Or { operands: terms.iter().map(WhenNode::from).collect() },
+            When::Equals(key, value) => WhenNode::Equals { key: key.clone(), value: value.clone() },
+            When::NotEquals(key, value) => WhenNode::NotEquals { key: key.clone(), value: value.clone() },
+            When::Compare(key, op, value) => WhenNode::Compare { key: key.clone(), op: op.symbol().to_string(), value: value.clone() },
+            When::Matches(key, pattern, flags) => WhenNode::Matches { key: key.clone(), pattern: pattern.clone(), flags: flags.clone() },
+            When::In(key, collection) => WhenNode::In { key: key.clone(), collection: collection.clone() },
+            When::NotIn(key, collection) => WhenNode::NotIn { key: key.clone(), collection: collection.clone() }
+        }
+    }
+}
+
+/// A binding taken apart.
+#[derive(Serialize)]
+pub struct BindingDescription {
+    /// position in the input
+    pub index: usize,
+    pub key: KeyRule,
+    pub chord: Vec<KeyPress>,
+    /// the command run or removed, without the `-` of a disable
+    /// (`runCommands` for a macro, with the steps in `args`)
+    pub command: String,
+    pub disable: bool,
+    pub when: Option<String>,
+    /// the parsed `when`, none without one or if it does not parse
+    pub when_tree: Option<WhenNode>,
+    /// why `when` does not parse
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when_error: Option<String>,
+    pub args: Option<Value>,
+    pub source: Option<Arc<BindingSource>>
+}
+
+impl std::fmt::Display for BindingDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:>4}] {}  {}{}", self.index, self.key, if self.disable { "-" } else { "" }, self.command)?;
+        if let Some(w) = &self.when {
+            write!(f, "  when {}", w)?;
+        }
+        if let Some(e) = &self.when_error {
+            write!(f, "  ({})", e)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// The description of a whole file.
+#[derive(Serialize)]
+pub struct Description {
+    pub version: u32,
+    pub bindings: Vec<BindingDescription>
+}
+
+impl std::fmt::Display for Description {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.bindings.iter().try_for_each(|b| b.fmt(f))
+    }
+}
+
+pub fn describe(bindings: &[KeyBinding]) -> Description {
+    let bindings = bindings.iter().enumerate().map(|(index, kb)| {
+        let parsed = kb.when.as_deref().map(parse_when);
+        BindingDescription {
+            index,
+            key: kb.keys.clone(),
+            chord: kb.keys.keys().map(KeyPress::from).collect(),
+            command: kb.command().to_string(),
+            disable: kb.is_disable(),
+            when: kb.when.clone(),
+            when_tree: parsed.as_ref().and_then(|p| p.as_ref().ok()).map(WhenNode::from),
+            when_error: parsed.and_then(|p| p.err()).map(|e| format!("{:#}", e)),
+            args: ConfigItem::from(kb).args,
+            source: kb.source.clone()
+        }
+    }).collect();
+    Description { version: DESCRIBE_VERSION, bindings }
+}
@@ -0,0 +1,148 @@
+//! Errors in an input file that point at the entry and the place they were found in.
+
+use serde_json::Value;
+
+
+/// An input file that cannot be read, with the place of the problem. `index` and `key` are
+/// those of the entry the problem is in, None for one outside of any entry.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InputError {
+    pub message: String,
+    pub file: Option<String>,
+    pub index: Option<usize>,
+    /// the key string of the entry, or the JSON of its `key` field if that is not a string
+    pub key: Option<String>,
+    /// byte offset into the file
+    pub offset: usize,
+    /// one-based line and column of the offset
+    pub line: usize,
+    pub column: usize,
+    /// the text of that line
+    pub source_line: String
+}
+
+impl InputError {
+    /// The error `message` at `offset` in `content`.
+    pub fn at(content: &str, offset: usize, message: impl Into<String>) -> Self {
+        let mut offset = offset.min(content.len());
+        while !content.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let start = content[..offset].rfind('\n').map_or(0, |n| n + 1);
+        let end = content[offset..].find('\n').map_or(content.len(), |n| offset + n);
+        InputError {
+            message: message.into(),
+            file: None,
+            index: None,
+            key: None,
+            offset,
+            line: content[..offset].matches('\n').count() + 1,
+            column: content[start..offset].chars().count() + 1,
+            source_line: content[start..end].trim_end_matches('\r').to_string()
+        }
+    }
+
+    pub fn in_file(self, file: impl Into<String>) -> Self {
+        InputError { file: Some(file.into()), ..self }
+    }
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+        let label = match (self.index, &self.key) {
+            (Some(i), Some(k)) => format!("in entry [{}] with key {}", i, k),
+            (Some(i), None) => format!("in entry [{}]", i),
+            _ => "here".to_string()
+        };
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let pad = " ".repeat(self.column - 1);
+
+        writeln!(f, "× {}", self.message)?;
+        writeln!(f, "{} ╭─[{}:{}:{}]", gutter, self.file.as_deref().unwrap_or("input"), self.line, self.column)?;
+        writeln!(f, "{} │ {}", number, self.source_line)?;
+        writeln!(f, "{} · {}┬", gutter, pad)?;
+        writeln!(f, "{} · {}╰── {}", gutter, pad, label)?;
+        write!(f, "{} ╰────", gutter)
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// The byte ranges of the elements of the top level array of `json`, ends exclusive.
+fn entry_spans(json: &str) -> Vec<(usize, usize)> {
+
+    let bytes = json.as_bytes();
+    let mut spans = vec!();
+    let mut depth = 0;
+    let mut start: Option<usize> = None;
+    let mut last = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        if depth == 1 && start.is_none() && !b.is_ascii_whitespace() && b != b',' && b != b']' {
+            start = Some(i);
+        }
+        match b {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            },
+            b'[' | b'{' => depth += 1,
+            b']' | b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(s) = start.take() {
+                        spans.push((s, last + 1));
+                    }
+                    break;
+                }
+            },
+            b',' if depth == 1 => {
+                if let Some(s) = start.take() {
+                    spans.push((s, last + 1));
+                }
+            },
+            _ => ()
+        }
+        if !(bytes.get(i).is_some_and(u8::is_ascii_whitespace) || depth == 1 && b == b',') {
+            last = i;
+        }
+        i += 1;
+    }
+    // an unterminated last entry runs to the end
+    if let Some(s) = start {
+        spans.push((s, bytes.len()));
+    }
+    spans
+}
+
+/// The `InputError` for a serde error parsing `json`, a keybindings array with the comments of
+/// `content` blanked out, so offsets into both are the same and the source line shows `content`.
+pub fn json_error(content: &str, json: &str, error: &serde_json::Error) -> InputError {
+
+    // offset of the one-based line and column serde reports, which are counted in bytes
+    let line_start: usize = json.split_inclusive('\n').take(error.line().saturating_sub(1)).map(str::len).sum();
+    let offset = (line_start + error.column().saturating_sub(1)).min(json.len());
+
+    let message = error.to_string();
+    // the position is shown in the diagnostic already
+    let message = match message.rfind(" at line ") {
+        Some(n) => message[..n].to_string(),
+        None => message
+    };
+    let mut diagnostic = InputError::at(content, offset, message);
+
+    let spans = entry_spans(json);
+    if let Some((index, (start, end))) = spans.iter().enumerate().find(|(_, (s, e))| (*s..*e).contains(&offset)) {
+        diagnostic.index = Some(index);
+        diagnostic.key = serde_json::from_str::<Value>(&json[*start..*end]).ok()
+            .and_then(|entry| entry.get("key").cloned())
+            .map(|key| key.to_string());
+    }
+    diagnostic
+}
@@ -0,0 +1,117 @@
+//! Differences between two keybinding files, keyed by chord and when clause.
+
+use std::{collections::HashMap, sync::Arc};
+
+use serde_derive::Serialize;
+
+use crate::{binding::{BindingSource, KeyBinding}, key::KeyRule};
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Removed,
+    Changed
+}
+
+/// What a (chord, when) pair is bound to in the old and in the new file. A command is
+/// written with its args, e.g. `cursorEnd {"sticky":false}`, and the sources are those of
+/// the commands, in the same order.
+#[derive(Serialize)]
+pub struct BindingDiff {
+    pub change: Change,
+    #[serde(rename = "key")]
+    pub keys: KeyRule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+    #[serde(skip_serializing_if = "no_sources")]
+    pub old_sources: Vec<Option<Arc<BindingSource>>>,
+    #[serde(skip_serializing_if = "no_sources")]
+    pub new_sources: Vec<Option<Arc<BindingSource>>>
+}
+
+fn no_sources(sources: &[Option<Arc<BindingSource>>]) -> bool {
+    sources.iter().all(Option::is_none)
+}
+
+/// ` (from ...)` for a source that is not a plain file.
+fn from_text(source: Option<&Option<Arc<BindingSource>>>) -> String {
+    match source.and_then(Option::as_ref).filter(|s| !s.is_file()) {
+        Some(s) => format!("  (from {})", s),
+        None => String::new()
+    }
+}
+
+impl std::fmt::Display for BindingDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let context = match &self.when {
+            Some(w) => format!("{}  when {}", self.keys, w),
+            None => self.keys.to_string()
+        };
+        match self.change {
+            Change::Added => self.new.iter().enumerate()
+                .try_for_each(|(i, c)| writeln!(f, "+ {}  {}{}", context, c, from_text(self.new_sources.get(i)))),
+            Change::Removed => self.old.iter().enumerate()
+                .try_for_each(|(i, c)| writeln!(f, "- {}  {}{}", context, c, from_text(self.old_sources.get(i)))),
+            Change::Changed => writeln!(f, "~ {}  {} -> {}", context, self.old.join(", "), self.new.join(", "))
+        }
+    }
+}
+
+type ChordId = (KeyRule, Option<String>);
+
+fn command_text(kb: &KeyBinding) -> String {
+    match &kb.args {
+        Some(args) => format!("{} {}", kb.action, args),
+        None => kb.action.to_string()
+    }
+}
+
+type Commands = (Vec<String>, Vec<Option<Arc<BindingSource>>>);
+
+fn by_chord(bindings: &[KeyBinding]) -> (Vec<ChordId>, HashMap<ChordId, Commands>) {
+    let mut order = vec!();
+    let mut map: HashMap<ChordId, Commands> = HashMap::new();
+    for kb in bindings.iter() {
+        let id = (kb.keys.clone(), kb.when.clone());
+        let (commands, sources) = map.entry(id.clone()).or_insert_with(|| {
+            order.push(id);
+            (vec!(), vec!())
+        });
+        commands.push(command_text(kb));
+        sources.push(kb.source.clone());
+    }
+    (order, map)
+}
+
+/// Removed and changed pairs in the order of the old file, followed by the added ones
+/// in the order of the new file.
+pub fn diff_bindings(old: &[KeyBinding], new: &[KeyBinding]) -> Vec<BindingDiff> {
+
+    let (old_order, old_map) = by_chord(old);
+    let (new_order, new_map) = by_chord(new);
+
+    let mut diffs = vec!();
+
+    // the sources do not count as a change
+    for id in old_order {
+        let (before, old_sources) = old_map[&id].clone();
+        let change = match new_map.get(&id) {
+            None => Change::Removed,
+            Some((after, _)) if *after != before => Change::Changed,
+            Some(_) => continue
+        };
+        let (after, new_sources) = new_map.get(&id).cloned().unwrap_or_default();
+        diffs.push(BindingDiff { change, keys: id.0, when: id.1, old: before, new: after, old_sources, new_sources });
+    }
+
+    for id in new_order.into_iter().filter(|id| !old_map.contains_key(id)) {
+        let (after, new_sources) = new_map[&id].clone();
+        diffs.push(BindingDiff { change: Change::Added, keys: id.0, when: id.1, old: vec!(), new: after, old_sources: vec!(), new_sources });
+    }
+
+    diffs
+}
@@ -0,0 +1,107 @@
+//! Keybindings contributed by installed extensions in the `contributes.keybindings` of their `package.json`.
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use anyhow::anyhow;
+use serde_json::{Map, Value};
+
+use crate::{Result, binding::{BindingSource, ConfigItem, KeyBinding}, fetch::Platform};
+
+
+/// The field of a scanned entry naming the extension it comes from, `publisher.name`.
+pub const EXTENSION_FIELD: &str = "extension";
+
+/// An installed extension and the keybindings it contributes.
+pub struct Extension {
+    /// `publisher.name`
+    pub id: String,
+    pub version: String,
+    pub bindings: Vec<KeyBinding>
+}
+
+/// The numeric parts of a version, `1.10.2` before `1.9.0`.
+fn version_parts(version: &str) -> Vec<u64> {
+    version.split(['.', '-']).map_while(|p| p.parse().ok()).collect()
+}
+
+/// The keybindings of a `package.json` for `platform`: its `mac`, `linux` or `win` key when it
+/// has one, `key` otherwise. The entries keep the fields codekeys knows and name the extension
+/// in EXTENSION_FIELD, so they are to be written preserved.
+pub fn parse_package(content: &str, platform: Platform) -> Result<Option<Extension>> {
+
+    let package: Value = serde_json::from_str(content)?;
+    let field = |name: &str| package.get(name).and_then(Value::as_str).unwrap_or_default().to_string();
+    let (publisher, name) = (field("publisher"), field("name"));
+    if publisher.is_empty() || name.is_empty() {
+        return Ok(None);
+    }
+    let id = format!("{}.{}", publisher, name);
+
+    // a single keybinding may be written without the array
+    let entries = match package.pointer("/contributes/keybindings") {
+        Some(Value::Array(entries)) => entries.clone(),
+        Some(entry @ Value::Object(_)) => vec!(entry.clone()),
+        _ => vec!()
+    };
+    let platform_key = match platform {
+        Platform::Macos => "mac",
+        Platform::Linux => "linux",
+        Platform::Windows => "win"
+    };
+
+    let source = Some(Arc::new(BindingSource::Extension { id: id.clone() }));
+    let mut bindings = vec!();
+    for entry in entries.iter() {
+        let text = |name: &str| entry.get(name).and_then(Value::as_str).map(String::from);
+        let (Some(key), Some(command)) = (text(platform_key).or_else(|| text("key")), text("command")) else {
+            continue;
+        };
+        let item = ConfigItem { key, command, when: text("when"), args: entry.get("args").cloned() };
+        let mut original: Map<String, Value> = match serde_json::to_value(&item)? {
+            Value::Object(o) => o,
+            _ => Map::new()
+        };
+        original.insert(EXTENSION_FIELD.to_string(), Value::String(id.clone()));
+        bindings.push(KeyBinding { original: Some(Arc::new(original)), source: source.clone(), ..KeyBinding::from(item) });
+    }
+
+    Ok(Some(Extension { id, version: field("version"), bindings }))
+}
+
+/// The extensions installed in `dir` that contribute keybindings, the newest version of each,
+/// sorted by id, and the directories whose `package.json` cannot be read with the reason.
+pub fn scan_extensions(dir: &Path, platform: Platform) -> Result<(Vec<Extension>, Vec<String>)> {
+
+    let entries = std::fs::read_dir(dir).map_err(|e| anyhow!("cannot read {}: {}", dir.display(), e))?;
+    let mut newest: HashMap<String, Extension> = HashMap::new();
+    let mut unreadable = vec!();
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let package = entry.path().join("package.json");
+        if !package.is_file() {
+            continue;
+        }
+        let parsed = std::fs::read_to_string(&package).map_err(anyhow::Error::from)
+            .and_then(|content| parse_package(&content, platform));
+        match parsed {
+            Ok(Some(ext)) if !ext.bindings.is_empty() => {
+                let newer = newest.get(&ext.id).is_none_or(|old| version_parts(&ext.version) > version_parts(&old.version));
+                if newer {
+                    newest.insert(ext.id.clone(), ext);
+                }
+            },
+            Ok(_) => (),
+            Err(e) => unreadable.push(format!("{}: {}", entry.file_name().to_string_lossy(), e))
+        }
+    }
+
+    let mut extensions: Vec<Extension> = newest.into_values().collect();
+    extensions.sort_by(|a, b| a.id.cmp(&b.id));
+    unreadable.sort();
+    Ok((extensions, unreadable))
+}
+
+/// The keybindings of all `extensions` in order, as VS Code adds them after the defaults.
+pub fn extension_bindings(extensions: &[Extension]) -> Vec<KeyBinding> {
+    extensions.iter().flat_map(|e| e.bindings.iter().cloned()).collect()
+}
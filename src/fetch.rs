@@ -0,0 +1,111 @@
+//! Default keybindings of a VS Code release, fetched once and cached per platform and version.
+//!
+//! VS Code does not ship its defaults as a file, they are generated at runtime. The source is
+//! either a local export ("Preferences: Open Default Keyboard Shortcuts (JSON)") or a URL,
+//! where `{platform}` and `{version}` are replaced by the requested ones.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail};
+
+use crate::{Result, input::{InputFormat, parse_config_items}, install::write_atomic, paths::cache_dir};
+
+
+/// The platforms VS Code ships different default keybindings for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Platform {
+    Macos,
+    Linux,
+    Windows
+}
+
+impl Platform {
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Macos => "macos",
+            Platform::Linux => "linux",
+            Platform::Windows => "windows"
+        }
+    }
+
+    /// The platform codekeys runs on, Linux for the ones VS Code has no defaults of its own for.
+    pub fn current() -> Platform {
+        if cfg!(target_os = "macos") {
+            Platform::Macos
+        } else if cfg!(windows) {
+            Platform::Windows
+        } else {
+            Platform::Linux
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Platform> {
+        match name {
+            "macos" | "mac" | "darwin" => Ok(Platform::Macos),
+            "linux" => Ok(Platform::Linux),
+            "windows" | "win" => Ok(Platform::Windows),
+            _ => bail!("unknown platform '{}', expected macos, linux or windows", name)
+        }
+    }
+}
+
+fn check_version(version: &str) -> Result<()> {
+    if version.is_empty() || !version.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-') {
+        bail!("invalid version '{}'", version);
+    }
+    Ok(())
+}
+
+/// Where the defaults of `platform` and `version` are cached.
+pub fn cached_defaults_path(platform: Platform, version: &str) -> Result<PathBuf> {
+    check_version(version)?;
+    Ok(cache_dir()?.join("defaults").join(format!("{}-{}.json", platform.name(), version)))
+}
+
+/// The cached file for a `platform@version` reference like `macos@1.90`.
+pub fn resolve_cached(reference: &str) -> Result<PathBuf> {
+    let (platform, version) = reference.split_once('@')
+        .ok_or_else(|| anyhow!("expected PLATFORM@VERSION, got '{}'", reference))?;
+    let path = cached_defaults_path(Platform::from_name(platform)?, version)?;
+    if !path.exists() {
+        bail!("no cached defaults for {}, run `codekeys fetch --platform {} --version {}` first", reference, platform, version);
+    }
+    Ok(path)
+}
+
+fn read_source(source: &str) -> Result<String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        download(source)
+    } else {
+        std::fs::read_to_string(source).map_err(|e| anyhow!("cannot read {}: {}", source, e))
+    }
+}
+
+#[cfg(feature = "fetch")]
+fn download(url: &str) -> Result<String> {
+    let mut response = ureq::get(url).call().map_err(|e| anyhow!("cannot download {}: {}", url, e))?;
+    Ok(response.body_mut().read_to_string()?)
+}
+
+#[cfg(not(feature = "fetch"))]
+fn download(url: &str) -> Result<String> {
+    bail!("cannot download {}, codekeys was built without the fetch feature", url)
+}
+
+/// Reads the defaults from `source`, checks that they parse and stores them in the cache.
+/// Returns the cache path and the number of bindings.
+pub fn fetch_defaults(platform: Platform, version: &str, source: &str) -> Result<(PathBuf, usize)> {
+
+    let url = source.replace("{platform}", platform.name()).replace("{version}", version);
+    let content = read_source(&url)?;
+    let count = parse_config_items(&content, InputFormat::Json)
+        .map_err(|e| anyhow!("{} is not a keybindings file: {}", url, e))?
+        .len();
+
+    // a failed fetch never leaves half a file behind
+    let path = cached_defaults_path(platform, version)?;
+    write_atomic(&path, &content)?;
+
+    Ok((path, count))
+}
@@ -0,0 +1,118 @@
+//! The `[keys]` section of a Helix `config.toml`: a table per mode, chords as nested tables.
+
+use toml::{Table, Value};
+
+use crate::{
+    binding::{BindingAction, KeyBinding},
+    format::zed::zed_context,
+    key::{Key, KeyCode, NamedKey, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION, from_physical}
+};
+
+
+/// VS Code keycaps Helix spells differently.
+const KEY_NAMES: &[(NamedKey, &str)] = &[
+    (NamedKey::Enter, "ret"), (NamedKey::Escape, "esc"), (NamedKey::Delete, "del"), (NamedKey::Insert, "ins"),
+    (NamedKey::Minus, "minus"),
+];
+
+/// A key in Helix's notation, `C-S-k`, shifted letters are written upper case.
+pub fn to_helix_key(key: &Key) -> String {
+
+    let key = from_physical(key);
+    let mut modifiers = key.modifiers;
+    let mut name = match KEY_NAMES.iter().find(|(k, _)| key.code == KeyCode::Named(*k)) {
+        Some((_, h)) => h.to_string(),
+        None => key.code.to_string()
+    };
+    if let (KeyCode::Letter(c), true) = (&key.code, modifiers & MODIFIER_SHIFT != 0) {
+        name = c.to_ascii_uppercase().to_string();
+        modifiers &= !MODIFIER_SHIFT;
+    }
+
+    let mut out = String::new();
+    for (m, prefix) in [(MODIFIER_COMMAND, "Meta-"), (MODIFIER_CONTROL, "C-"), (MODIFIER_OPTION, "A-"), (MODIFIER_SHIFT, "S-")] {
+        if modifiers & m != 0 {
+            out += prefix;
+        }
+    }
+    out + &name
+}
+
+/// Binds `keys` to a command or a sequence of them in a mode table, creating the tables of the
+/// chord prefixes on the way. Fails if a prefix is already bound to a command.
+fn insert_chord(table: &mut Table, keys: &[String], command: &Value) -> Result<(), String> {
+    match keys {
+        [] => Ok(()),
+        [last] => match table.get(last) {
+            Some(Value::Table(_)) => Err(format!("{} already starts a chord", last)),
+            _ => {
+                table.insert(last.clone(), command.clone());
+                Ok(())
+            }
+        },
+        [prefix, rest @ ..] => {
+            let entry = table.entry(prefix.clone()).or_insert_with(|| Value::Table(Table::new()));
+            match entry {
+                Value::Table(t) => insert_chord(t, rest, command),
+                _ => Err(format!("{} is already bound to a command", prefix))
+            }
+        }
+    }
+}
+
+/// The `keys` table of a Helix config for `bindings`, along with the bindings left out and why.
+/// Bindings for the editor (or without a when clause) go to normal mode, those with ctrl, alt or
+/// meta to insert mode as well. Macros are written as a list of their commands. Disables and args
+/// are left out, Helix commands take none, and so are macros with steps that have args.
+pub fn to_helix(bindings: &[KeyBinding]) -> (Table, Vec<(usize, String)>) {
+
+    let mut normal = Table::new();
+    let mut insert = Table::new();
+    let mut skipped = vec!();
+
+    for (i, kb) in bindings.iter().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        if kb.keys.keys().any(|k| k.pointer().is_some()) {
+            skipped.push((i, "Helix has no mouse bindings".to_string()));
+            continue;
+        }
+        match zed_context(kb.when.as_deref()) {
+            Ok(None) => (),
+            Ok(Some(c)) if c.starts_with("Editor") => (),
+            Ok(Some(c)) => {
+                skipped.push((i, format!("Helix has no mode for {}", c)));
+                continue;
+            },
+            Err(e) => {
+                skipped.push((i, e.to_string()));
+                continue;
+            }
+        }
+
+        let command = match &kb.action {
+            BindingAction::Macro(steps) if steps.iter().any(|s| s.args.is_some()) => {
+                skipped.push((i, "Helix commands take no args, the macro has steps with args".to_string()));
+                continue;
+            },
+            BindingAction::Macro(steps) => Value::Array(steps.iter().map(|s| Value::String(s.command.clone())).collect()),
+            _ => Value::String(kb.command().to_string())
+        };
+
+        let keys: Vec<String> = kb.keys.keys().map(to_helix_key).collect();
+        if let Err(e) = insert_chord(&mut normal, &keys, &command) {
+            skipped.push((i, e));
+            continue;
+        }
+        if kb.has_modifier(MODIFIER_CONTROL | MODIFIER_OPTION | MODIFIER_COMMAND) {
+            if let Err(e) = insert_chord(&mut insert, &keys, &command) {
+                skipped.push((i, e));
+            }
+        }
+    }
+
+    let mut modes = Table::new();
+    modes.insert("normal".to_string(), Value::Table(normal));
+    modes.insert("insert".to_string(), Value::Table(insert));
+    let mut keys = Table::new();
+    keys.insert("keys".to_string(), Value::Table(modes));
+    (keys, skipped)
+}
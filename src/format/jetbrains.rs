@@ -0,0 +1,195 @@
+//! IntelliJ IDEA `keymap.xml` files: actions with up to two keystrokes in Java's
+//! `KeyStroke` notation (`shift ctrl K`), and no contexts.
+
+use anyhow::anyhow;
+use quick_xml::{Reader, XmlVersion, events::Event};
+
+use crate::{
+    Result,
+    binding::{ConfigItem, KeyBinding},
+    key::{Key, KeyCode, KeyRule, NamedKey, POINTER_NAMES, Pointer, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION, from_physical}
+};
+
+
+/// VS Code keycaps with their Java key name, letters, digits and function keys are just upper case.
+const KEY_NAMES: &[(NamedKey, &str)] = &[
+    (NamedKey::Enter, "ENTER"), (NamedKey::Escape, "ESCAPE"), (NamedKey::Tab, "TAB"), (NamedKey::Space, "SPACE"),
+    (NamedKey::Backspace, "BACK_SPACE"), (NamedKey::Delete, "DELETE"), (NamedKey::Insert, "INSERT"),
+    (NamedKey::Home, "HOME"), (NamedKey::End, "END"), (NamedKey::PageUp, "PAGE_UP"), (NamedKey::PageDown, "PAGE_DOWN"),
+    (NamedKey::Up, "UP"), (NamedKey::Down, "DOWN"), (NamedKey::Left, "LEFT"), (NamedKey::Right, "RIGHT"),
+    (NamedKey::Backquote, "BACK_QUOTE"), (NamedKey::Minus, "MINUS"), (NamedKey::Equal, "EQUALS"),
+    (NamedKey::BracketLeft, "OPEN_BRACKET"), (NamedKey::BracketRight, "CLOSE_BRACKET"),
+    (NamedKey::Backslash, "BACK_SLASH"), (NamedKey::Semicolon, "SEMICOLON"), (NamedKey::Quote, "QUOTE"),
+    (NamedKey::Comma, "COMMA"), (NamedKey::Period, "PERIOD"), (NamedKey::Slash, "SLASH"), (NamedKey::NumpadAdd, "ADD"),
+    (NamedKey::NumpadSubtract, "SUBTRACT"), (NamedKey::NumpadMultiply, "MULTIPLY"), (NamedKey::NumpadDivide, "DIVIDE"),
+    (NamedKey::NumpadDecimal, "DECIMAL"),
+];
+
+/// Mouse buttons numbered the codekeys way (1 left, 2 right, 3 middle) with Java's number.
+const MOUSE_BUTTONS: &[(u8, u8)] = &[(1, 1), (2, 3), (3, 2), (4, 4), (5, 5)];
+
+/// Modifiers in the order IDEA writes them.
+const MODIFIERS: &[(usize, &str)] = &[
+    (MODIFIER_SHIFT, "shift"), (MODIFIER_CONTROL, "ctrl"), (MODIFIER_COMMAND, "meta"), (MODIFIER_OPTION, "alt"),
+];
+
+/// Parses a keystroke like `shift ctrl K` or `meta BACK_SPACE`.
+pub fn parse_keystroke(stroke: &str) -> Key {
+
+    let mut modifiers = 0;
+    let mut name = "";
+    for part in stroke.split_whitespace() {
+        match part {
+            "shift" => modifiers |= MODIFIER_SHIFT,
+            "ctrl" | "control" => modifiers |= MODIFIER_CONTROL,
+            "meta" => modifiers |= MODIFIER_COMMAND,
+            "alt" | "altGraph" => modifiers |= MODIFIER_OPTION,
+            "pressed" | "released" | "typed" => (),
+            p => name = p
+        }
+    }
+
+    let code = match KEY_NAMES.iter().find(|(_, j)| *j == name) {
+        Some((k, _)) => KeyCode::Named(*k),
+        None => match name.strip_prefix("NUMPAD") {
+            Some(digit) => KeyCode::parse(&format!("numpad{}", digit)),
+            None => KeyCode::parse(&name.to_lowercase())
+        }
+    };
+    Key { modifiers, code }
+}
+
+/// The key of a mouse shortcut like `ctrl button1`, None for one with a click count or
+/// a button that has no key name.
+pub fn parse_mouse_keystroke(stroke: &str) -> Option<Key> {
+    let parts: Vec<&str> = stroke.split_whitespace().collect();
+    let (button, modifiers) = parts.split_last()?;
+    let java: u8 = button.strip_prefix("button")?.parse().ok()?;
+    let (n, _) = MOUSE_BUTTONS.iter().find(|(_, j)| *j == java)?;
+    let (_, pointer) = POINTER_NAMES.iter().find(|(_, p)| *p == Pointer::Button(*n))?;
+    Some(Key { modifiers: parse_keystroke(&modifiers.join(" ")).modifiers, code: KeyCode::Pointer(*pointer) })
+}
+
+/// The keystroke of a key in IDEA's notation, a mouse button as `button1`.
+pub fn to_keystroke(key: &Key) -> String {
+
+    let key = from_physical(key);
+    let name = match (KEY_NAMES.iter().find(|(k, _)| key.code == KeyCode::Named(*k)), &key.code) {
+        (Some((_, j)), _) => j.to_string(),
+        (None, KeyCode::Pointer(Pointer::Button(n))) => {
+            let java = MOUSE_BUTTONS.iter().find(|(b, _)| b == n).map_or(*n, |(_, j)| *j);
+            format!("button{}", java)
+        },
+        (None, code) => code.to_string().to_uppercase()
+    };
+
+    let mut parts: Vec<&str> = MODIFIERS.iter()
+        .filter(|(m, _)| key.modifiers & m != 0)
+        .map(|(_, n)| *n)
+        .collect();
+    parts.push(&name);
+    parts.join(" ")
+}
+
+/// Reads the keyboard and mouse shortcuts of a `keymap.xml`, one entry per shortcut with the
+/// action id as the command. Double clicks, wheel shortcuts and actions without shortcuts are skipped.
+pub fn parse_jetbrains_items(content: &str) -> Result<Vec<ConfigItem>> {
+
+    let mut reader = Reader::from_str(content);
+    let mut items = vec!();
+    let mut action: Option<String> = None;
+
+    loop {
+        let event = reader.read_event().map_err(|e| anyhow!("not an IDEA keymap: {}", e))?;
+        match &event {
+            Event::Start(e) | Event::Empty(e) => {
+                let attr = |name: &str| -> Result<Option<String>> {
+                    match e.try_get_attribute(name).map_err(|e| anyhow!("not an IDEA keymap: {}", e))? {
+                        Some(a) => Ok(Some(a.normalized_value(XmlVersion::Implicit1_0)?.into_owned())),
+                        None => Ok(None)
+                    }
+                };
+                match e.name().as_ref() {
+                    "action" => action = attr("id")?,
+                    "keyboard-shortcut" => {
+                        let command = action.clone().ok_or_else(|| anyhow!("keyboard-shortcut outside of an action"))?;
+                        let first = attr("first-keystroke")?.ok_or_else(|| anyhow!("keyboard-shortcut of {} has no first-keystroke", command))?;
+                        let keys = KeyRule {
+                            first: parse_keystroke(&first),
+                            rest: attr("second-keystroke")?.iter().map(|s| parse_keystroke(s)).collect()
+                        };
+                        items.push(ConfigItem { key: keys.to_string(), command, when: None, args: None });
+                    },
+                    "mouse-shortcut" => {
+                        let command = action.clone().ok_or_else(|| anyhow!("mouse-shortcut outside of an action"))?;
+                        if let Some(key) = attr("keystroke")?.as_deref().and_then(parse_mouse_keystroke) {
+                            items.push(ConfigItem { key: key.to_string(), command, when: None, args: None });
+                        }
+                    },
+                    _ => ()
+                }
+            },
+            Event::End(e) if e.name().as_ref() == "action" => action = None,
+            Event::Eof => break,
+            _ => ()
+        }
+    }
+
+    Ok(items)
+}
+
+fn escape_attr(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A `keymap.xml` named `name` with an action per command and a shortcut per chord, along with
+/// the bindings left out and why: IDEA takes at most two keystrokes, a mouse shortcut is a
+/// single click and a shortcut runs one action, not a macro. Disables are left out and when clauses are dropped, IDEA has no contexts.
+pub fn to_jetbrains(bindings: &[KeyBinding], name: &str) -> (String, Vec<(usize, String)>) {
+
+    let mut actions: Vec<(&str, Vec<String>)> = vec!();
+    let mut skipped = vec!();
+
+    for (i, kb) in bindings.iter().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        if kb.action.is_macro() {
+            skipped.push((i, "IDEA shortcuts run a single action".to_string()));
+            continue;
+        }
+        let pointer = kb.keys.keys().find_map(Key::pointer);
+        let shortcut = match (kb.keys.rest.as_slice(), pointer) {
+            (_, Some(p)) if p.is_wheel() => {
+                skipped.push((i, "wheel shortcuts are not written".to_string()));
+                continue;
+            },
+            ([], Some(_)) => format!("<mouse-shortcut keystroke=\"{}\" />", escape_attr(&to_keystroke(&kb.keys.first))),
+            (_, Some(_)) => {
+                skipped.push((i, "a mouse shortcut is a single click".to_string()));
+                continue;
+            },
+            ([], None) => format!("<keyboard-shortcut first-keystroke=\"{}\" />", escape_attr(&to_keystroke(&kb.keys.first))),
+            ([second], None) => format!("<keyboard-shortcut first-keystroke=\"{}\" second-keystroke=\"{}\" />",
+                escape_attr(&to_keystroke(&kb.keys.first)), escape_attr(&to_keystroke(second))),
+            _ => {
+                skipped.push((i, "IDEA takes at most two keystrokes".to_string()));
+                continue;
+            }
+        };
+        match actions.iter_mut().find(|(c, _)| *c == kb.command()) {
+            Some((_, shortcuts)) if shortcuts.contains(&shortcut) => (),
+            Some((_, shortcuts)) => shortcuts.push(shortcut),
+            None => actions.push((kb.command(), vec!(shortcut)))
+        }
+    }
+
+    let mut xml = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<keymap version=\"1\" name=\"{}\" parent=\"$default\">\n", escape_attr(name));
+    for (command, shortcuts) in actions.iter() {
+        xml += &format!("  <action id=\"{}\">\n", escape_attr(command));
+        for s in shortcuts.iter() {
+            xml += &format!("    {}\n", s);
+        }
+        xml += "  </action>\n";
+    }
+    xml += "</keymap>\n";
+
+    (xml, skipped)
+}
@@ -0,0 +1,8 @@
+//! Keymap formats of other editors.
+
+pub mod helix;
+pub mod jetbrains;
+pub mod os;
+pub mod sublime;
+pub mod terminal;
+pub mod zed;
@@ -0,0 +1,234 @@
+//! OS level remaps for Windows and Linux: an AutoHotkey v2 script, a keyd config and an xremap
+//! keymap that make the keys a keymap binds to copy, paste, save and the like send the shortcut
+//! those commands have everywhere else on the system, so other applications follow the same
+//! modifier conventions as the editor.
+
+use serde_json::{Map, Value};
+
+use crate::{
+    binding::KeyBinding,
+    key::{Key, KeyCode, NamedKey, Pointer, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION, from_physical, parse_one_key}
+};
+
+
+/// A key remapper an OS level keymap is written for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Remapper {
+    /// AutoHotkey v2 on Windows
+    Autohotkey,
+    /// keyd on Linux
+    Keyd,
+    /// xremap on Linux
+    Xremap
+}
+
+/// A VS Code command with the shortcut doing the same on Windows and on Linux.
+const SHORTCUTS: &[(&str, &str, &str)] = &[
+    ("editor.action.clipboardCopyAction", "ctrl+c", "ctrl+c"),
+    ("editor.action.clipboardCutAction", "ctrl+x", "ctrl+x"),
+    ("editor.action.clipboardPasteAction", "ctrl+v", "ctrl+v"),
+    ("undo", "ctrl+z", "ctrl+z"),
+    ("redo", "ctrl+y", "ctrl+shift+z"),
+    ("editor.action.selectAll", "ctrl+a", "ctrl+a"),
+    ("workbench.action.files.save", "ctrl+s", "ctrl+s"),
+    ("workbench.action.files.openFile", "ctrl+o", "ctrl+o"),
+    ("workbench.action.files.newUntitledFile", "ctrl+n", "ctrl+n"),
+    ("workbench.action.newWindow", "ctrl+shift+n", "ctrl+shift+n"),
+    ("actions.find", "ctrl+f", "ctrl+f"),
+    ("editor.action.startFindReplaceAction", "ctrl+h", "ctrl+h"),
+    ("workbench.action.closeActiveEditor", "ctrl+w", "ctrl+w"),
+    ("workbench.action.reopenClosedEditor", "ctrl+shift+t", "ctrl+shift+t"),
+    ("workbench.action.nextEditor", "ctrl+tab", "ctrl+tab"),
+    ("workbench.action.previousEditor", "ctrl+shift+tab", "ctrl+shift+tab"),
+    ("workbench.action.zoomIn", "ctrl+=", "ctrl+="),
+    ("workbench.action.zoomOut", "ctrl+-", "ctrl+-"),
+    ("workbench.action.zoomReset", "ctrl+0", "ctrl+0"),
+    ("workbench.action.quit", "alt+f4", "ctrl+q"),
+    ("cursorHome", "home", "home"),
+    ("cursorEnd", "end", "end"),
+    ("cursorTop", "ctrl+home", "ctrl+home"),
+    ("cursorBottom", "ctrl+end", "ctrl+end"),
+    ("cursorWordLeft", "ctrl+left", "ctrl+left"),
+    ("cursorWordRight", "ctrl+right", "ctrl+right"),
+    ("deleteWordLeft", "ctrl+backspace", "ctrl+backspace"),
+    ("deleteWordRight", "ctrl+delete", "ctrl+delete"),
+];
+
+/// VS Code key names with the AutoHotkey and the evdev spelling keyd and xremap use, empty
+/// where the remapper cannot bind the key. Letters, digits and function keys are the same in
+/// all of them, AutoHotkey writing the function keys upper case.
+const KEY_NAMES: &[(KeyCode, &str, &str)] = &[
+    (KeyCode::Named(NamedKey::Enter), "Enter", "enter"), (KeyCode::Named(NamedKey::Escape), "Escape", "esc"),
+    (KeyCode::Named(NamedKey::Tab), "Tab", "tab"), (KeyCode::Named(NamedKey::Space), "Space", "space"),
+    (KeyCode::Named(NamedKey::Backspace), "Backspace", "backspace"),
+    (KeyCode::Named(NamedKey::Delete), "Delete", "delete"), (KeyCode::Named(NamedKey::Insert), "Insert", "insert"),
+    (KeyCode::Named(NamedKey::Home), "Home", "home"), (KeyCode::Named(NamedKey::End), "End", "end"),
+    (KeyCode::Named(NamedKey::PageUp), "PgUp", "pageup"), (KeyCode::Named(NamedKey::PageDown), "PgDn", "pagedown"),
+    (KeyCode::Named(NamedKey::Up), "Up", "up"), (KeyCode::Named(NamedKey::Down), "Down", "down"),
+    (KeyCode::Named(NamedKey::Left), "Left", "left"), (KeyCode::Named(NamedKey::Right), "Right", "right"),
+    (KeyCode::Named(NamedKey::Equal), "=", "equal"), (KeyCode::Named(NamedKey::Minus), "-", "minus"),
+    (KeyCode::Named(NamedKey::Comma), ",", "comma"), (KeyCode::Named(NamedKey::Period), ".", "dot"),
+    (KeyCode::Named(NamedKey::Slash), "/", "slash"), (KeyCode::Named(NamedKey::Backslash), "\\", "backslash"),
+    (KeyCode::Named(NamedKey::Semicolon), ";", "semicolon"), (KeyCode::Named(NamedKey::Quote), "'", "apostrophe"),
+    (KeyCode::Named(NamedKey::BracketLeft), "[", "leftbrace"),
+    (KeyCode::Named(NamedKey::BracketRight), "]", "rightbrace"), (KeyCode::Named(NamedKey::Backquote), "`", "grave"),
+    (KeyCode::Numpad(0), "Numpad0", "kp0"), (KeyCode::Numpad(1), "Numpad1", "kp1"),
+    (KeyCode::Numpad(2), "Numpad2", "kp2"), (KeyCode::Numpad(3), "Numpad3", "kp3"),
+    (KeyCode::Numpad(4), "Numpad4", "kp4"), (KeyCode::Numpad(5), "Numpad5", "kp5"),
+    (KeyCode::Numpad(6), "Numpad6", "kp6"), (KeyCode::Numpad(7), "Numpad7", "kp7"),
+    (KeyCode::Numpad(8), "Numpad8", "kp8"), (KeyCode::Numpad(9), "Numpad9", "kp9"),
+    (KeyCode::Named(NamedKey::NumpadAdd), "NumpadAdd", "kpplus"),
+    (KeyCode::Named(NamedKey::NumpadSubtract), "NumpadSub", "kpminus"),
+    (KeyCode::Named(NamedKey::NumpadMultiply), "NumpadMult", "kpasterisk"),
+    (KeyCode::Named(NamedKey::NumpadDivide), "NumpadDiv", "kpslash"),
+    (KeyCode::Named(NamedKey::NumpadDecimal), "NumpadDot", "kpdot"),
+];
+
+/// Mouse buttons and wheel directions in AutoHotkey, keyd and xremap remap keys only.
+const POINTERS: &[(Pointer, &str)] = &[
+    (Pointer::Button(1), "LButton"), (Pointer::Button(2), "RButton"), (Pointer::Button(3), "MButton"),
+    (Pointer::Button(4), "XButton1"), (Pointer::Button(5), "XButton2"),
+    (Pointer::WheelUp, "WheelUp"), (Pointer::WheelDown, "WheelDown"),
+    (Pointer::WheelLeft, "WheelLeft"), (Pointer::WheelRight, "WheelRight"),
+];
+
+impl Remapper {
+    /// The shortcut `command` has on the remapper's platform, None for commands without one.
+    pub fn shortcut(self, command: &str) -> Option<Key> {
+        let (_, windows, linux) = SHORTCUTS.iter().find(|(c, ..)| *c == command)?;
+        Some(parse_one_key(if self == Remapper::Autohotkey { windows } else { linux }))
+    }
+
+    /// The name of the key, without its modifiers, in the remapper's spelling.
+    fn key_name(self, key: &Key) -> Option<String> {
+        let name = match (KEY_NAMES.iter().find(|(k, ..)| *k == key.code), &key.code) {
+            (Some((_, ahk, evdev)), _) => if self == Remapper::Autohotkey { *ahk } else { *evdev }.to_string(),
+            (None, c @ (KeyCode::Letter(_) | KeyCode::Digit(_))) => c.to_string(),
+            (None, c @ KeyCode::Function(_)) if self == Remapper::Autohotkey => c.to_string().to_uppercase(),
+            (None, c @ KeyCode::Function(_)) => c.to_string(),
+            (None, KeyCode::Pointer(p)) if self == Remapper::Autohotkey => POINTERS.iter().find(|(q, _)| q == p).map(|(_, n)| n.to_string())?,
+            _ => return None
+        };
+        Some(name).filter(|n| !n.is_empty())
+    }
+
+    /// The keys as the remapper writes what is pressed.
+    fn trigger(self, key: &Key) -> Option<String> {
+        let name = self.key_name(key)?;
+        Some(match self {
+            Remapper::Autohotkey => ahk_modifiers(key.modifiers) + match name.as_str() {
+                ";" => "`;",
+                "`" => "``",
+                n => n
+            },
+            Remapper::Keyd => name,
+            Remapper::Xremap => xremap_keys(key.modifiers, &name)
+        })
+    }
+
+    /// The keys as the remapper writes what is sent instead.
+    fn output(self, key: &Key) -> Option<String> {
+        let name = self.key_name(key)?;
+        Some(match self {
+            Remapper::Autohotkey if name.len() > 1 => format!("{}{{{}}}", ahk_modifiers(key.modifiers), name),
+            Remapper::Autohotkey if "+^!#{}".contains(name.as_str()) => format!("{}{{{}}}", ahk_modifiers(key.modifiers), name),
+            Remapper::Autohotkey if name == "`" => ahk_modifiers(key.modifiers) + "``",
+            Remapper::Autohotkey => ahk_modifiers(key.modifiers) + &name,
+            Remapper::Keyd => [(MODIFIER_CONTROL, "C-"), (MODIFIER_OPTION, "A-"), (MODIFIER_SHIFT, "S-"), (MODIFIER_COMMAND, "M-")].iter()
+                .filter(|(m, _)| key.modifiers & m != 0)
+                .map(|(_, p)| *p)
+                .collect::<String>() + &name,
+            Remapper::Xremap => xremap_keys(key.modifiers, &name)
+        })
+    }
+}
+
+/// The AutoHotkey prefix of the modifiers, `#^+` for meta+ctrl+shift.
+fn ahk_modifiers(modifiers: usize) -> String {
+    [(MODIFIER_COMMAND, '#'), (MODIFIER_CONTROL, '^'), (MODIFIER_OPTION, '!'), (MODIFIER_SHIFT, '+')].iter()
+        .filter(|(m, _)| modifiers & m != 0)
+        .map(|(_, c)| *c)
+        .collect()
+}
+
+fn xremap_keys(modifiers: usize, name: &str) -> String {
+    [(MODIFIER_CONTROL, "C-"), (MODIFIER_OPTION, "M-"), (MODIFIER_SHIFT, "Shift-"), (MODIFIER_COMMAND, "Super-")].iter()
+        .filter(|(m, _)| modifiers & m != 0)
+        .map(|(_, p)| *p)
+        .collect::<String>() + name
+}
+
+/// The keyd layer a key with `modifiers` is pressed in, `main` without any.
+fn keyd_layer(modifiers: usize) -> String {
+    let layers: Vec<&str> = [(MODIFIER_CONTROL, "control"), (MODIFIER_OPTION, "alt"), (MODIFIER_SHIFT, "shift"), (MODIFIER_COMMAND, "meta")].iter()
+        .filter(|(m, _)| modifiers & m != 0)
+        .map(|(_, l)| *l)
+        .collect();
+    match layers.is_empty() {
+        true => "main".to_string(),
+        false => layers.join("+")
+    }
+}
+
+/// The remaps of `remapper` from the keys bound to a command with a system wide shortcut to that
+/// shortcut, along with the bindings of such commands that are left out and why. Bindings on the
+/// shortcut itself need no remap, when clauses are dropped and disables are left out.
+pub fn to_os_remap(bindings: &[KeyBinding], remapper: Remapper) -> (String, Vec<(usize, String)>) {
+
+    let mut remaps: Vec<(Key, Key)> = vec!();
+    let mut skipped = vec!();
+
+    for (i, kb) in bindings.iter().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        let Some(shortcut) = remapper.shortcut(kb.command()) else { continue };
+        if kb.keys.is_chord() {
+            skipped.push((i, "chords are not remapped system wide".to_string()));
+            continue;
+        }
+        let key = from_physical(&kb.keys.first);
+        if key == shortcut {
+            continue;
+        }
+        if key.pointer().is_some() && remapper != Remapper::Autohotkey {
+            skipped.push((i, "the remapper binds keys of the keyboard only".to_string()));
+            continue;
+        }
+        if remapper.trigger(&key).is_none() || remapper.output(&shortcut).is_none() {
+            skipped.push((i, "the remapper has no name for the key".to_string()));
+            continue;
+        }
+        // the same command is often bound once per context, the remap is needed once
+        match remaps.iter().find(|(k, _)| *k == key) {
+            Some((_, to)) if *to == shortcut => (),
+            Some((_, to)) => skipped.push((i, format!("the keys send {} already", to))),
+            None => remaps.push((key, shortcut))
+        }
+    }
+
+    let line = |(from, to): &(Key, Key)| (remapper.trigger(from).unwrap_or_default(), remapper.output(to).unwrap_or_default());
+    let text = match remapper {
+        Remapper::Autohotkey => {
+            let lines: Vec<String> = remaps.iter().map(line).map(|(from, to)| format!("{}::Send \"{}\"", from, to)).collect();
+            format!("#Requires AutoHotkey v2.0\n\n{}", lines.join("\n"))
+        },
+        Remapper::Keyd => {
+            // a section per layer, in the order they are first needed
+            let mut layers: Vec<(String, Vec<String>)> = vec!();
+            for remap in remaps.iter() {
+                let layer = keyd_layer(remap.0.modifiers);
+                let (from, to) = line(remap);
+                match layers.iter_mut().find(|(l, _)| *l == layer) {
+                    Some((_, lines)) => lines.push(format!("{} = {}", from, to)),
+                    None => layers.push((layer, vec!(format!("{} = {}", from, to))))
+                }
+            }
+            let sections: Vec<String> = layers.iter().map(|(l, lines)| format!("[{}]\n\n{}", l, lines.join("\n"))).collect();
+            format!("[ids]\n\n*\n\n{}", sections.join("\n\n"))
+        },
+        Remapper::Xremap => {
+            let remap: Map<String, Value> = remaps.iter().map(line).map(|(from, to)| (from, Value::String(to))).collect();
+            let config = serde_json::json!({ "keymap": [{ "name": "codekeys", "remap": remap }] });
+            serde_yaml::to_string(&config).unwrap_or_default().trim_end().to_string()
+        }
+    };
+    (text, skipped)
+}
@@ -0,0 +1,192 @@
+//! Sublime Text `.sublime-keymap` files: `keys` lists instead of chord strings and a `context`
+//! list of conditions instead of a when clause.
+
+use anyhow::{anyhow, bail};
+use serde_derive::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{
+    Result,
+    binding::{ConfigItem, KeyBinding},
+    input::strip_jsonc,
+    key::{Key, KeyCode, NamedKey, from_physical, parse_one_key},
+    when::{When, parse_when}
+};
+
+
+/// An entry of a `.sublime-keymap` file.
+#[derive(Serialize, Deserialize)]
+pub struct SublimeBinding {
+    pub keys: Vec<String>,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<SublimeContext>
+}
+
+/// A condition of a binding's context, `operator` defaults to `equal` and `operand` to `true`.
+#[derive(Serialize, Deserialize)]
+pub struct SublimeContext {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operand: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_all: Option<bool>
+}
+
+/// Sublime key names that VS Code spells differently.
+const KEY_NAMES: &[(&str, NamedKey)] = &[
+    ("keypad_plus", NamedKey::NumpadAdd), ("keypad_minus", NamedKey::NumpadSubtract),
+    ("keypad_multiply", NamedKey::NumpadMultiply), ("keypad_divide", NamedKey::NumpadDivide),
+    ("keypad_period", NamedKey::NumpadDecimal),
+];
+
+fn from_sublime_key(key: &str) -> String {
+    // `primary` is ctrl on Linux and Windows, the keymap is written for those
+    let mut k = parse_one_key(&key.replace("primary+", "ctrl+"));
+    let name = k.code.to_string();
+    k.code = match KEY_NAMES.iter().find(|(s, _)| *s == name) {
+        Some((_, v)) => KeyCode::Named(*v),
+        None => match name.strip_prefix("keypad") {
+            Some(digit) if digit.len() == 1 => KeyCode::parse(&format!("numpad{}", digit)),
+            _ => k.code
+        }
+    };
+    k.to_string()
+}
+
+fn to_sublime_key(key: &Key) -> String {
+    // the Sublime names are keys VS Code does not know
+    let mut k = from_physical(key);
+    k.code = match (KEY_NAMES.iter().find(|(_, v)| k.code == KeyCode::Named(*v)), k.code) {
+        (Some((s, _)), _) => KeyCode::Other(s.to_string()),
+        (None, KeyCode::Numpad(digit)) => KeyCode::Other(format!("keypad{}", digit)),
+        (_, code) => code
+    };
+    k.to_string().replacen("meta+", "super+", 1)
+}
+
+fn operand_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string()
+    }
+}
+
+/// The when clause of a context, its conditions joined with `&&`.
+pub fn context_to_when(context: &[SublimeContext]) -> Result<When> {
+
+    let mut terms = vec!();
+    for c in context.iter() {
+        let key = c.key.clone();
+        let operand = c.operand.clone().unwrap_or(Value::Bool(true));
+        let regex = || operand_text(&operand).replace('/', "\\/");
+        let term = match (c.operator.as_deref().unwrap_or("equal"), &operand) {
+            ("equal", Value::Bool(true)) | ("not_equal", Value::Bool(false)) => When::Key(key),
+            ("equal", Value::Bool(false)) | ("not_equal", Value::Bool(true)) => When::Not(Box::new(When::Key(key))),
+            ("equal", v) => When::Equals(key, operand_text(v)),
+            ("not_equal", v) => When::NotEquals(key, operand_text(v)),
+            ("regex_contains", _) => When::Matches(key, regex(), String::new()),
+            ("regex_match", _) => When::Matches(key, format!("^({})$", regex()), String::new()),
+            ("not_regex_contains", _) => When::Not(Box::new(When::Matches(key, regex(), String::new()))),
+            ("not_regex_match", _) => When::Not(Box::new(When::Matches(key, format!("^({})$", regex()), String::new()))),
+            (op, _) => bail!("context operator '{}' of {} has no when clause equivalent", op, c.key)
+        };
+        terms.push(term);
+    }
+
+    Ok(match terms.len() {
+        0 => When::True,
+        1 => terms.remove(0),
+        _ => When::And(terms)
+    })
+}
+
+fn to_context(term: &When) -> Option<SublimeContext> {
+    let context = |key: &str, operator: Option<&str>, operand: Option<Value>| SublimeContext {
+        key: key.to_string(),
+        operator: operator.map(String::from),
+        operand,
+        match_all: None
+    };
+    match term {
+        When::Key(k) => Some(context(k, None, None)),
+        When::Equals(k, v) => Some(context(k, Some("equal"), Some(Value::String(v.clone())))),
+        When::NotEquals(k, v) => Some(context(k, Some("not_equal"), Some(Value::String(v.clone())))),
+        When::Matches(k, p, flags) if flags.is_empty() => Some(context(k, Some("regex_contains"), Some(Value::String(p.replace("\\/", "/"))))),
+        When::Not(inner) => match inner.as_ref() {
+            When::Key(k) => Some(context(k, Some("equal"), Some(Value::Bool(false)))),
+            When::Matches(k, p, flags) if flags.is_empty() => Some(context(k, Some("not_regex_contains"), Some(Value::String(p.replace("\\/", "/"))))),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// The context list of a when clause, None if it is not a plain `&&` of conditions Sublime has.
+pub fn when_to_context(when: &str) -> Option<Vec<SublimeContext>> {
+    match parse_when(when).ok()? {
+        When::True => Some(vec!()),
+        When::And(terms) => terms.iter().map(to_context).collect(),
+        term => Some(vec!(to_context(&term)?))
+    }
+}
+
+/// Reads the entries of a `.sublime-keymap` file, comments and trailing commas are allowed.
+pub fn parse_sublime_items(content: &str) -> Result<Vec<ConfigItem>> {
+
+    let entries: Vec<SublimeBinding> = serde_json::from_str(&strip_jsonc(content))
+        .map_err(|e| anyhow!("not a Sublime Text keymap: {}", e))?;
+
+    entries.into_iter().map(|e| {
+        let when = context_to_when(&e.context)?;
+        Ok(ConfigItem {
+            key: e.keys.iter().map(|k| from_sublime_key(k)).collect::<Vec<_>>().join(" "),
+            command: e.command,
+            when: if when == When::True { None } else { Some(when.to_string()) },
+            args: e.args
+        })
+    }).collect()
+}
+
+/// The Sublime entries of `bindings`, along with the bindings left out and why: when clauses
+/// without a context equivalent, mouse keys, which go to a `.sublime-mousemap`, and macros, Sublime
+/// binds one command to a key. Disables are left out as well, in Sublime a later entry on the
+/// same keys wins anyway.
+pub fn to_sublime(bindings: &[KeyBinding]) -> (Vec<SublimeBinding>, Vec<(usize, String)>) {
+
+    let mut entries = vec!();
+    let mut skipped = vec!();
+
+    for (i, kb) in bindings.iter().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        if kb.keys.keys().any(|k| k.pointer().is_some()) {
+            skipped.push((i, "Sublime binds the mouse in a .sublime-mousemap".to_string()));
+            continue;
+        }
+        if kb.action.is_macro() {
+            skipped.push((i, "Sublime has no command sequences".to_string()));
+            continue;
+        }
+        let context = match &kb.when {
+            Some(w) => match when_to_context(w) {
+                Some(c) => c,
+                None => {
+                    skipped.push((i, "when clause has no Sublime context".to_string()));
+                    continue;
+                }
+            },
+            None => vec!()
+        };
+        entries.push(SublimeBinding {
+            keys: kb.keys.keys().map(to_sublime_key).collect(),
+            command: kb.command().to_string(),
+            args: kb.args.clone(),
+            context
+        });
+    }
+
+    (entries, skipped)
+}
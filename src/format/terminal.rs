@@ -0,0 +1,239 @@
+//! Keymaps for terminal emulators: kitty `map` lines, Alacritty `[[keyboard.bindings]]` and a
+//! WezTerm Lua `keys` table, with `mouse_map`, `[[mouse.bindings]]` and `mouse_bindings` for
+//! mouse keys. Only the commands with a terminal equivalent are written, copy, paste, tabs,
+//! font size and the like, so they follow the same modifier conventions.
+
+use serde_derive::Serialize;
+
+use crate::{
+    binding::KeyBinding,
+    key::{Key, KeyCode, NamedKey, Pointer, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION, from_physical}
+};
+
+
+/// A terminal emulator a keymap is written for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Terminal {
+    Kitty,
+    Alacritty,
+    Wezterm
+}
+
+/// A VS Code command with the kitty, Alacritty and WezTerm action doing the same.
+type Action = (&'static str, Option<&'static str>, Option<&'static str>, Option<&'static str>);
+
+const ACTIONS: &[Action] = &[
+    ("editor.action.clipboardCopyAction", Some("copy_to_clipboard"), Some("Copy"), Some("act.CopyTo 'Clipboard'")),
+    ("workbench.action.terminal.copySelection", Some("copy_to_clipboard"), Some("Copy"), Some("act.CopyTo 'Clipboard'")),
+    ("editor.action.clipboardPasteAction", Some("paste_from_clipboard"), Some("Paste"), Some("act.PasteFrom 'Clipboard'")),
+    ("workbench.action.terminal.paste", Some("paste_from_clipboard"), Some("Paste"), Some("act.PasteFrom 'Clipboard'")),
+    ("workbench.action.terminal.new", Some("new_tab"), Some("CreateNewTab"), Some("act.SpawnTab 'CurrentPaneDomain'")),
+    ("workbench.action.closeActiveEditor", Some("close_tab"), None, Some("act.CloseCurrentTab { confirm = true }")),
+    ("workbench.action.nextEditor", Some("next_tab"), Some("SelectNextTab"), Some("act.ActivateTabRelative(1)")),
+    ("workbench.action.previousEditor", Some("previous_tab"), Some("SelectPreviousTab"), Some("act.ActivateTabRelative(-1)")),
+    ("workbench.action.newWindow", Some("new_os_window"), Some("CreateNewWindow"), Some("act.SpawnWindow")),
+    ("workbench.action.zoomIn", Some("change_font_size all +1.0"), Some("IncreaseFontSize"), Some("act.IncreaseFontSize")),
+    ("workbench.action.zoomOut", Some("change_font_size all -1.0"), Some("DecreaseFontSize"), Some("act.DecreaseFontSize")),
+    ("workbench.action.zoomReset", Some("change_font_size all 0"), Some("ResetFontSize"), Some("act.ResetFontSize")),
+    ("workbench.action.terminal.focusFind", Some("show_scrollback"), Some("SearchForward"), Some("act.Search 'CurrentSelectionOrEmptyString'")),
+    ("workbench.action.terminal.clear", Some("clear_terminal reset active"), Some("ClearHistory"), Some("act.ClearScrollback 'ScrollbackAndViewport'")),
+    ("workbench.action.quit", Some("quit"), Some("Quit"), Some("act.QuitApplication")),
+];
+
+/// VS Code key names with the kitty, Alacritty and WezTerm spelling, empty where the terminal
+/// cannot bind the key. Other keys are the same in kitty and WezTerm and upper case in Alacritty.
+const KEY_NAMES: &[(KeyCode, &str, &str, &str)] = &[
+    (KeyCode::Named(NamedKey::Enter), "enter", "Enter", "Enter"),
+    (KeyCode::Named(NamedKey::Escape), "escape", "Escape", "Escape"),
+    (KeyCode::Named(NamedKey::Tab), "tab", "Tab", "Tab"), (KeyCode::Named(NamedKey::Space), "space", "Space", "Space"),
+    (KeyCode::Named(NamedKey::Backspace), "backspace", "Backspace", "Backspace"),
+    (KeyCode::Named(NamedKey::Delete), "delete", "Delete", "Delete"),
+    (KeyCode::Named(NamedKey::Insert), "insert", "Insert", "Insert"),
+    (KeyCode::Named(NamedKey::Home), "home", "Home", "Home"), (KeyCode::Named(NamedKey::End), "end", "End", "End"),
+    (KeyCode::Named(NamedKey::PageUp), "page_up", "PageUp", "PageUp"),
+    (KeyCode::Named(NamedKey::PageDown), "page_down", "PageDown", "PageDown"),
+    (KeyCode::Named(NamedKey::Up), "up", "ArrowUp", "UpArrow"),
+    (KeyCode::Named(NamedKey::Down), "down", "ArrowDown", "DownArrow"),
+    (KeyCode::Named(NamedKey::Left), "left", "ArrowLeft", "LeftArrow"),
+    (KeyCode::Named(NamedKey::Right), "right", "ArrowRight", "RightArrow"),
+    (KeyCode::Named(NamedKey::Equal), "equal", "=", "="), (KeyCode::Named(NamedKey::Minus), "minus", "-", "-"),
+    (KeyCode::Named(NamedKey::Comma), "comma", ",", ","), (KeyCode::Named(NamedKey::Period), "period", ".", "."),
+    (KeyCode::Named(NamedKey::Slash), "slash", "/", "/"),
+    (KeyCode::Named(NamedKey::Backslash), "backslash", "\\", "\\"),
+    (KeyCode::Named(NamedKey::Semicolon), "semicolon", ";", ";"),
+    (KeyCode::Named(NamedKey::Quote), "apostrophe", "'", "'"),
+    (KeyCode::Named(NamedKey::BracketLeft), "left_bracket", "[", "["),
+    (KeyCode::Named(NamedKey::BracketRight), "right_bracket", "]", "]"),
+    (KeyCode::Named(NamedKey::Backquote), "grave_accent", "`", "`"), (KeyCode::Numpad(0), "kp_0", "", "Numpad0"),
+    (KeyCode::Numpad(1), "kp_1", "", "Numpad1"), (KeyCode::Numpad(2), "kp_2", "", "Numpad2"),
+    (KeyCode::Numpad(3), "kp_3", "", "Numpad3"), (KeyCode::Numpad(4), "kp_4", "", "Numpad4"),
+    (KeyCode::Numpad(5), "kp_5", "", "Numpad5"), (KeyCode::Numpad(6), "kp_6", "", "Numpad6"),
+    (KeyCode::Numpad(7), "kp_7", "", "Numpad7"), (KeyCode::Numpad(8), "kp_8", "", "Numpad8"),
+    (KeyCode::Numpad(9), "kp_9", "", "Numpad9"), (KeyCode::Named(NamedKey::NumpadAdd), "kp_add", "", "Add"),
+    (KeyCode::Named(NamedKey::NumpadSubtract), "kp_subtract", "", "Subtract"),
+    (KeyCode::Named(NamedKey::NumpadMultiply), "kp_multiply", "", "Multiply"),
+    (KeyCode::Named(NamedKey::NumpadDivide), "kp_divide", "", "Divide"),
+    (KeyCode::Named(NamedKey::NumpadDecimal), "kp_decimal", "", "Decimal"),
+];
+
+/// Mouse buttons and wheel directions with the kitty, Alacritty and WezTerm spelling, empty
+/// where the terminal cannot bind them.
+const POINTERS: &[(Pointer, &str, &str, &str)] = &[
+    (Pointer::Button(1), "left", "Left", "'Left'"), (Pointer::Button(2), "right", "Right", "'Right'"),
+    (Pointer::Button(3), "middle", "Middle", "'Middle'"), (Pointer::Button(4), "b4", "Back", ""),
+    (Pointer::Button(5), "b5", "Forward", ""),
+    (Pointer::WheelUp, "", "", "{ WheelUp = 1 }"), (Pointer::WheelDown, "", "", "{ WheelDown = 1 }"),
+    (Pointer::WheelLeft, "", "", "{ WheelLeft = 1 }"), (Pointer::WheelRight, "", "", "{ WheelRight = 1 }"),
+];
+
+impl Terminal {
+    /// The action for `command`, None if the terminal has no equivalent.
+    pub fn action(self, command: &str) -> Option<&'static str> {
+        let (_, kitty, alacritty, wezterm) = ACTIONS.iter().find(|(c, ..)| *c == command)?;
+        match self {
+            Terminal::Kitty => *kitty,
+            Terminal::Alacritty => *alacritty,
+            Terminal::Wezterm => *wezterm
+        }
+    }
+
+    /// The key name and the modifier names in the terminal's spelling, None if it cannot bind the key.
+    fn key(self, key: &Key) -> Option<(String, Vec<&'static str>)> {
+
+        let key = from_physical(key);
+        let table = KEY_NAMES.iter().map(|(k, kitty, alacritty, wezterm)| (k.clone(), *kitty, *alacritty, *wezterm))
+            .chain(POINTERS.iter().map(|(p, kitty, alacritty, wezterm)| (KeyCode::Pointer(*p), *kitty, *alacritty, *wezterm)));
+        let name = match (table.into_iter().find(|(k, ..)| *k == key.code), &key.code) {
+            (Some((_, kitty, alacritty, wezterm)), _) => match self {
+                Terminal::Kitty => kitty.to_string(),
+                Terminal::Alacritty => alacritty.to_string(),
+                Terminal::Wezterm => wezterm.to_string()
+            },
+            (None, c @ (KeyCode::Letter(_) | KeyCode::Digit(_) | KeyCode::Function(_))) if self == Terminal::Alacritty => c.to_string().to_uppercase(),
+            (None, c) => c.to_string()
+        };
+
+        let modifiers: &[(usize, &str)] = match self {
+            Terminal::Kitty => &[(MODIFIER_COMMAND, "cmd"), (MODIFIER_CONTROL, "ctrl"), (MODIFIER_OPTION, "alt"), (MODIFIER_SHIFT, "shift")],
+            Terminal::Alacritty => &[(MODIFIER_COMMAND, "Command"), (MODIFIER_CONTROL, "Control"), (MODIFIER_OPTION, "Alt"), (MODIFIER_SHIFT, "Shift")],
+            Terminal::Wezterm => &[(MODIFIER_COMMAND, "CMD"), (MODIFIER_CONTROL, "CTRL"), (MODIFIER_OPTION, "ALT"), (MODIFIER_SHIFT, "SHIFT")]
+        };
+        let mods = modifiers.iter().filter(|(m, _)| key.modifiers & m != 0).map(|(_, n)| *n).collect();
+        Some((name, mods)).filter(|(name, _)| !name.is_empty())
+    }
+}
+
+#[derive(Serialize)]
+struct AlacrittyConfig {
+    keyboard: AlacrittyBindings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mouse: Option<AlacrittyBindings>
+}
+
+#[derive(Serialize)]
+struct AlacrittyBindings {
+    bindings: Vec<AlacrittyBinding>
+}
+
+#[derive(Serialize)]
+struct AlacrittyBinding {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mouse: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mods: Option<String>,
+    action: String
+}
+
+fn lua_string(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The keymap of `terminal` for the bindings of commands it has an action for, along with the
+/// bindings of such commands that are left out and why. When clauses are dropped, a terminal
+/// has no contexts; disables are left out, as are chords except in kitty.
+pub fn to_terminal(bindings: &[KeyBinding], terminal: Terminal) -> (String, Vec<(usize, String)>) {
+
+    let mut written: Vec<(String, &str)> = vec!();
+    let mut lines = vec!();
+    let mut mouse_lines = vec!();
+    let mut alacritty = vec!();
+    let mut alacritty_mouse = vec!();
+    let mut skipped = vec!();
+
+    for (i, kb) in bindings.iter().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        let Some(action) = terminal.action(kb.command()) else { continue };
+        let mouse = kb.keys.first.pointer().is_some();
+        if kb.keys.is_chord() && (mouse || kb.keys.rest.iter().any(|k| k.pointer().is_some())) {
+            skipped.push((i, "a mouse binding is a single click".to_string()));
+            continue;
+        }
+        if kb.keys.is_chord() && terminal != Terminal::Kitty {
+            skipped.push((i, "chords need a leader key in this terminal".to_string()));
+            continue;
+        }
+
+        let Some(keys) = kb.keys.keys().map(|k| terminal.key(k)).collect::<Option<Vec<_>>>() else {
+            skipped.push((i, "the terminal has no name for the key".to_string()));
+            continue;
+        };
+        let kitty_keys: Vec<String> = keys.iter()
+            .map(|(name, mods)| mods.iter().copied().chain([name.as_str()]).collect::<Vec<_>>().join("+"))
+            .collect();
+        // the same command is often bound once per context, the terminal needs it once
+        let id = kitty_keys.join(">");
+        if written.iter().any(|(k, a)| *k == id && *a == action) {
+            continue;
+        }
+        written.push((id.clone(), action));
+
+        let (name, mods) = &keys[0];
+        let alacritty_mods = Some(mods.join("|")).filter(|m| !m.is_empty());
+        let wezterm_mods = match mods.is_empty() {
+            true => String::new(),
+            false => format!(" mods = {},", lua_string(&mods.join("|")))
+        };
+        match terminal {
+            Terminal::Kitty if mouse => mouse_lines.push(format!("mouse_map {} press ungrabbed {}", id, action)),
+            Terminal::Kitty => lines.push(format!("map {} {}", id, action)),
+            Terminal::Alacritty if mouse => alacritty_mouse.push(AlacrittyBinding {
+                key: None,
+                mouse: Some(name.clone()),
+                mods: alacritty_mods,
+                action: action.to_string()
+            }),
+            Terminal::Alacritty => alacritty.push(AlacrittyBinding {
+                key: Some(name.clone()),
+                mouse: None,
+                mods: alacritty_mods,
+                action: action.to_string()
+            }),
+            Terminal::Wezterm if mouse => {
+                mouse_lines.push(format!("    {{ event = {{ Down = {{ streak = 1, button = {} }} }},{} action = {} }},", name, wezterm_mods, action));
+            },
+            Terminal::Wezterm => lines.push(format!("    {{ key = {},{} action = {} }},", lua_string(name), wezterm_mods, action))
+        }
+    }
+
+    let text = match terminal {
+        Terminal::Kitty => lines.into_iter().chain(mouse_lines).collect::<Vec<_>>().join("\n"),
+        Terminal::Alacritty => {
+            let config = AlacrittyConfig {
+                keyboard: AlacrittyBindings { bindings: alacritty },
+                mouse: Some(AlacrittyBindings { bindings: alacritty_mouse }).filter(|m| !m.bindings.is_empty())
+            };
+            toml::to_string(&config).unwrap_or_default().trim_end().to_string()
+        },
+        Terminal::Wezterm if mouse_lines.is_empty() => format!(
+            "local act = require('wezterm').action\n\nreturn {{\n  keys = {{\n{}\n  }},\n}}",
+            lines.join("\n")
+        ),
+        Terminal::Wezterm => format!(
+            "local act = require('wezterm').action\n\nreturn {{\n  keys = {{\n{}\n  }},\n  mouse_bindings = {{\n{}\n  }},\n}}",
+            lines.join("\n"),
+            mouse_lines.join("\n")
+        )
+    };
+    (text, skipped)
+}
@@ -0,0 +1,112 @@
+//! Zed `keymap.json` files: bindings grouped into sections by context, chords written like
+//! `cmd-k cmd-c`.
+
+use anyhow::{anyhow, bail};
+use serde_derive::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    Result,
+    binding::KeyBinding,
+    key::{Key, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION, from_physical},
+    when::{When, parse_when}
+};
+
+
+/// VS Code context keys with the Zed context that is active in the same situation.
+const CONTEXTS: &[(&str, &str)] = &[
+    ("editorTextFocus", "Editor"), ("editorFocus", "Editor"), ("textInputFocus", "Editor"),
+    ("suggestWidgetVisible", "Editor && showing_completions"),
+    ("terminalFocus", "Terminal"),
+    ("filesExplorerFocus", "ProjectPanel"), ("explorerViewletFocus", "ProjectPanel"),
+    ("inQuickOpen", "Picker"),
+    ("findWidgetVisible", "BufferSearchBar"), ("findInputFocussed", "BufferSearchBar"),
+    ("searchViewletFocus", "ProjectSearchBar"), ("searchInputBoxFocus", "ProjectSearchBar"),
+];
+
+const MODIFIERS: &[(usize, &str)] = &[
+    (MODIFIER_COMMAND, "cmd"), (MODIFIER_CONTROL, "ctrl"), (MODIFIER_OPTION, "alt"), (MODIFIER_SHIFT, "shift"),
+];
+
+/// A section of a Zed keymap, the bindings that apply in `context` (everywhere without one).
+#[derive(Serialize)]
+pub struct ZedSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    pub bindings: Map<String, Value>
+}
+
+/// The Zed context nearest to a when clause: the context of the first of its conditions Zed
+/// has one for, conditions without one are dropped. Fails for `||` and for clauses where no
+/// condition has a Zed context.
+pub fn zed_context(when: Option<&str>) -> Result<Option<String>> {
+
+    let terms = match parse_when(when.unwrap_or_default())? {
+        When::True => return Ok(None),
+        When::And(terms) => terms,
+        When::Or(_) => bail!("alternatives (`||`) have no context equivalent"),
+        term => vec!(term)
+    };
+
+    terms.iter()
+        .find_map(|t| match t {
+            When::Key(k) => CONTEXTS.iter().find(|(v, _)| v == k).map(|(_, z)| Some(z.to_string())),
+            _ => None
+        })
+        .ok_or_else(|| anyhow!("no context equivalent for '{}'", when.unwrap_or_default()))
+}
+
+/// A key in Zed's notation, `cmd-shift-p`.
+pub fn to_zed_key(key: &Key) -> String {
+    let key = from_physical(key);
+    let name = key.code.to_string();
+    let mut parts: Vec<&str> = MODIFIERS.iter()
+        .filter(|(m, _)| key.modifiers & m != 0)
+        .map(|(_, n)| *n)
+        .collect();
+    parts.push(&name);
+    parts.join("-")
+}
+
+/// The sections of a Zed keymap for `bindings`, in the order their contexts first appear,
+/// along with the bindings left out and why, macros among them, a Zed key runs one action.
+/// Disables are left out, they remove VS Code defaults Zed does not have.
+pub fn to_zed(bindings: &[KeyBinding]) -> (Vec<ZedSection>, Vec<(usize, String)>) {
+
+    let mut sections: Vec<ZedSection> = vec!();
+    let mut skipped = vec!();
+
+    for (i, kb) in bindings.iter().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        if kb.keys.keys().any(|k| k.pointer().is_some()) {
+            skipped.push((i, "Zed has no mouse bindings".to_string()));
+            continue;
+        }
+        if kb.action.is_macro() {
+            skipped.push((i, "Zed has no command sequences".to_string()));
+            continue;
+        }
+        let context = match zed_context(kb.when.as_deref()) {
+            Ok(c) => c,
+            Err(e) => {
+                skipped.push((i, e.to_string()));
+                continue;
+            }
+        };
+        let keys: Vec<String> = kb.keys.keys().map(to_zed_key).collect();
+        let action = match &kb.args {
+            Some(args) => Value::Array(vec!(Value::String(kb.command().to_string()), args.clone())),
+            None => Value::String(kb.command().to_string())
+        };
+
+        let si = match sections.iter().position(|s| s.context == context) {
+            Some(si) => si,
+            None => {
+                sections.push(ZedSection { context, bindings: Map::new() });
+                sections.len() - 1
+            }
+        };
+        sections[si].bindings.insert(keys.join(" "), action);
+    }
+
+    (sections, skipped)
+}
@@ -0,0 +1,343 @@
+//! Reading keybindings from JSON(C), CSV, YAML and TOML.
+
+use std::{path::{Path, PathBuf}, io::Read, sync::Arc};
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, de::{Error as _, SeqAccess, Visitor}};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{Result, binding::{BindingSource, ConfigItem, KeyBinding}, diagnostic::{InputError, json_error}, format::{jetbrains::parse_jetbrains_items, sublime::parse_sublime_items}, par};
+
+
+/// The format of a keybindings file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum InputFormat {
+    /// Guess the format from the content
+    Auto,
+    Json,
+    Csv,
+    Yaml,
+    /// `[[binding]]` tables
+    Toml,
+    /// A Sublime Text `.sublime-keymap`, guessed from the file extension
+    Sublime,
+    /// An IntelliJ IDEA `keymap.xml`, guessed from the file extension
+    Jetbrains
+}
+
+/// A keybindings file in TOML, which has no top level arrays: the entries are `[[binding]]` tables.
+#[derive(Serialize, Deserialize)]
+pub struct TomlBindings<T> {
+    #[serde(default = "Vec::new")]
+    pub binding: Vec<T>
+}
+
+/// Reads the bindings of a file, `-` reads stdin, and inlines `$file` args references.
+pub fn load_bindings(path: &Path, format: InputFormat) -> Result<Vec<KeyBinding>> {
+
+    let mut content = String::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut content)?;
+    } else {
+        content = std::fs::read_to_string(path)?;
+    }
+
+    let format = match format {
+        InputFormat::Auto if path.extension().is_some_and(|e| e == "sublime-keymap") => InputFormat::Sublime,
+        InputFormat::Auto if path.extension().is_some_and(|e| e == "xml") => InputFormat::Jetbrains,
+        InputFormat::Auto if path.extension().is_some_and(|e| e == "toml") => InputFormat::Toml,
+        InputFormat::Auto if path.extension().is_some_and(|e| e == "yaml" || e == "yml") => InputFormat::Yaml,
+        f => f
+    };
+    // point diagnostics at the file
+    let entries = read_entries(&content, format).map_err(|e| match e.downcast::<InputError>() {
+        Ok(diagnostic) if path == Path::new("-") => diagnostic.in_file("stdin").into(),
+        Ok(diagnostic) => diagnostic.in_file(path.display().to_string()).into(),
+        Err(e) => e
+    })?;
+
+    let source = Some(Arc::new(BindingSource::File {
+        path: if path == Path::new("-") { "stdin".to_string() } else { path.display().to_string() }
+    }));
+    let mut bindings: Vec<KeyBinding> = par::map_owned(entries, |(item, original)| KeyBinding {
+        original: Some(Arc::new(original)),
+        source: source.clone(),
+        ..KeyBinding::from(item)
+    });
+
+    // args file references are relative to the input file, or the working directory for stdin
+    let base = match path.parent() {
+        Some(dir) if path != Path::new("-") => dir.to_path_buf(),
+        _ => PathBuf::from(".")
+    };
+    for kb in bindings.iter_mut() {
+        inline_args_file(kb, &base)?;
+    }
+
+    Ok(bindings)
+}
+
+/// The entries of a keybindings file, each one as read and as the JSON object it was written as.
+/// JSON is read in a single pass, one entry at a time.
+fn read_entries(content: &str, format: InputFormat) -> Result<Vec<(ConfigItem, Map<String, Value>)>> {
+
+    let is_json = format == InputFormat::Json || format == InputFormat::Auto && matches!(sniff_format(content), Ok(InputFormat::Json));
+    if !is_json {
+        return Ok(parse_config_items(content, format)?.into_iter().zip(parse_original_entries(content, format)?).collect());
+    }
+    let json = strip_jsonc(content);
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    match serde::Deserializer::deserialize_seq(&mut deserializer, EntriesVisitor).and_then(|entries| deserializer.end().map(|_| entries)) {
+        Ok(entries) => Ok(entries),
+        // parse again for an error that points at the entry
+        Err(e) => Err(parse_config_items(content, InputFormat::Json).err().unwrap_or_else(|| e.into()))
+    }
+}
+
+/// Deserializes a keybindings array entry by entry, without a `Value` of the whole file.
+struct EntriesVisitor;
+
+impl<'de> Visitor<'de> for EntriesVisitor {
+    type Value = Vec<(ConfigItem, Map<String, Value>)>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "an array of keybindings")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(entry) = seq.next_element::<Map<String, Value>>()? {
+            let item = ConfigItem::deserialize(&entry).map_err(A::Error::custom)?;
+            entries.push((item, entry));
+        }
+        Ok(entries)
+    }
+}
+
+/// Replaces `"args": { "$file": "snippets/foo.json" }` with the JSON content of that file.
+pub fn inline_args_file(kb: &mut KeyBinding, base: &Path) -> Result<()> {
+
+    let file = match kb.args.as_ref().and_then(Value::as_object) {
+        Some(obj) if obj.len() == 1 => match obj.get("$file") {
+            Some(Value::String(f)) => f.clone(),
+            _ => return Ok(())
+        },
+        _ => return Ok(())
+    };
+
+    let path = base.join(&file);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("cannot read args file {} of {}: {}", path.display(), kb.action, e))?;
+    kb.args = Some(serde_json::from_str(&content)
+        .map_err(|e| anyhow!("args file {} is not valid JSON: {}", path.display(), e))?);
+    kb.args_file = Some(file);
+    Ok(())
+}
+
+/// Parses the entries of a keybindings file in the given format.
+pub fn parse_config_items(content: &str, format: InputFormat) -> Result<Vec<ConfigItem>> {
+    match format {
+        InputFormat::Json => {
+            let json = strip_jsonc(content);
+            serde_json::from_str(&json).map_err(|e| json_error(content, &json, &e).into())
+        },
+        InputFormat::Csv => parse_csv_items(content),
+        InputFormat::Yaml => Ok(serde_yaml::from_str(content)?),
+        InputFormat::Toml => Ok(toml::from_str::<TomlBindings<ConfigItem>>(content)?.binding),
+        InputFormat::Sublime => parse_sublime_items(content),
+        InputFormat::Jetbrains => parse_jetbrains_items(content),
+        InputFormat::Auto => match sniff_format(content)? {
+            InputFormat::Yaml => serde_yaml::from_str(content)
+                .map_err(|e| anyhow!("input is neither JSON, CSV nor YAML ({}), use --input-format", e)),
+            f => parse_config_items(content, f)
+        }
+    }
+}
+
+/// The entries of a keybindings file as plain JSON objects, in the order their fields were written.
+pub fn parse_original_entries(content: &str, format: InputFormat) -> Result<Vec<Map<String, Value>>> {
+    match format {
+        InputFormat::Json => Ok(serde_json::from_str(&strip_jsonc(content))?),
+        InputFormat::Csv | InputFormat::Sublime | InputFormat::Jetbrains => parse_config_items(content, format)?.iter()
+            .map(|item| match serde_json::to_value(item)? {
+                Value::Object(entry) => Ok(entry),
+                _ => bail!("entry is not an object")
+            })
+            .collect(),
+        InputFormat::Yaml => Ok(serde_yaml::from_str(content)?),
+        // toml sorts the fields of a table, put the known ones back into the order VS Code writes
+        InputFormat::Toml => Ok(toml::from_str::<TomlBindings<Map<String, Value>>>(content)?.binding.into_iter()
+            .map(|mut entry| {
+                let mut ordered: Map<String, Value> = ["key", "command", "when", "args"].iter()
+                    .filter_map(|field| entry.remove(*field).map(|v| (field.to_string(), v)))
+                    .collect();
+                ordered.append(&mut entry);
+                ordered
+            })
+            .collect()),
+        InputFormat::Auto => parse_original_entries(content, sniff_format(content)?)
+    }
+}
+
+/// Guesses the input format from the first non-whitespace bytes after `#` comment lines:
+/// `[[binding]]` is TOML, a leading `[`, `{` or comment is JSON, a first line with commas is
+/// a CSV header (unless it starts a YAML sequence), anything else is tried as YAML.
+pub fn sniff_format(content: &str) -> Result<InputFormat> {
+    let mut start = content.trim_start();
+    while start.starts_with('#') {
+        start = start.split_once('\n').map_or("", |(_, rest)| rest).trim_start();
+    }
+    match start.as_bytes().first() {
+        None if content.trim_start().is_empty() => bail!("input is empty"),
+        None => Ok(InputFormat::Yaml),
+        Some(b'[') if start.starts_with("[[binding]]") => Ok(InputFormat::Toml),
+        Some(b'[') | Some(b'{') | Some(b'/') => Ok(InputFormat::Json),
+        Some(b'-') => Ok(InputFormat::Yaml),
+        Some(_) if start.lines().next().unwrap_or_default().contains(',') => Ok(InputFormat::Csv),
+        Some(_) => Ok(InputFormat::Yaml)
+    }
+}
+
+/// Turns the JSONC VS Code accepts in keybindings.json into plain JSON: `//` and `/* */`
+/// comments and trailing commas are blanked out with spaces, so line and column numbers
+/// of serde errors still point into the original text.
+pub fn strip_jsonc(content: &str) -> String {
+
+    let bytes = content.as_bytes();
+    let mut out = bytes.to_vec();
+    let mut i = 0;
+    let mut last_comma: Option<usize> = None;
+
+    let blank = |out: &mut Vec<u8>, from: usize, to: usize| {
+        for b in out[from..to].iter_mut().filter(|b| **b != b'\n') {
+            *b = b' ';
+        }
+    };
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                last_comma = None;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                let end = content[i..].find('\n').map_or(bytes.len(), |e| i + e);
+                blank(&mut out, i, end);
+                i = end;
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = content[i + 2..].find("*/").map_or(bytes.len(), |e| i + 2 + e + 2);
+                blank(&mut out, i, end);
+                i = end;
+            },
+            b',' => {
+                last_comma = Some(i);
+                i += 1;
+            },
+            b']' | b'}' => {
+                if let Some(c) = last_comma.take() {
+                    out[c] = b' ';
+                }
+                i += 1;
+            },
+            b if b.is_ascii_whitespace() => i += 1,
+            _ => {
+                last_comma = None;
+                i += 1;
+            }
+        }
+    }
+
+    // only ASCII bytes outside of strings were replaced, so this is still valid UTF-8
+    String::from_utf8(out).unwrap_or_else(|_| content.to_string())
+}
+
+/// Reads a CSV file with a `key,command,when,args` header; `args` holds inline JSON.
+pub fn parse_csv_items(content: &str) -> Result<Vec<ConfigItem>> {
+
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+
+    let key = column("key").ok_or_else(|| anyhow!("csv header has no 'key' column"))?;
+    let command = column("command").ok_or_else(|| anyhow!("csv header has no 'command' column"))?;
+    let when = column("when");
+    let args = column("args");
+
+    let mut items = vec!();
+    for record in reader.records() {
+        let record = record?;
+        let field = |i: Option<usize>| i.and_then(|i| record.get(i)).filter(|v| !v.is_empty());
+        items.push(ConfigItem {
+            key: field(Some(key)).unwrap_or_default().to_string(),
+            command: field(Some(command)).unwrap_or_default().to_string(),
+            when: field(when).map(String::from),
+            args: field(args).map(serde_json::from_str).transpose()?
+        });
+    }
+    Ok(items)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binding::to_config_items;
+
+    #[test]
+    fn sniffs_json_csv_yaml_and_toml() {
+        let formats = [
+            (r#"[{ "key": "ctrl+s", "command": "save" }]"#, InputFormat::Json),
+            ("// keybindings\n[]", InputFormat::Json),
+            ("key,command,when\nctrl+s,save,\n", InputFormat::Csv),
+            ("- key: ctrl+s\n  command: save\n", InputFormat::Yaml),
+            ("# keybindings\n- key: ctrl+s, then\n  command: save\n", InputFormat::Yaml),
+            ("[[binding]]\nkey = \"ctrl+s\"\ncommand = \"save\"\n", InputFormat::Toml),
+        ];
+        for (content, format) in formats {
+            assert_eq!(sniff_format(content).unwrap(), format, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn parses_sniffed_content_with_the_parser_of_its_format() {
+        for content in [
+            r#"[{ "key": "ctrl+s", "command": "save" }]"#,
+            "key,command\nctrl+s,save\n",
+            "- key: ctrl+s\n  command: save\n",
+        ] {
+            let items = parse_config_items(content, InputFormat::Auto).unwrap();
+            assert_eq!((items.len(), items[0].key.as_str(), items[0].command.as_str()), (1, "ctrl+s", "save"), "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn fails_on_content_no_parser_reads() {
+        assert!(sniff_format(" \n").unwrap_err().to_string().contains("empty"));
+        let e = parse_config_items("just some text", InputFormat::Auto).err().unwrap();
+        assert!(e.to_string().contains("neither JSON, CSV nor YAML"), "{}", e);
+    }
+
+    #[test]
+    fn inlines_args_files_relative_to_the_input_and_writes_them_back_with_keep_refs() {
+        let dir = std::env::temp_dir().join(format!("codekeys-args-file-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("snippets")).unwrap();
+        std::fs::write(dir.join("snippets/log.json"), r#"{ "snippet": "console.log($1)" }"#).unwrap();
+        std::fs::write(dir.join("keys.json"), r#"[{ "key": "ctrl+l", "command": "editor.action.insertSnippet", "args": { "$file": "snippets/log.json" } }]"#).unwrap();
+        let bindings = load_bindings(&dir.join("keys.json"), InputFormat::Auto);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let bindings = bindings.unwrap();
+        assert_eq!(bindings[0].args, Some(serde_json::json!({ "snippet": "console.log($1)" })));
+        assert_eq!(bindings[0].args_file.as_deref(), Some("snippets/log.json"));
+        let written = |keep_refs| to_config_items(&bindings, keep_refs).remove(0).args;
+        assert_eq!(written(true), Some(serde_json::json!({ "$file": "snippets/log.json" })));
+        assert_eq!(written(false), bindings[0].args);
+    }
+}
@@ -0,0 +1,125 @@
+//! Writing generated keybindings into place, with a history of the files replaced.
+
+use std::{path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use anyhow::{anyhow, bail};
+
+use crate::{Result, paths::state_dir};
+
+
+/// How many replaced versions of a file are kept.
+pub const HISTORY_LIMIT: usize = 20;
+
+
+/// Writes `content` next to `path` and renames it over `path`, so readers never see half a file.
+pub fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, content).map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// The current UTC time as `20240131-235959`.
+fn timestamp() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+    // civil date from days since 1970-01-01, Howard Hinnant's algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// The directory the replaced versions of `target` are kept in, in the state directory under
+/// a hash of the path, with the path itself in a `target` file.
+fn history_dir(target: &Path) -> Result<PathBuf> {
+    let path = std::path::absolute(target).unwrap_or_else(|_| target.to_path_buf());
+    // FNV-1a, stable across builds unlike the std hasher
+    let hash = path.to_string_lossy().bytes()
+        .fold(0xcbf29ce484222325u64, |h, b| (h ^ u64::from(b)).wrapping_mul(0x100000001b3));
+    let dir = state_dir()?.join("history").join(format!("{:016x}", hash));
+    std::fs::create_dir_all(&dir).map_err(|e| anyhow!("cannot create {}: {}", dir.display(), e))?;
+    std::fs::write(dir.join("target"), path.to_string_lossy().as_bytes())?;
+    Ok(dir)
+}
+
+/// The order of a kept version: its timestamp, then the `-n` suffix of the copies made within
+/// the same second as a number, so `-10` comes after `-9`.
+fn version_order(path: &Path) -> (String, u64) {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut parts = stem.splitn(3, '-');
+    let stamp = [parts.next(), parts.next()].into_iter().flatten().collect::<Vec<_>>().join("-");
+    (stamp, parts.next().and_then(|n| n.parse().ok()).unwrap_or(0))
+}
+
+/// The kept versions of `target`, newest first.
+pub fn history(target: &Path) -> Result<Vec<PathBuf>> {
+    let mut versions: Vec<PathBuf> = std::fs::read_dir(history_dir(target)?)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    versions.sort_by_cached_key(|p| version_order(p));
+    versions.reverse();
+    Ok(versions)
+}
+
+/// Keeps a copy of `target` in its history unless the newest version has the same content,
+/// the oldest versions beyond HISTORY_LIMIT are removed. Returns the copy, None if there was
+/// nothing to keep.
+fn remember(target: &Path) -> Result<Option<PathBuf>> {
+    let content = match std::fs::read(target) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => bail!("cannot back up {}: {}", target.display(), e)
+    };
+    let versions = history(target)?;
+    if let Some(newest) = versions.first().filter(|v| std::fs::read(v).is_ok_and(|c| c == content)) {
+        return Ok(Some(newest.clone()));
+    }
+
+    let dir = history_dir(target)?;
+    let stamp = timestamp();
+    let copy = (0..).map(|n| match n {
+            0 => dir.join(format!("{}.json", stamp)),
+            n => dir.join(format!("{}-{}.json", stamp, n))
+        })
+        .find(|p| !p.exists())
+        .unwrap_or_default();
+    std::fs::write(&copy, &content).map_err(|e| anyhow!("cannot back up {}: {}", target.display(), e))?;
+
+    for old in versions.iter().skip(HISTORY_LIMIT - 1) {
+        std::fs::remove_file(old)?;
+    }
+    Ok(Some(copy))
+}
+
+/// Replaces `target` with `content`, keeping the file it replaces in the history of `target`.
+/// Returns that copy, None if there was nothing to keep.
+pub fn install(target: &Path, content: &str) -> Result<Option<PathBuf>> {
+    let backup = remember(target)?;
+    write_atomic(target, content)?;
+    Ok(backup)
+}
+
+/// Puts the `n`th newest kept version of `target` back, 1 is the file the last install replaced.
+/// The file it replaces is kept as well, so a rollback can be rolled back. Returns the version restored.
+pub fn rollback(target: &Path, n: usize) -> Result<PathBuf> {
+    let versions = history(target)?;
+    let Some(version) = n.checked_sub(1).and_then(|i| versions.get(i)).cloned() else {
+        bail!("{} has {} earlier versions, cannot go back {}", target.display(), versions.len(), n);
+    };
+    let content = std::fs::read_to_string(&version)
+        .map_err(|e| anyhow!("cannot read {}: {}", version.display(), e))?;
+    install(target, &content)?;
+    Ok(version)
+}
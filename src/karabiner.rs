@@ -0,0 +1,259 @@
+//! Karabiner-Elements complex modifications, to apply a remap system-wide on macOS.
+
+use anyhow::anyhow;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::{
+    Result,
+    binding::KeyBinding,
+    key::{Key, KeyCode, NamedKey, POINTER_NAMES, Pointer, from_physical, to_physical, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION},
+    map::RemapCounts
+};
+
+
+/// Pressing `from` sends `to`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyMapping {
+    pub from: Key,
+    pub to: Key
+}
+
+/// A complex modifications file as found in `~/.config/karabiner/assets/complex_modifications`.
+#[derive(Serialize, Deserialize)]
+pub struct ComplexModifications {
+    #[serde(default)]
+    pub title: String,
+    pub rules: Vec<KarabinerRule>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KarabinerRule {
+    #[serde(default)]
+    pub description: String,
+    pub manipulators: Vec<Manipulator>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Manipulator {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub from: FromEvent,
+    #[serde(default)]
+    pub to: Vec<ToEvent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<serde_json::Value>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FromEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointing_button: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modifiers: Option<FromModifiers>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FromModifiers {
+    #[serde(default)]
+    pub mandatory: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub optional: Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ToEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointing_button: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<String>
+}
+
+/// VS Code keycaps whose Karabiner key code is spelled differently.
+const KEY_CODES: &[(&str, &str)] = &[
+    ("enter", "return_or_enter"), ("space", "spacebar"), ("backspace", "delete_or_backspace"),
+    ("delete", "delete_forward"), ("insert", "insert"), ("pageup", "page_up"), ("pagedown", "page_down"),
+    ("up", "up_arrow"), ("down", "down_arrow"), ("left", "left_arrow"), ("right", "right_arrow"),
+    ("`", "grave_accent_and_tilde"), ("-", "hyphen"), ("=", "equal_sign"),
+    ("[", "open_bracket"), ("]", "close_bracket"), ("\\", "backslash"), (";", "semicolon"),
+    ("'", "quote"), (",", "comma"), (".", "period"), ("/", "slash"),
+    ("numpad_add", "keypad_plus"), ("numpad_subtract", "keypad_hyphen"), ("numpad_multiply", "keypad_asterisk"),
+    ("numpad_divide", "keypad_slash"), ("numpad_decimal", "keypad_period"),
+];
+
+const MODIFIERS: &[(usize, &str)] = &[
+    (MODIFIER_COMMAND, "command"), (MODIFIER_OPTION, "option"),
+    (MODIFIER_CONTROL, "control"), (MODIFIER_SHIFT, "shift"),
+];
+
+/// The Karabiner key code of a keycap or `[KeyCode]`, None for keys Karabiner has no name for.
+fn key_code(key: &Key) -> Option<String> {
+    let code = from_physical(key).code;
+    let keycap = code.to_string();
+    let simple = matches!(code, KeyCode::Letter(_) | KeyCode::Digit(_) | KeyCode::Function(_))
+        || matches!(code, KeyCode::Named(NamedKey::Escape | NamedKey::Tab | NamedKey::Home | NamedKey::End));
+    match (KEY_CODES.iter().find(|(k, _)| *k == keycap), code) {
+        (Some((_, code)), _) => Some(code.to_string()),
+        (None, _) if simple => Some(keycap),
+        (None, KeyCode::Numpad(n)) => Some(format!("keypad_{}", n)),
+        (None, _) => None
+    }
+}
+
+/// The Karabiner key code, or pointing button for a mouse button, of a key. None for keys
+/// Karabiner has no name for and for the wheel, which it only sends.
+fn event_code(key: &Key) -> Option<(Option<String>, Option<String>)> {
+    match key.pointer() {
+        Some(Pointer::Button(n)) => Some((None, Some(format!("button{}", n)))),
+        Some(_) => None,
+        None => Some((Some(key_code(key)?), None))
+    }
+}
+
+/// The VS Code keycap of a Karabiner key code.
+fn keycap(code: &str) -> String {
+    match KEY_CODES.iter().find(|(_, c)| *c == code) {
+        Some((k, _)) => k.to_string(),
+        None => match code.strip_prefix("keypad_") {
+            Some(digit) if digit.len() == 1 => format!("numpad{}", digit),
+            _ => code.to_string()
+        }
+    }
+}
+
+fn modifier_names(modifiers: usize) -> Vec<String> {
+    MODIFIERS.iter()
+        .filter(|(m, _)| modifiers & m != 0)
+        .map(|(_, name)| format!("left_{}", name))
+        .collect()
+}
+
+/// The MODIFIER_ bits of Karabiner modifier names, None if one of them is not a plain modifier.
+fn parse_modifier_names(names: &[String]) -> Option<usize> {
+    names.iter().try_fold(0, |acc, name| {
+        let side = name.trim_start_matches("left_").trim_start_matches("right_");
+        MODIFIERS.iter().find(|(_, n)| *n == side).map(|(m, _)| acc | m)
+    })
+}
+
+/// The key mappings that make `bindings` generated by a remap work without VS Code knowing:
+/// each `-command` entry followed by a binding of the same command on another chord maps the
+/// new keys to the removed ones. Duplicates and keys mapped before are left out.
+pub fn key_mappings(bindings: &[KeyBinding]) -> Vec<KeyMapping> {
+
+    let mut mappings: Vec<KeyMapping> = vec!();
+
+    for pair in bindings.windows(2) {
+        let (disable, kb) = (&pair[0], &pair[1]);
+        if !disable.action.removes(&kb.action) || disable.keys == kb.keys {
+            continue;
+        }
+        for (to, from) in disable.keys.keys().zip(kb.keys.keys()) {
+            if from != to && !mappings.iter().any(|m| m.from == *from) {
+                mappings.push(KeyMapping { from: from.clone(), to: to.clone() });
+            }
+        }
+    }
+
+    mappings
+}
+
+/// A complex modifications file with one rule holding a manipulator per mapping, keys without
+/// a Karabiner key code are skipped. With `vscode_only` the rule applies in VS Code only.
+pub fn to_complex_modifications(mappings: &[KeyMapping], title: &str, vscode_only: bool) -> ComplexModifications {
+
+    let conditions = match vscode_only {
+        true => vec!(serde_json::json!({
+            "type": "frontmost_application_if",
+            "bundle_identifiers": ["^com\\.microsoft\\.VSCode$"]
+        })),
+        false => vec!()
+    };
+
+    let manipulators = mappings.iter().filter_map(|m| {
+        let (from_key, from_button) = event_code(&m.from)?;
+        let (to_key, to_button) = event_code(&m.to)?;
+        Some(Manipulator {
+            kind: "basic".to_string(),
+            from: FromEvent {
+                key_code: from_key,
+                pointing_button: from_button,
+                modifiers: Some(FromModifiers { mandatory: modifier_names(m.from.modifiers), optional: vec!() })
+            },
+            to: vec!(ToEvent { key_code: to_key, pointing_button: to_button, modifiers: modifier_names(m.to.modifiers) }),
+            conditions: conditions.clone()
+        })
+    }).collect();
+
+    ComplexModifications {
+        title: title.to_string(),
+        rules: vec!(KarabinerRule { description: title.to_string(), manipulators })
+    }
+}
+
+/// The VS Code key name of a Karabiner key code or pointing button.
+fn event_key(key_code: &Option<String>, pointing_button: &Option<String>) -> Option<String> {
+    match (key_code, pointing_button) {
+        (Some(code), _) => Some(keycap(code)),
+        (None, Some(button)) => {
+            let n = button.strip_prefix("button")?.parse::<u8>().ok()?;
+            POINTER_NAMES.iter().find(|(_, p)| *p == Pointer::Button(n)).map(|(name, _)| name.to_string())
+        },
+        (None, None) => None
+    }
+}
+
+/// The key mappings of the simple manipulators of a complex modifications file: a key code or
+/// pointing button with plain modifiers sending a single other one. Anything else is skipped.
+pub fn parse_complex_modifications(content: &str) -> Result<Vec<KeyMapping>> {
+
+    let file: ComplexModifications = serde_json::from_str(content)
+        .map_err(|e| anyhow!("not a Karabiner complex modifications file: {}", e))?;
+
+    let mut mappings = vec!();
+    for m in file.rules.iter().flat_map(|r| r.manipulators.iter()).filter(|m| m.kind == "basic") {
+        let to = match m.to.as_slice() {
+            [to] => to,
+            _ => continue
+        };
+        let from_mods = m.from.modifiers.as_ref().map_or(Some(0), |fm| parse_modifier_names(&fm.mandatory));
+        let from_key = event_key(&m.from.key_code, &m.from.pointing_button);
+        let to_key = event_key(&to.key_code, &to.pointing_button);
+        if let (Some(fk), Some(fm), Some(tk), Some(tm)) = (from_key, from_mods, to_key, parse_modifier_names(&to.modifiers)) {
+            mappings.push(KeyMapping {
+                from: Key::new(fm, &fk),
+                to: Key::new(tm, &tk)
+            });
+        }
+    }
+    Ok(mappings)
+}
+
+/// Moves every binding on a mapping's `to` keys over to its `from` keys, so VS Code does
+/// itself what Karabiner would do. Changed bindings are emitted like the plain remap does.
+pub fn apply_key_mappings(bindings: &[KeyBinding], mappings: &[KeyMapping], disable: bool, counts: &mut RemapCounts) -> Vec<KeyBinding> {
+
+    let mut bneu = vec!();
+
+    for kb in bindings.iter() {
+        // `[Backquote]` and `` ` `` are the same Karabiner key, a code stays a code
+        let keys = kb.keys.map(|k| match mappings.iter().find(|m| m.to.modifiers == k.modifiers && event_code(&m.to).is_some_and(|c| event_code(k) == Some(c))) {
+            Some(m) if matches!(k.code, KeyCode::Physical(_)) => to_physical(&m.from),
+            Some(m) => m.from.clone(),
+            None => k.clone()
+        });
+        if keys != kb.keys {
+            if disable {
+                bneu.push(kb.copy_disabled().generated("karabiner mapping"));
+                counts.disables += 1;
+            }
+            bneu.push(KeyBinding { keys, ..kb.clone() }.generated("karabiner mapping"));
+            counts.remaps += 1;
+        }
+    }
+
+    bneu
+}
@@ -0,0 +1,683 @@
+//! Keys, chords and the parser for VS Code's key strings.
+
+use anyhow::bail;
+
+use crate::Result;
+
+
+// modifier bits of Key::modifiers
+pub const MODIFIER_SHIFT: usize = 1;
+pub const MODIFIER_CONTROL: usize = 2;
+pub const MODIFIER_COMMAND: usize = 4;
+pub const MODIFIER_OPTION: usize = 8;
+
+
+
+/// A key sequence as VS Code writes it, `ctrl+k` or the chord `ctrl+k ctrl+c`, chords
+/// may have any number of keys.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct KeyRule {
+    pub first: Key,
+    pub rest: Vec<Key>
+}
+
+impl KeyRule {
+    /// All keys of the sequence in order.
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        std::iter::once(&self.first).chain(self.rest.iter())
+    }
+
+    /// Whether this is a chord of two or more keys.
+    pub fn is_chord(&self) -> bool {
+        !self.rest.is_empty()
+    }
+
+    /// A rule with `f` applied to each of its keys.
+    pub fn map(&self, f: impl Fn(&Key) -> Key) -> KeyRule {
+        KeyRule { first: f(&self.first), rest: self.rest.iter().map(f).collect() }
+    }
+}
+
+/// Parses strictly like [`parse_key_sequence_strict`]. Whatever parses writes back to a string
+/// that parses into the same rule, `s.parse::<KeyRule>()?.to_string().parse()` is the rule again.
+impl std::str::FromStr for KeyRule {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        parse_key_sequence_strict(s)
+    }
+}
+
+impl serde::Serialize for KeyRule {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl std::fmt::Display for KeyRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.first.fmt(f)?;
+        for k in self.rest.iter() {
+            write!(f, " ")?;
+            k.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+
+
+
+
+/// A single key press, a set of MODIFIER_ bits and what the key is. Two presses of the same
+/// key are equal however they were spelled, the key string is the one VS Code writes.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Key {
+    pub modifiers: usize,
+    pub code: KeyCode
+}
+
+/// Parses a single key strictly like [`parse_one_key_strict`], with the same round trip
+/// guarantee as a [`KeyRule`]. It covers the keys that came from parsing and those built from a
+/// known key name as VS Code writes it (a [`KeyCode`] written with `to_string`) and any
+/// modifiers; a key that is a modifier pressed with its own bit, which no parser makes, writes
+/// back without that bit. Keys VS Code does not know and empty keys do not parse strictly.
+impl std::str::FromStr for Key {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        parse_one_key_strict(s, 0)
+    }
+}
+
+/// Writes the modifiers in the order meta, alt, ctrl, shift, with `meta` for the command key
+/// on every platform. The parsers take any order and all spellings, so none of this gets lost.
+/// A modifier pressed alone is written without its own bit, `shift` rather than `shift+shift`.
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let modifiers = self.modifiers & !self.modifier_key().unwrap_or(0);
+        if modifiers & MODIFIER_COMMAND != 0 {
+            write!(f, "meta+")?
+        }
+        if modifiers & MODIFIER_OPTION != 0 {
+            write!(f, "alt+")?
+        }
+        if modifiers & MODIFIER_CONTROL != 0 {
+            write!(f, "ctrl+")?
+        }
+        if modifiers & MODIFIER_SHIFT != 0 {
+            write!(f, "shift+")?
+        }
+        write!(f, "{}", self.code)
+    }
+}
+
+/// A mouse button or wheel direction written as a key name, for tools that bind them
+/// (Karabiner, terminals). Buttons are numbered like Karabiner's: 1 left, 2 right, 3 middle.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Pointer {
+    Button(u8),
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight
+}
+
+/// The key names of the pointer inputs.
+pub const POINTER_NAMES: &[(&str, Pointer)] = &[
+    ("mouseleft", Pointer::Button(1)), ("mouseright", Pointer::Button(2)), ("mousemiddle", Pointer::Button(3)),
+    ("mouse4", Pointer::Button(4)), ("mouse5", Pointer::Button(5)),
+    ("wheelup", Pointer::WheelUp), ("wheeldown", Pointer::WheelDown),
+    ("wheelleft", Pointer::WheelLeft), ("wheelright", Pointer::WheelRight),
+];
+
+impl Pointer {
+    /// The key name of the input, `mouseleft` or `wheelup`.
+    pub fn name(self) -> &'static str {
+        POINTER_NAMES.iter().find(|(_, p)| *p == self).map_or("", |(n, _)| n)
+    }
+
+    pub fn is_wheel(self) -> bool {
+        !matches!(self, Pointer::Button(_))
+    }
+}
+
+/// The modifiers that are keys of their own when pressed alone, as in the double tap chord
+/// `shift shift`, with the name VS Code writes.
+pub const MODIFIER_KEYS: &[(&str, usize)] = &[
+    ("ctrl", MODIFIER_CONTROL), ("shift", MODIFIER_SHIFT), ("alt", MODIFIER_OPTION), ("meta", MODIFIER_COMMAND),
+];
+
+/// The key name of a MODIFIER_ bit, `shift` for MODIFIER_SHIFT.
+pub fn modifier_key_name(modifier: usize) -> Option<&'static str> {
+    MODIFIER_KEYS.iter().find(|(_, m)| *m == modifier).map(|(n, _)| *n)
+}
+
+impl Key {
+    /// The key named `name` in any spelling [`KeyCode::parse`] takes, with `modifiers`.
+    pub fn new(modifiers: usize, name: &str) -> Key {
+        Key { modifiers, code: KeyCode::parse(name) }
+    }
+
+    /// Whether there is no key besides the modifiers, as in `ctrl+`.
+    pub fn is_empty(&self) -> bool {
+        matches!(&self.code, KeyCode::Other(s) if s.is_empty())
+    }
+
+    /// The MODIFIER_ bit of a key that is a modifier pressed alone, None for other keys.
+    pub fn modifier_key(&self) -> Option<usize> {
+        match self.code {
+            KeyCode::Modifier(m) => Some(m),
+            _ => None
+        }
+    }
+
+    /// The mouse button or wheel direction this key is, None for a key of the keyboard.
+    pub fn pointer(&self) -> Option<Pointer> {
+        match self.code {
+            KeyCode::Pointer(p) => Some(p),
+            _ => None
+        }
+    }
+}
+
+/// The empty key an empty key string parses into.
+pub fn anykey() -> Key {
+    Key { modifiers: 0, code: KeyCode::Other(String::new()) }
+}
+
+/// Parses a key string like `ctrl+k ctrl+c` of any number of keys.
+pub fn parse_key_sequence(code: &str) -> KeyRule {
+    let mut iter = code.split_ascii_whitespace().map(parse_one_key);
+    let k1 = iter.next();
+    KeyRule {
+        first: k1.unwrap_or_else(anykey),
+        rest: iter.collect()
+    }
+}
+
+
+/// Parses a single key like `ctrl+shift+k`, accepting the modifier spellings of all platforms.
+/// A modifier name at the end is the key, `shift` alone or `ctrl+shift`, and its own bit is
+/// left out of the modifiers, so `shift+shift` is `shift`.
+pub fn parse_one_key(key: &str) -> Key {
+
+    let mut modifiers: usize = 0;
+    let mut thekey: Option<String> = None;
+
+    for k in key.split_inclusive('+') {
+        match k.to_lowercase().as_str() {
+            "ctrl+" => modifiers |= MODIFIER_CONTROL,
+            "shift+" => modifiers |= MODIFIER_SHIFT,
+            "super+" => modifiers |= MODIFIER_COMMAND,
+            "cmd+" => modifiers |= MODIFIER_COMMAND,
+            "meta+" => modifiers |= MODIFIER_COMMAND,
+            "win+" => modifiers |= MODIFIER_COMMAND,
+            "alt+" => modifiers |= MODIFIER_OPTION,
+            c if c.len() > 2 && c.starts_with('[') && c.ends_with(']') => thekey = Some(canonical_code(k)),
+            c => thekey = Some(canonical_key_name(c))
+        }
+    }
+
+    let key = Key::new(modifiers, &thekey.unwrap_or_default());
+    match key.modifier_key() {
+        Some(m) => Key { modifiers: key.modifiers & !m, ..key },
+        None => key
+    }
+}
+
+
+/// Why a key string is not valid.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum KeyErrorKind {
+    /// the string has no keys at all
+    EmptySequence,
+    /// a `+` with nothing after it, like `ctrl+`
+    EmptyKey,
+    UnknownModifier(String),
+    DuplicateModifier(String),
+    UnsupportedKey(String)
+}
+
+/// A key string rejected by the strict parser, `position` is the byte offset of the offending part.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyError {
+    pub kind: KeyErrorKind,
+    pub position: usize
+}
+
+impl std::fmt::Display for KeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            KeyErrorKind::EmptySequence => write!(f, "no keys")?,
+            KeyErrorKind::EmptyKey => write!(f, "missing key after the modifiers")?,
+            KeyErrorKind::UnknownModifier(m) => write!(f, "unknown modifier '{}'", m)?,
+            KeyErrorKind::DuplicateModifier(m) => write!(f, "duplicate modifier '{}'", m)?,
+            KeyErrorKind::UnsupportedKey(k) => write!(f, "unsupported key name '{}'", k)?
+        }
+        write!(f, " at column {}", self.position + 1)
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+/// The keys VS Code names besides letters, digits, function and numpad digit keys.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum NamedKey {
+    Backquote, Minus, Equal, BracketLeft, BracketRight, Backslash, Semicolon, Quote, Comma, Period, Slash,
+    Enter, Escape, Tab, Space, Backspace, Delete, Insert, Home, End, PageUp, PageDown,
+    Up, Down, Left, Right, CapsLock, NumLock, ScrollLock, PauseBreak, ContextMenu, PrintScreen,
+    NumpadMultiply, NumpadAdd, NumpadSeparator, NumpadSubtract, NumpadDecimal, NumpadDivide,
+    /// keys of other layouts without a US keycap: `oem_8`, the ISO key next to the left shift
+    /// (`oem_102`, `[IntlBackslash]`) and the Brazilian ABNT keys (`abnt_c1` is `[IntlRo]`)
+    Oem8, Oem102, AbntC1, AbntC2,
+    AudioVolumeMute, AudioVolumeUp, AudioVolumeDown,
+    MediaTrackNext, MediaTrackPrevious, MediaStop, MediaPlayPause,
+    LaunchMediaPlayer, LaunchMail, LaunchApp1, LaunchApp2,
+    BrowserSearch, BrowserHome, BrowserBack, BrowserForward, BrowserStop, BrowserRefresh, BrowserFavorites
+}
+
+/// The named keys with the name VS Code writes.
+pub const NAMED_KEYS: &[(NamedKey, &str)] = &[
+    (NamedKey::Backquote, "`"), (NamedKey::Minus, "-"), (NamedKey::Equal, "="),
+    (NamedKey::BracketLeft, "["), (NamedKey::BracketRight, "]"), (NamedKey::Backslash, "\\"),
+    (NamedKey::Semicolon, ";"), (NamedKey::Quote, "'"), (NamedKey::Comma, ","), (NamedKey::Period, "."), (NamedKey::Slash, "/"),
+    (NamedKey::Enter, "enter"), (NamedKey::Escape, "escape"), (NamedKey::Tab, "tab"), (NamedKey::Space, "space"),
+    (NamedKey::Backspace, "backspace"), (NamedKey::Delete, "delete"), (NamedKey::Insert, "insert"),
+    (NamedKey::Home, "home"), (NamedKey::End, "end"), (NamedKey::PageUp, "pageup"), (NamedKey::PageDown, "pagedown"),
+    (NamedKey::Up, "up"), (NamedKey::Down, "down"), (NamedKey::Left, "left"), (NamedKey::Right, "right"),
+    (NamedKey::CapsLock, "capslock"), (NamedKey::NumLock, "numlock"), (NamedKey::ScrollLock, "scrolllock"),
+    (NamedKey::PauseBreak, "pausebreak"), (NamedKey::ContextMenu, "contextmenu"), (NamedKey::PrintScreen, "printscreen"),
+    (NamedKey::NumpadMultiply, "numpad_multiply"), (NamedKey::NumpadAdd, "numpad_add"), (NamedKey::NumpadSeparator, "numpad_separator"),
+    (NamedKey::NumpadSubtract, "numpad_subtract"), (NamedKey::NumpadDecimal, "numpad_decimal"), (NamedKey::NumpadDivide, "numpad_divide"),
+    (NamedKey::Oem8, "oem_8"), (NamedKey::Oem102, "oem_102"), (NamedKey::AbntC1, "abnt_c1"), (NamedKey::AbntC2, "abnt_c2"),
+    (NamedKey::AudioVolumeMute, "audiovolumemute"), (NamedKey::AudioVolumeUp, "audiovolumeup"), (NamedKey::AudioVolumeDown, "audiovolumedown"),
+    (NamedKey::MediaTrackNext, "mediatracknext"), (NamedKey::MediaTrackPrevious, "mediatrackprevious"),
+    (NamedKey::MediaStop, "mediastop"), (NamedKey::MediaPlayPause, "mediaplaypause"),
+    (NamedKey::LaunchMediaPlayer, "launchmediaplayer"), (NamedKey::LaunchMail, "launchmail"),
+    (NamedKey::LaunchApp1, "launchapp1"), (NamedKey::LaunchApp2, "launchapp2"),
+    (NamedKey::BrowserSearch, "browsersearch"), (NamedKey::BrowserHome, "browserhome"), (NamedKey::BrowserBack, "browserback"),
+    (NamedKey::BrowserForward, "browserforward"), (NamedKey::BrowserStop, "browserstop"),
+    (NamedKey::BrowserRefresh, "browserrefresh"), (NamedKey::BrowserFavorites, "browserfavorites"),
+];
+
+impl NamedKey {
+    pub fn name(self) -> &'static str {
+        NAMED_KEYS.iter().find(|(k, _)| *k == self).map_or("", |(_, n)| n)
+    }
+}
+
+/// What a key is, the key of a [`Key`], written as the key string VS Code writes. Keys of other layouts, dead keys
+/// among them, are bound by their physical code.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum KeyCode {
+    /// `a` to `z`
+    Letter(char),
+    /// `0` to `9`
+    Digit(u8),
+    /// `f1` to `f24`
+    Function(u8),
+    /// `numpad0` to `numpad9`
+    Numpad(u8),
+    Named(NamedKey),
+    /// a modifier pressed alone, its MODIFIER_ bit
+    Modifier(usize),
+    Pointer(Pointer),
+    /// a `[KeyCode]`, without the brackets in VS Code's spelling, e.g. `IntlBackslash`
+    Physical(String),
+    /// a key VS Code does not know, as written
+    Other(String)
+}
+
+/// The number after `prefix` in `name` if it is in `range`, `f12` is 12 for `f`. The number
+/// is written without leading zeros, `f01` is not a function key.
+fn numbered(name: &str, prefix: &str, range: std::ops::RangeInclusive<u8>) -> Option<u8> {
+    let digits = name.strip_prefix(prefix)?;
+    match !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+        true => digits.parse().ok().filter(|n| range.contains(n) && n.to_string() == digits),
+        false => None
+    }
+}
+
+impl KeyCode {
+    /// What the key string `name` is, in any spelling the parser accepts.
+    pub fn parse(name: &str) -> KeyCode {
+        if name.len() > 2 && name.starts_with('[') && name.ends_with(']') {
+            let code = canonical_code(name);
+            let inner = &code[1..code.len() - 1];
+            return match inner.chars().all(|c| c.is_ascii_alphanumeric()) {
+                true => KeyCode::Physical(inner.to_string()),
+                false => KeyCode::Other(name.to_string())
+            };
+        }
+        let canonical = canonical_key_name(&name.to_lowercase());
+        if let Some((k, _)) = NAMED_KEYS.iter().find(|(_, n)| *n == canonical) {
+            return KeyCode::Named(*k);
+        }
+        if let Some((_, p)) = POINTER_NAMES.iter().find(|(n, _)| *n == canonical) {
+            return KeyCode::Pointer(*p);
+        }
+        if let Some((_, m)) = MODIFIER_KEYS.iter().find(|(n, _)| *n == canonical) {
+            return KeyCode::Modifier(*m);
+        }
+        let mut chars = canonical.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_lowercase() => KeyCode::Letter(c),
+            (Some(c), None) if c.is_ascii_digit() => KeyCode::Digit(c as u8 - b'0'),
+            _ => match (numbered(&canonical, "f", 1..=24), numbered(&canonical, "numpad", 0..=9)) {
+                (Some(n), _) => KeyCode::Function(n),
+                (_, Some(n)) if canonical.len() == 7 => KeyCode::Numpad(n),
+                _ => KeyCode::Other(name.to_string())
+            }
+        }
+    }
+
+    /// Whether VS Code knows the key.
+    pub fn is_known(&self) -> bool {
+        !matches!(self, KeyCode::Other(_))
+    }
+}
+
+/// The key string VS Code writes, which parses back into the same code.
+impl std::fmt::Display for KeyCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyCode::Letter(c) => write!(f, "{}", c),
+            KeyCode::Digit(d) => write!(f, "{}", d),
+            KeyCode::Function(n) => write!(f, "f{}", n),
+            KeyCode::Numpad(n) => write!(f, "numpad{}", n),
+            KeyCode::Named(k) => write!(f, "{}", k.name()),
+            KeyCode::Modifier(m) => write!(f, "{}", modifier_key_name(*m).unwrap_or("")),
+            KeyCode::Pointer(p) => write!(f, "{}", p.name()),
+            KeyCode::Physical(c) => write!(f, "[{}]", c),
+            KeyCode::Other(s) => write!(f, "{}", s)
+        }
+    }
+}
+
+fn is_known_key(key: &str) -> bool {
+    KeyCode::parse(key).is_known()
+}
+
+/// Parses a key string like [`parse_key_sequence`], but rejects what that one guesses around:
+/// unknown or repeated modifiers, missing keys and key names VS Code does not know.
+pub fn parse_key_sequence_strict(code: &str) -> std::result::Result<KeyRule, KeyError> {
+
+    let mut keys = vec!();
+    let mut offset = 0;
+    for part in code.split(|c: char| c.is_ascii_whitespace()) {
+        if !part.is_empty() {
+            keys.push(parse_one_key_strict(part, offset)?);
+        }
+        offset += part.len() + 1;
+    }
+
+    let mut iter = keys.into_iter();
+    match iter.next() {
+        Some(first) => Ok(KeyRule { first, rest: iter.collect() }),
+        None => Err(KeyError { kind: KeyErrorKind::EmptySequence, position: 0 })
+    }
+}
+
+/// Parses a single key like `ctrl+shift+k` strictly, `offset` is its position in the whole key string.
+pub fn parse_one_key_strict(key: &str, offset: usize) -> std::result::Result<Key, KeyError> {
+
+    let parts: Vec<&str> = key.split('+').collect();
+    let (name, modifiers) = parts.split_last().unwrap_or((&"", &[]));
+
+    let mut bits = 0;
+    let mut position = offset;
+    for m in modifiers.iter() {
+        let bit = match m.to_lowercase().as_str() {
+            "ctrl" => MODIFIER_CONTROL,
+            "shift" => MODIFIER_SHIFT,
+            "alt" => MODIFIER_OPTION,
+            "meta" | "cmd" | "super" | "win" => MODIFIER_COMMAND,
+            _ => return Err(KeyError { kind: KeyErrorKind::UnknownModifier(m.to_string()), position })
+        };
+        if bits & bit != 0 {
+            return Err(KeyError { kind: KeyErrorKind::DuplicateModifier(m.to_string()), position });
+        }
+        bits |= bit;
+        position += m.len() + 1;
+    }
+
+    if name.is_empty() {
+        return Err(KeyError { kind: KeyErrorKind::EmptyKey, position });
+    }
+    let canonical = canonical_key_name(&name.to_lowercase());
+    if !is_known_key(&canonical) {
+        return Err(KeyError { kind: KeyErrorKind::UnsupportedKey(name.to_string()), position });
+    }
+    if MODIFIER_KEYS.iter().any(|(n, m)| *n == canonical && bits & m != 0) {
+        return Err(KeyError { kind: KeyErrorKind::DuplicateModifier(name.to_string()), position });
+    }
+    Ok(parse_one_key(key))
+}
+
+
+/// Keycaps of the US layout that do not follow the letter, digit or function key pattern, and
+/// the named keys of other layouts and keyboards, with the physical key code VS Code knows them by.
+/// `oem_8` is the key left of 1 on a UK keyboard, where the US one has `` ` ``, which keeps
+/// `[Backquote]`.
+pub const US_PHYSICAL_CODES: &[(&str, &str)] = &[
+    ("`", "Backquote"), ("-", "Minus"), ("=", "Equal"),
+    ("[", "BracketLeft"), ("]", "BracketRight"), ("\\", "Backslash"),
+    (";", "Semicolon"), ("'", "Quote"), (",", "Comma"), (".", "Period"), ("/", "Slash"),
+    ("enter", "Enter"), ("escape", "Escape"), ("tab", "Tab"), ("space", "Space"),
+    ("backspace", "Backspace"), ("delete", "Delete"), ("insert", "Insert"),
+    ("home", "Home"), ("end", "End"), ("pageup", "PageUp"), ("pagedown", "PageDown"),
+    ("up", "ArrowUp"), ("down", "ArrowDown"), ("left", "ArrowLeft"), ("right", "ArrowRight"),
+    ("numpad0", "Numpad0"), ("numpad1", "Numpad1"), ("numpad2", "Numpad2"), ("numpad3", "Numpad3"),
+    ("numpad4", "Numpad4"), ("numpad5", "Numpad5"), ("numpad6", "Numpad6"), ("numpad7", "Numpad7"),
+    ("numpad8", "Numpad8"), ("numpad9", "Numpad9"), ("numpad_add", "NumpadAdd"), ("numpad_subtract", "NumpadSubtract"),
+    ("numpad_multiply", "NumpadMultiply"), ("numpad_divide", "NumpadDivide"), ("numpad_decimal", "NumpadDecimal"),
+    ("capslock", "CapsLock"), ("numlock", "NumLock"), ("scrolllock", "ScrollLock"), ("pausebreak", "Pause"),
+    ("contextmenu", "ContextMenu"), ("printscreen", "PrintScreen"), ("oem_102", "IntlBackslash"), ("abnt_c1", "IntlRo"),
+    ("abnt_c2", "NumpadComma"), ("oem_8", "Backquote"),
+    ("audiovolumemute", "AudioVolumeMute"), ("audiovolumeup", "AudioVolumeUp"), ("audiovolumedown", "AudioVolumeDown"),
+    ("mediatracknext", "MediaTrackNext"), ("mediatrackprevious", "MediaTrackPrevious"), ("mediastop", "MediaStop"),
+    ("mediaplaypause", "MediaPlayPause"), ("launchmediaplayer", "MediaSelect"), ("launchmail", "LaunchMail"),
+    ("launchapp1", "LaunchApp1"), ("launchapp2", "LaunchApp2"), ("browsersearch", "BrowserSearch"),
+    ("browserhome", "BrowserHome"), ("browserback", "BrowserBack"), ("browserforward", "BrowserForward"),
+    ("browserstop", "BrowserStop"), ("browserrefresh", "BrowserRefresh"), ("browserfavorites", "BrowserFavorites"),
+];
+
+/// Other spellings of key names, with the one VS Code writes. The `oem_` names are those of the
+/// US layout.
+pub const KEY_ALIASES: &[(&str, &str)] = &[
+    ("esc", "escape"), ("return", "enter"), ("del", "delete"), ("ins", "insert"), ("spacebar", "space"),
+    ("pgup", "pageup"), ("pgdn", "pagedown"), ("page_up", "pageup"), ("page_down", "pagedown"),
+    ("arrowup", "up"), ("arrowdown", "down"), ("arrowleft", "left"), ("arrowright", "right"),
+    ("uparrow", "up"), ("downarrow", "down"), ("leftarrow", "left"), ("rightarrow", "right"),
+    ("caps", "capslock"), ("pause", "pausebreak"), ("break", "pausebreak"), ("menu", "contextmenu"), ("apps", "contextmenu"),
+    ("oem_1", ";"), ("oem_plus", "="), ("oem_comma", ","), ("oem_minus", "-"), ("oem_period", "."),
+    ("oem_2", "/"), ("oem_3", "`"), ("oem_4", "["), ("oem_5", "\\"), ("oem_6", "]"), ("oem_7", "'"),
+    ("numpadadd", "numpad_add"), ("numpad_plus", "numpad_add"), ("numpadsubtract", "numpad_subtract"),
+    ("numpad_minus", "numpad_subtract"), ("numpadmultiply", "numpad_multiply"), ("numpaddivide", "numpad_divide"),
+    ("numpaddecimal", "numpad_decimal"),
+    ("intlbackslash", "oem_102"), ("intlro", "abnt_c1"), ("print", "printscreen"), ("prtsc", "printscreen"),
+    ("volumemute", "audiovolumemute"), ("volumeup", "audiovolumeup"), ("volumedown", "audiovolumedown"),
+    ("playpause", "mediaplaypause"), ("medianexttrack", "mediatracknext"), ("mediaprevioustrack", "mediatrackprevious"),
+    ("mouse1", "mouseleft"), ("mouse2", "mouseright"), ("mouse3", "mousemiddle"),
+    ("button1", "mouseleft"), ("button2", "mouseright"), ("button3", "mousemiddle"), ("button4", "mouse4"), ("button5", "mouse5"),
+    ("mousewheelup", "wheelup"), ("mousewheeldown", "wheeldown"), ("mousewheelleft", "wheelleft"), ("mousewheelright", "wheelright"),
+    ("scrollup", "wheelup"), ("scrolldown", "wheeldown"),
+    ("cmd", "meta"), ("super", "meta"), ("win", "meta"),
+];
+
+/// The name VS Code writes for a lower case key name, e.g. `esc` -> `escape`, `numpad_0` -> `numpad0`.
+pub fn canonical_key_name(name: &str) -> String {
+    if let Some((_, canonical)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return canonical.to_string();
+    }
+    match name.strip_prefix("numpad_") {
+        Some(digit) if digit.len() == 1 && digit.as_bytes()[0].is_ascii_digit() => format!("numpad{}", digit),
+        _ => name.to_string()
+    }
+}
+
+/// The physical key code (`[KeyP]`, `[Digit1]`, `[BracketLeft]`) of a US layout keycap.
+pub fn physical_code(keycap: &str) -> Option<String> {
+    let mut chars = keycap.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_lowercase() => format!("Key{}", c.to_ascii_uppercase()),
+        (Some(c), None) if c.is_ascii_digit() => format!("Digit{}", c),
+        // `f1` to `f24`, not `f0` or `f01`
+        (Some('f'), Some(_)) if numbered(keycap, "f", 1..=24).is_some() => keycap.to_uppercase(),
+        _ => US_PHYSICAL_CODES.iter().find(|(k, _)| *k == keycap)?.1.to_string()
+    };
+    Some(format!("[{}]", code))
+}
+
+/// Brings a bracketed key code into VS Code's spelling, e.g. `[bracketleft]` -> `[BracketLeft]`.
+/// Codes that are not known are kept as written.
+pub fn canonical_code(code: &str) -> String {
+    let inner = code.trim_start_matches('[').trim_end_matches(']').to_lowercase();
+    let known = match inner.as_str() {
+        c if c.len() == 4 && c.starts_with("key") => physical_code(&c[3..]),
+        c if c.len() == 6 && c.starts_with("digit") => physical_code(&c[5..]),
+        c if c.len() > 1 && c.starts_with('f') => physical_code(c),
+        c => US_PHYSICAL_CODES.iter()
+            .find(|(_, p)| p.to_lowercase() == c)
+            .map(|(_, p)| format!("[{}]", p))
+            // `[Esc]` is `[Escape]`
+            .or_else(|| Some(canonical_key_name(c)).filter(|k| k != c).and_then(|k| physical_code(&k)))
+    };
+    known.unwrap_or_else(|| code.to_string())
+}
+
+/// The key with its keycap replaced by the physical key code, if the US layout has one.
+pub fn to_physical(key: &Key) -> Key {
+    match physical_code(&key.code.to_string()) {
+        Some(code) => Key::new(key.modifiers, &code),
+        None => key.clone()
+    }
+}
+
+/// The key with its physical key code replaced by the US layout keycap, if it has one.
+pub fn from_physical(key: &Key) -> Key {
+    let keycap = match &key.code {
+        KeyCode::Physical(c) if c.len() == 4 && c.starts_with("Key") => Some(c[3..].to_lowercase()),
+        KeyCode::Physical(c) if c.len() == 6 && c.starts_with("Digit") => Some(c[5..].to_string()),
+        KeyCode::Physical(c) if numbered(c, "F", 1..=24).is_some() => Some(c.to_lowercase()),
+        KeyCode::Physical(c) => US_PHYSICAL_CODES.iter().find(|(_, p)| p == c).map(|(k, _)| k.to_string()),
+        _ => None
+    };
+    match keycap {
+        Some(k) => Key::new(key.modifiers, &k),
+        None => key.clone()
+    }
+}
+
+/// The MODIFIER_ bit of a modifier name like `ctrl`, `cmd` or `alt`.
+pub fn parse_modifier(name: &str) -> Result<usize> {
+    match parse_one_key(&format!("{}+", name)).modifiers {
+        0 => bail!("unknown modifier '{}'", name),
+        m => Ok(m)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small xorshift generator, the same cases on every run.
+    struct Cases(u64);
+
+    impl Cases {
+        fn next(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.next(items.len())]
+        }
+    }
+
+    /// Every key name VS Code writes.
+    fn known_keys() -> Vec<String> {
+        let mut keys: Vec<String> = ('a'..='z').map(String::from).collect();
+        keys.extend((0..=9).map(|d| d.to_string()));
+        keys.extend((1..=24).map(|n| format!("f{}", n)));
+        keys.extend((0..=9).map(|n| format!("numpad{}", n)));
+        keys.extend(NAMED_KEYS.iter().map(|(_, n)| n.to_string()));
+        keys.extend(POINTER_NAMES.iter().map(|(n, _)| n.to_string()));
+        keys.extend(MODIFIER_KEYS.iter().map(|(n, _)| n.to_string()));
+        keys.extend(keys.clone().iter().filter_map(|k| physical_code(k)));
+        keys
+    }
+
+    #[test]
+    fn every_known_key_with_any_modifiers_round_trips() {
+        for name in known_keys() {
+            for modifiers in 0..16 {
+                let key = Key::new(modifiers, &name);
+                let expected = Key { modifiers: modifiers & !key.modifier_key().unwrap_or(0), ..key.clone() };
+                assert_eq!(key.to_string().parse::<Key>().ok(), Some(expected), "{}", key);
+            }
+        }
+    }
+
+    #[test]
+    fn parsed_key_strings_round_trip() {
+        let modifiers = ["ctrl", "Ctrl", "shift", "SHIFT", "alt", "meta", "cmd", "Cmd", "super", "win", "option", ""];
+        let names = ["k", "K", "esc", "Escape", "numpad_0", "numpad_add", "oem_102", "intlro", "[keyp]", "[IntlBackslash]",
+            "[Esc]", "f12", "f25", "shift", "ctrl", "mouse1", "wheelup", "arrowup", "audiovolumeup", "=", "+", "", "nope"];
+        let mut cases = Cases(0x9e3779b97f4a7c15);
+        let mut parsed = 0;
+        for _ in 0..20000 {
+            let presses: Vec<String> = (0..1 + cases.next(3)).map(|_| {
+                let mut press: Vec<&str> = (0..cases.next(4)).map(|_| cases.pick(&modifiers)).collect();
+                press.push(cases.pick(&names));
+                press.join("+")
+            }).collect();
+            let s = presses.join(if cases.next(2) == 0 { " " } else { "  " });
+            if let Ok(rule) = s.parse::<KeyRule>() {
+                parsed += 1;
+                assert_eq!(rule.to_string().parse::<KeyRule>().as_ref(), Ok(&rule), "{:?} wrote {}", s, rule);
+            }
+            if let Ok(key) = presses[0].parse::<Key>() {
+                assert_eq!(key.to_string().parse::<Key>().as_ref(), Ok(&key), "{:?} wrote {}", presses[0], key);
+            }
+        }
+        assert!(parsed > 1000, "only {} of the cases parsed", parsed);
+    }
+
+    #[test]
+    fn us_keycaps_have_their_physical_codes() {
+        for (keycap, code) in [("p", "[KeyP]"), ("1", "[Digit1]"), ("[", "[BracketLeft]"), ("f1", "[F1]"), ("f24", "[F24]")] {
+            assert_eq!(physical_code(keycap).as_deref(), Some(code), "{}", keycap);
+            let key = Key::new(MODIFIER_CONTROL, keycap);
+            assert_eq!(to_physical(&key), Key::new(MODIFIER_CONTROL, code));
+            assert_eq!(to_physical(&key).to_string(), format!("ctrl+{}", code));
+            assert_eq!(from_physical(&to_physical(&key)), key);
+        }
+    }
+
+    #[test]
+    fn only_f1_to_f24_are_function_keys() {
+        for keycap in ["f0", "f25", "f99", "f01", "f+1"] {
+            assert_eq!(physical_code(keycap), None, "{}", keycap);
+        }
+        for code in ["[F0]", "[F25]", "[F01]"] {
+            let key = Key::new(0, code);
+            assert_eq!(from_physical(&key), key);
+        }
+    }
+
+    #[test]
+    fn spellings_of_a_key_are_the_same_key() {
+        for (a, b) in [("Ctrl+Esc", "ctrl+escape"), ("cmd+numpad_0", "meta+numpad0"), ("ctrl+[keyp]", "ctrl+[KeyP]"), ("win+shift+arrowup", "meta+shift+up")] {
+            assert_eq!(parse_one_key(a), parse_one_key(b), "{} {}", a, b);
+            assert_eq!(parse_one_key(a).to_string(), b);
+        }
+        assert_eq!(Key::new(MODIFIER_CONTROL, "esc"), parse_one_key("ctrl+escape"));
+        assert_ne!(parse_one_key("ctrl+p"), parse_one_key("ctrl+[KeyP]"));
+        assert!(parse_one_key("ctrl+").is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+//! Keyboard layouts other than US: VS Code's defaults name keys by the character they give on
+//! a US keyboard, some of those characters are typed with other keys (or not at all) elsewhere.
+
+use crate::{
+    binding::KeyBinding,
+    key::{Key, KeyCode, KeyRule, NamedKey, MODIFIER_SHIFT}
+};
+
+
+/// A layout the generated bindings are rewritten for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Layout {
+    /// German QWERTZ
+    De,
+    /// French AZERTY
+    Fr
+}
+
+/// How a character is typed on a layout.
+enum Typed {
+    /// with these modifiers on this key, a physical code where the keycap differs from US
+    Key(usize, &'static str),
+    /// only with AltGr or a dead key, which do not combine with the binding's modifiers
+    Manual(&'static str)
+}
+
+const DE: &[(NamedKey, Typed)] = &[
+    (NamedKey::Slash, Typed::Key(MODIFIER_SHIFT, "7")),
+    (NamedKey::Equal, Typed::Key(MODIFIER_SHIFT, "0")),
+    (NamedKey::Semicolon, Typed::Key(MODIFIER_SHIFT, "[Comma]")),
+    (NamedKey::Quote, Typed::Key(MODIFIER_SHIFT, "[Backslash]")),
+    (NamedKey::BracketLeft, Typed::Manual("AltGr+8")),
+    (NamedKey::BracketRight, Typed::Manual("AltGr+9")),
+    (NamedKey::Backslash, Typed::Manual("AltGr+ß")),
+    (NamedKey::Backquote, Typed::Manual("a dead key, shift+´")),
+];
+
+const FR: &[(NamedKey, Typed)] = &[
+    (NamedKey::Slash, Typed::Key(MODIFIER_SHIFT, "[Period]")),
+    (NamedKey::Period, Typed::Key(MODIFIER_SHIFT, "[Comma]")),
+    (NamedKey::Semicolon, Typed::Key(0, "[Comma]")),
+    (NamedKey::Comma, Typed::Key(0, "[KeyM]")),
+    (NamedKey::BracketLeft, Typed::Manual("AltGr+5")),
+    (NamedKey::BracketRight, Typed::Manual("AltGr+°")),
+    (NamedKey::Backslash, Typed::Manual("AltGr+8")),
+    (NamedKey::Backquote, Typed::Manual("AltGr+7, a dead key")),
+];
+
+impl Layout {
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::De => "de",
+            Layout::Fr => "fr"
+        }
+    }
+
+    fn table(self) -> &'static [(NamedKey, Typed)] {
+        match self {
+            Layout::De => DE,
+            Layout::Fr => FR
+        }
+    }
+
+    /// The key to press for `key` on this layout, or why it needs a decision by hand.
+    /// Keys typed the same way as on a US keyboard, and physical codes, are returned unchanged.
+    pub fn key(self, key: &Key) -> std::result::Result<Key, String> {
+        match self.table().iter().find(|(c, _)| key.code == KeyCode::Named(*c)) {
+            None => Ok(key.clone()),
+            Some((c, Typed::Manual(how))) => Err(format!("`{}` is typed with {} on {}", c.name(), how, self.name())),
+            Some((c, Typed::Key(m, _))) if key.modifiers & m != 0 => {
+                Err(format!("`{}` needs shift on {}, which the binding uses already", c.name(), self.name()))
+            },
+            Some((_, Typed::Key(m, k))) => Ok(Key::new(key.modifiers | m, k))
+        }
+    }
+}
+
+/// Rewrites the keys of `bindings` for `layout`. Disables keep their keys, they name the default
+/// they remove. A binding with a key that cannot be rewritten stays as it is and is returned with
+/// the reason, so it can be fixed by hand.
+pub fn apply_layout(bindings: &mut [KeyBinding], layout: Layout) -> Vec<(usize, String)> {
+
+    let mut attention = vec!();
+
+    for (i, kb) in bindings.iter_mut().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        let first = layout.key(&kb.keys.first);
+        let rest = kb.keys.rest.iter().map(|k| layout.key(k)).collect::<std::result::Result<Vec<Key>, String>>();
+        match (first, rest) {
+            (Ok(first), Ok(rest)) => kb.keys = KeyRule { first, rest },
+            (Err(reason), _) | (_, Err(reason)) => attention.push((i, reason))
+        }
+    }
+
+    attention
+}
@@ -0,0 +1,146 @@
+//! Moving commands under a leader key: `ctrl+space` followed by a mnemonic key, the way
+//! spacemacs-like layers are built.
+
+use std::sync::Arc;
+
+use anyhow::bail;
+
+use crate::{
+    Result,
+    binding::{BindingAction, BindingSource, KeyBinding},
+    key::{Key, KeyRule, parse_key_sequence_strict},
+    resolve::removed_indices,
+    rules::wildcard_match
+};
+
+
+/// Commands to move under the leader: a command or `*` pattern, optionally with the key
+/// that follows the leader, `workbench.action.files.save=s`.
+pub struct LeaderEntry {
+    pub pattern: String,
+    pub key: Option<Key>
+}
+
+impl LeaderEntry {
+    pub fn parse(entry: &str) -> Result<LeaderEntry> {
+        let (pattern, key) = match entry.rsplit_once('=') {
+            Some((p, k)) => (p, Some(single_key(k)?)),
+            None => (entry, None)
+        };
+        if pattern.is_empty() {
+            bail!("'{}' names no command", entry);
+        }
+        Ok(LeaderEntry { pattern: pattern.to_string(), key })
+    }
+}
+
+/// A key that is not a chord, like the leader itself.
+pub fn single_key(code: &str) -> Result<Key> {
+    let keys = parse_key_sequence_strict(code)?;
+    if !keys.rest.is_empty() {
+        bail!("'{}' is a chord, expected a single key", code);
+    }
+    Ok(keys.first)
+}
+
+/// The keys a mnemonic is picked from for `command`, best first: the initials of the words of
+/// its last segment (`toggleSidebarVisibility` gives t, s, v), its other letters, then digits.
+fn mnemonics(command: &str) -> Vec<String> {
+
+    let name = command.rsplit('.').next().unwrap_or(command);
+    let initials = name.char_indices()
+        .filter(|(i, c)| c.is_ascii_alphabetic() && (*i == 0 || c.is_ascii_uppercase()))
+        .map(|(_, c)| c);
+    let letters = name.chars().filter(|c| c.is_ascii_alphabetic());
+
+    let mut keys: Vec<String> = vec!();
+    for c in initials.chain(letters).chain('0'..='9') {
+        let k = c.to_ascii_lowercase().to_string();
+        if !keys.contains(&k) {
+            keys.push(k);
+        }
+    }
+    keys
+}
+
+/// Moves the commands matching `entries` under `leader`. Every binding of a matched command
+/// gets a two-key chord leader + mnemonic with its when clause, preceded by a disable of the
+/// original unless `disable` is false; an entry with a key and no bindings is added without
+/// a when clause. Second keys already bound after the leader are never reused. Returns the
+/// new bindings and the commands that were left out, with the reason.
+pub fn leader_bindings(bindings: &[KeyBinding], leader: &Key, entries: &[LeaderEntry], disable: bool) -> (Vec<KeyBinding>, Vec<(String, String)>) {
+
+    let removed = removed_indices(bindings);
+    let active: Vec<&KeyBinding> = bindings.iter().enumerate()
+        .filter(|(i, kb)| !kb.is_disable() && !removed.contains(i))
+        .map(|(_, kb)| kb)
+        .collect();
+
+    let mut taken: Vec<Key> = active.iter()
+        .filter(|kb| kb.keys.first == *leader && kb.keys.rest.len() == 1)
+        .map(|kb| kb.keys.rest[0].clone())
+        .collect();
+
+    let mut bneu = vec!();
+    let mut skipped = vec!();
+    let mut placed: Vec<String> = vec!();
+
+    for entry in entries.iter() {
+
+        let mut commands: Vec<&str> = vec!();
+        for kb in active.iter().filter(|kb| wildcard_match(&entry.pattern, kb.command())) {
+            // already under the leader, or moved by an earlier entry
+            if !commands.contains(&kb.command()) && !placed.iter().any(|p| p == kb.command()) && kb.keys.first != *leader {
+                commands.push(kb.command());
+            }
+        }
+        if commands.is_empty() && entry.key.is_some() && !entry.pattern.contains('*') {
+            commands.push(&entry.pattern);
+        }
+        if commands.is_empty() {
+            skipped.push((entry.pattern.clone(), "no binding matches".to_string()));
+            continue;
+        }
+
+        for command in commands {
+            let key = match &entry.key {
+                Some(k) if taken.contains(k) => {
+                    skipped.push((command.to_string(), format!("{} {} is already bound", leader, k)));
+                    continue;
+                },
+                Some(k) => Some(k.clone()),
+                None => mnemonics(command).into_iter()
+                    .map(|name| Key::new(0, &name))
+                    .find(|k| !taken.contains(k))
+            };
+            let Some(key) = key else {
+                skipped.push((command.to_string(), "every mnemonic key is taken".to_string()));
+                continue;
+            };
+
+            let keys = KeyRule { first: leader.clone(), rest: vec!(key.clone()) };
+            let originals: Vec<&&KeyBinding> = active.iter().filter(|kb| kb.command() == command).collect();
+            if originals.is_empty() {
+                bneu.push(KeyBinding {
+                    keys: keys.clone(), action: BindingAction::Run(command.to_string()), when: None, args: None, args_file: None, original: None,
+                    source: Some(Arc::new(BindingSource::Generated { by: "leader".to_string(), from: None }))
+                });
+            }
+            let mut moved: Vec<(&Option<String>, &Option<serde_json::Value>)> = vec!();
+            for kb in originals {
+                if disable {
+                    bneu.push(kb.copy_disabled().generated("leader"));
+                }
+                // a command bound to several keys in the same context needs the chord once
+                if !moved.contains(&(&kb.when, &kb.args)) {
+                    moved.push((&kb.when, &kb.args));
+                    bneu.push(KeyBinding { keys: keys.clone(), ..(*kb).clone() }.generated("leader"));
+                }
+            }
+            taken.push(key);
+            placed.push(command.to_string());
+        }
+    }
+
+    (bneu, skipped)
+}
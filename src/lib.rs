@@ -0,0 +1,52 @@
+//! Parsing and transformation of Visual Studio Code keybindings.
+//!
+//! [`binding::KeyBinding`] is the model everything works on, it is read from a
+//! keybindings file with [`input::load_bindings`] and written back as
+//! [`binding::ConfigItem`]s. [`map`] holds the ctrl <-> cmd remap the `codekeys`
+//! binary is built around, [`rules`] and [`pipeline`] more general transformations.
+
+pub mod binding;
+pub mod browser;
+pub mod build;
+pub mod check;
+pub mod cheatsheet;
+pub mod commands;
+pub mod compose;
+pub mod config;
+pub mod conflict;
+pub mod describe;
+pub mod diagnostic;
+pub mod diff;
+pub mod extensions;
+pub mod fetch;
+pub mod format;
+pub mod input;
+pub mod install;
+pub mod karabiner;
+pub mod key;
+pub mod layout;
+pub mod leader;
+pub mod lint;
+pub mod map;
+pub mod merge;
+pub mod order;
+pub mod par;
+pub mod paths;
+pub mod pipeline;
+pub mod preset;
+pub mod profile;
+pub mod query;
+pub mod render;
+pub mod resolution;
+pub mod resolve;
+pub mod rules;
+pub mod schema;
+pub mod stats;
+pub mod suggest;
+pub mod taxonomy;
+pub mod when;
+
+pub use binding::{ConfigItem, KeyBinding};
+pub use key::{Key, KeyRule};
+
+pub type Result<T> = anyhow::Result<T>;
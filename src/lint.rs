@@ -0,0 +1,252 @@
+//! Lint rules for keymaps: entries the lenient parsers accept but VS Code would not, and
+//! entries that are valid but most likely mistakes. Each rule has a severity, set by a profile
+//! and overridden per rule in the `[lint]` table of the config file.
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    Result,
+    binding::KeyBinding,
+    build::spell_key,
+    check::orphaned_disables,
+    conflict::find_conflicts,
+    fetch::Platform,
+    key::{parse_key_sequence_strict, parse_one_key},
+    resolve::removed_indices,
+    when::parse_when
+};
+
+
+/// A check of the linter.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    /// a key string the strict parser rejects
+    InvalidKey,
+    /// a when clause that does not parse
+    InvalidWhen,
+    /// a binding a later one on the same chord always wins over
+    UnreachableBinding,
+    /// the same chord, when clause and command as an earlier binding
+    DuplicateChord,
+    /// a disable of a command that is bound nowhere else, so it is left without a key
+    DisabledNeverRebound,
+    /// a key press with all four modifiers
+    ManyModifiers,
+    /// a key string not written the way VS Code writes it, e.g. `Shift+Ctrl+K` or `esc`
+    NonCanonicalKey,
+    /// a terminal command without a when clause, so it runs everywhere
+    TerminalWithoutWhen
+}
+
+/// The rules with their names in the config file and the output.
+pub const LINT_RULES: &[(LintRule, &str)] = &[
+    (LintRule::InvalidKey, "invalid-key"),
+    (LintRule::InvalidWhen, "invalid-when"),
+    (LintRule::UnreachableBinding, "unreachable-binding"),
+    (LintRule::DuplicateChord, "duplicate-chord"),
+    (LintRule::DisabledNeverRebound, "disabled-never-rebound"),
+    (LintRule::ManyModifiers, "many-modifiers"),
+    (LintRule::NonCanonicalKey, "non-canonical-key"),
+    (LintRule::TerminalWithoutWhen, "terminal-without-when"),
+];
+
+impl LintRule {
+    pub fn name(self) -> &'static str {
+        LINT_RULES.iter().find(|(r, _)| *r == self).map_or("", |(_, n)| n)
+    }
+
+    pub fn from_name(name: &str) -> Result<LintRule> {
+        LINT_RULES.iter().find(|(_, n)| *n == name).map(|(r, _)| *r).ok_or_else(|| {
+            let names: Vec<&str> = LINT_RULES.iter().map(|(_, n)| *n).collect();
+            anyhow!("unknown lint rule {}, the rules are {}", name, names.join(", "))
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Severity {
+    Off,
+    Warn,
+    Error
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Severity::Off => "off",
+            Severity::Warn => "warning",
+            Severity::Error => "error"
+        })
+    }
+}
+
+/// A set of severities for all rules.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum LintProfile {
+    /// Only entries VS Code cannot use are errors
+    Minimal,
+    /// Entries VS Code cannot use are errors, unreachable and repeated bindings and
+    /// four modifier chords warnings
+    #[default]
+    Default,
+    /// Every rule is an error
+    Strict
+}
+
+impl LintProfile {
+    pub fn severity(self, rule: LintRule) -> Severity {
+        match (self, rule) {
+            (_, LintRule::InvalidKey | LintRule::InvalidWhen) => Severity::Error,
+            (LintProfile::Strict, _) => Severity::Error,
+            (LintProfile::Default, LintRule::UnreachableBinding | LintRule::DuplicateChord | LintRule::ManyModifiers) => Severity::Warn,
+            _ => Severity::Off
+        }
+    }
+}
+
+/// The `[lint]` table of the config file: a `profile` and the severity of single rules,
+/// `rules = { "terminal-without-when" = "warn" }`.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    pub profile: Option<LintProfile>,
+    pub rules: HashMap<String, Severity>
+}
+
+/// The severity of each rule.
+pub struct LintSettings {
+    severities: HashMap<LintRule, Severity>
+}
+
+impl LintSettings {
+    /// The severities of `profile` with those of `overrides`, by rule name, in their place.
+    pub fn new(profile: LintProfile, overrides: &HashMap<String, Severity>) -> Result<LintSettings> {
+        let mut severities: HashMap<LintRule, Severity> = LINT_RULES.iter().map(|(r, _)| (*r, profile.severity(*r))).collect();
+        for (name, severity) in overrides.iter() {
+            severities.insert(LintRule::from_name(name)?, *severity);
+        }
+        Ok(LintSettings { severities })
+    }
+
+    pub fn severity(&self, rule: LintRule) -> Severity {
+        self.severities.get(&rule).copied().unwrap_or(Severity::Off)
+    }
+}
+
+impl Default for LintSettings {
+    fn default() -> Self {
+        LintSettings { severities: LINT_RULES.iter().map(|(r, _)| (*r, LintProfile::Default.severity(*r))).collect() }
+    }
+}
+
+/// A finding of a rule.
+#[derive(Serialize)]
+pub struct LintIssue {
+    pub index: usize,
+    /// the key string as it was written
+    pub key: String,
+    pub command: String,
+    pub rule: LintRule,
+    pub severity: Severity,
+    pub message: String
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}: [{:>4}] {:?} {}: {} ({})", self.severity, self.index, self.key, self.command, self.message, self.rule.name())
+    }
+}
+
+/// The key string of `kb` as it was read, the parsed keys if it was not read from a file.
+fn source_key(kb: &KeyBinding) -> String {
+    kb.original.as_ref()
+        .and_then(|o| o.get("key"))
+        .and_then(Value::as_str)
+        .map_or_else(|| kb.keys.to_string(), String::from)
+}
+
+/// `key` the way VS Code writes it, with the command modifier spelled like the platform
+/// it names (`cmd` macOS, `win` Windows, Linux otherwise).
+fn canonical_key(key: &str) -> String {
+    let platform = match key.to_lowercase() {
+        k if k.contains("cmd") => Platform::Macos,
+        k if k.contains("win") => Platform::Windows,
+        _ => Platform::Linux
+    };
+    let presses: Vec<String> = key.split_ascii_whitespace().map(|p| spell_key(&parse_one_key(p), platform)).collect();
+    presses.join(" ")
+}
+
+/// The issues of the rules `settings` does not turn off, in the order of the entries.
+pub fn lint(bindings: &[KeyBinding], settings: &LintSettings) -> Vec<LintIssue> {
+
+    let removed = removed_indices(bindings);
+    let orphaned = orphaned_disables(bindings);
+    let mut first_seen: HashMap<(String, &Option<String>, String, String), usize> = HashMap::new();
+
+    let mut issues = vec!();
+    let mut report = |index: usize, rule: LintRule, message: String| {
+        let severity = settings.severity(rule);
+        if severity != Severity::Off {
+            let kb = &bindings[index];
+            issues.push(LintIssue { index, key: source_key(kb), command: kb.action.to_string(), rule, severity, message });
+        }
+    };
+
+    for (index, kb) in bindings.iter().enumerate() {
+        let key = source_key(kb);
+        match parse_key_sequence_strict(&key) {
+            Err(e) => report(index, LintRule::InvalidKey, e.to_string()),
+            Ok(_) => {
+                let canonical = canonical_key(&key);
+                if canonical != key {
+                    report(index, LintRule::NonCanonicalKey, format!("VS Code writes {:?}", canonical));
+                }
+            }
+        }
+        if let Some(Err(e)) = kb.when.as_deref().map(parse_when) {
+            report(index, LintRule::InvalidWhen, format!("{:#}", e));
+        }
+        if kb.keys.keys().any(|k| k.modifiers.count_ones() >= 4) {
+            report(index, LintRule::ManyModifiers, "presses all four modifiers at once".to_string());
+        }
+
+        if kb.is_disable() {
+            if orphaned.contains(&index) {
+                report(index, LintRule::DisabledNeverRebound, format!("{} is not bound anywhere else, it is left without a key", kb.command()));
+            }
+            continue;
+        }
+        if kb.when.is_none() && kb.command().starts_with("workbench.action.terminal.") {
+            report(index, LintRule::TerminalWithoutWhen, "has no when clause, it runs outside the terminal as well".to_string());
+        }
+        if removed.contains(&index) {
+            continue;
+        }
+        let entry = (kb.keys.to_string(), &kb.when, kb.action.to_string(), kb.args.as_ref().map(Value::to_string).unwrap_or_default());
+        match first_seen.get(&entry) {
+            Some(&first) => report(index, LintRule::DuplicateChord, format!("repeats [{}]", first)),
+            None => { first_seen.insert(entry, index); }
+        }
+    }
+
+    for c in find_conflicts(bindings) {
+        for e in c.entries.iter() {
+            if let Some(by) = e.shadowed_by {
+                report(e.index, LintRule::UnreachableBinding, format!("never runs, [{}] {} always wins", by, bindings[by].action));
+            }
+        }
+    }
+
+    issues.sort_by_key(|i| i.index);
+    issues
+}
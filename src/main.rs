@@ -1,233 +1,1683 @@
-use std::{path::{Path, PathBuf}, fs::File};
+use std::{ffi::OsString, io::{IsTerminal, Write}, path::{Path, PathBuf}, sync::Arc};
 
-use serde::Serialize;
-use serde_derive::{Serialize, Deserialize};
-use serde_json::Value;
+use anyhow::{anyhow, bail};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "watch")]
+mod watch;
 
-type Result<T> = anyhow::Result<T>;
+use codekeys::{
+    Result,
+    binding::{BindingSource, KeyBinding, to_config_items, to_preserved_entries},
+    build::build,
+    cheatsheet::{GroupBy, SheetFormat, cheatsheet, render_html, render_markdown},
+    check::{check, orphaned_disable_problems, orphaned_disables_with},
+    commands::{load_commands, unknown_commands},
+    compose::compose,
+    config::{Config, config_path, init_config, load_config},
+    conflict::{ConflictPolicy, find_collisions, resolve_collisions, settle_collisions},
+    describe::describe,
+    diff::diff_bindings,
+    extensions::{Extension, extension_bindings, scan_extensions},
+    fetch::{Platform, fetch_defaults, resolve_cached},
+    format::{helix::to_helix, jetbrains::to_jetbrains, os::{Remapper, to_os_remap}, sublime::to_sublime, terminal::{Terminal, to_terminal}, zed::to_zed},
+    input::{InputFormat, TomlBindings, load_bindings},
+    karabiner::{apply_key_mappings, key_mappings, parse_complex_modifications, to_complex_modifications},
+    key::{parse_key_sequence_strict, parse_modifier, to_physical},
+    layout::{Layout, apply_layout},
+    leader::{LeaderEntry, leader_bindings, single_key},
+    lint::{LintProfile, LintSettings, Severity, lint},
+    map::{Direction, ModifierOp, RemapCounts, modify_bindings, remap_bindings},
+    merge::{cancel_rebinds, is_overridden, merge_user_bindings, resolve_user_conflicts},
+    order::{Sections, SortOrder, arrange, swapped_precedence, to_jsonc_sections},
+    install::{history, install, rollback},
+    paths::{Editor, extensions_dir, vscode_user_dir},
+    pipeline::{apply_pipeline, load_pipeline},
+    preset::{Preset, apply_preset},
+    profile::{active_profile, create_profile, list_profiles, open_profile, switch_profile},
+    query::{Query, query},
+    render::render_template,
+    resolution::{Resolution, Resolutions, load_resolutions, save_resolutions},
+    resolve::{effective, resolve, resolve_chord},
+    suggest::free_chords,
+    taxonomy::{Taxonomy, load_taxonomy},
+    rules::{apply_rules, load_macros, load_rules},
+    schema::{SchemaKind, schema},
+    stats::stats,
+    when::{parse_when, rewrite_when, simplify_when, within}
+};
 
 
-const MODIFIER_SHIFT: usize = 1;
-const MODIFIER_CONTROL: usize = 2;
-const MODIFIER_COMMAND: usize = 4;
-const MODIFIER_OPTION: usize = 8;
+#[derive(Parser)]
+#[command(about = "Visual Studio Code Unified Keymap for Mac and Linux")]
+struct Cli {
+    /// Config file for `apply` and `lint`, instead of codekeys.toml in the config directory
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
 
+    #[command(subcommand)]
+    command: Command
+}
 
+#[derive(Subcommand)]
+enum Command {
+    /// Remap the ctrl bindings of a keybindings file to cmd (or back with --direction)
+    Map(MapArgs),
+    /// Remap the defaults the user file does not override and append the user bindings
+    Merge(MergeArgs),
+    /// Check a keybindings file for unusable entries and conflicting chords
+    Check(CheckArgs),
+    /// Store the default keybindings of a VS Code version in the local cache
+    Fetch(FetchArgs),
+    /// Show the bindings added, removed and changed between two files
+    Diff(DiffArgs),
+    /// Render the bindings as a printable Markdown or HTML cheat sheet
+    Cheatsheet(CheatsheetArgs),
+    /// Write the bindings through a handlebars template, for formats codekeys has no backend for
+    Render(RenderArgs),
+    /// Write the remap as Karabiner-Elements complex modifications to apply it system-wide
+    Karabiner(KarabinerArgs),
+    /// Report entries with invalid key strings or when clauses
+    Lint(LintArgs),
+    /// Print the parsed bindings, chords taken apart and when clauses as trees, for other tools
+    Describe(DescribeArgs),
+    /// Print the JSON Schema of a keybindings, build source or rules file
+    Schema {
+        #[arg(value_enum)]
+        kind: SchemaKind
+    },
+    /// Rewrite the when clauses of a keybindings file
+    When {
+        #[command(subcommand)]
+        command: WhenCommand
+    },
+    /// List the bindings matching a command, key or when clause
+    Query(QueryArgs),
+    /// Report modifier usage, overloaded keys, unbound commands and unreachable bindings
+    Stats(StatsArgs),
+    /// Resolve a user file on top of the defaults into the chords and commands in effect
+    Effective(EffectiveArgs),
+    /// List the commands a key or chord can trigger, in the order VS Code tries them
+    Resolve(ResolveArgs),
+    /// List the chords under a prefix, or with given modifiers, that nothing is bound to
+    Suggest(SuggestArgs),
+    /// Write a built-in keymap with disables of the defaults it shadows
+    Preset(PresetArgs),
+    /// Move commands under a leader key followed by a mnemonic key
+    Leader(LeaderArgs),
+    /// Write the keybindings of one platform from a source file for all of them
+    Build(BuildArgs),
+    /// Collect the keybindings installed extensions contribute, after the defaults if given
+    ScanExtensions(ScanExtensionsArgs),
+    /// Put back an earlier keybindings.json replaced by `--install` or `profile switch`
+    Rollback(RollbackArgs),
+    /// Browse the bindings interactively and export the ones picked
+    #[cfg(feature = "tui")]
+    Tui(TuiArgs),
+    /// Manage named sets of defaults and rules and switch between them
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand
+    },
+    /// Run `map` (or `merge` with a user file) with the settings of the config file
+    Apply,
+    /// Run `apply` again whenever the config file or the files it names change
+    #[cfg(feature = "watch")]
+    Watch(WatchArgs),
+    /// Fail if the bindings `apply` generates differ from a golden file
+    Verify(VerifyArgs),
+    /// Manage the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand
+    },
+    /// Print the completion script of a shell, e.g. `codekeys completions bash > /etc/bash_completion.d/codekeys`
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell
+    }
+}
 
-#[derive(Serialize,Deserialize)]
-struct ConfigItem {
-    key: String,
-    command: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    when: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    args: Option<Value>
+#[derive(Subcommand)]
+enum WhenCommand {
+    /// Normalize and minimize the when clauses, dropping repeated, constant and absorbed terms
+    Simplify(WhenSimplifyArgs),
 }
 
-impl From<&KeyBinding> for ConfigItem {
-    fn from(kb: &KeyBinding) -> Self {
-        ConfigItem { 
-            key:  format!("{}", kb.keys), 
-            command: kb.command.clone(), 
-            when: kb.when.clone(), 
-            args: kb.args.clone() 
-        }
-    }
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Write a commented config file to start from
+    Init {
+        /// Replace an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print where the config file is looked for
+    Path,
 }
 
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// Store a new profile from a defaults file and an optional rules file
+    Create {
+        name: String,
 
-#[derive(Clone)]
-struct KeyBinding {
-    keys: KeyRule,
-    command : String,
-    when: Option<String>,
-    args: Option<Value>
+        /// The defaults the profile starts from
+        #[arg(long, default_value = "keys/default.json")]
+        defaults: PathBuf,
+
+        /// Rules transforming the defaults, without them the plain ctrl -> cmd remap is used
+        #[arg(long)]
+        rules: Option<PathBuf>,
+    },
+    /// List the stored profiles, the active one marked with `*`
+    List,
+    /// Generate the keybindings of a profile and install them
+    Switch {
+        name: String,
+
+        /// The keybindings.json to replace, VS Code's user keybindings by default
+        #[arg(long)]
+        target: Option<PathBuf>,
+
+        /// The VS Code build whose user keybindings are replaced, the first one found by default
+        #[arg(long, value_enum, conflicts_with = "target")]
+        editor: Option<Editor>,
+    },
+    /// Write the keybindings a profile generates
+    Export {
+        name: String,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    }
 }
 
+#[derive(clap::Args)]
+struct InputArgs {
+    /// Keybindings file to read, `-` reads from stdin
+    #[arg(long, short, default_value = "keys/default.json")]
+    input: PathBuf,
+
+    /// Read the keybindings from stdin, same as `--input -`
+    #[arg(long, conflicts_with = "input")]
+    stdin: bool,
+
+    /// Read defaults stored with `codekeys fetch`, e.g. `macos@1.90`
+    #[arg(long, value_name = "PLATFORM@VERSION", conflicts_with_all = ["input", "stdin"])]
+    cached: Option<String>,
+
+    /// Format of the input files
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    input_format: InputFormat,
+
+    /// Add the keybindings the extensions installed in this directory contribute after the input's,
+    /// e.g. `~/.vscode/extensions`
+    #[arg(long, value_name = "DIR")]
+    ext_dir: Option<PathBuf>,
+}
 
-impl KeyBinding {
-    fn has_control(&self) -> bool {
-        self.keys.first.modifiers & MODIFIER_CONTROL != 0
+impl InputArgs {
+    fn path(&self) -> Result<PathBuf> {
+        match &self.cached {
+            Some(reference) => resolve_cached(reference),
+            None if self.stdin => Ok(PathBuf::from("-")),
+            None => Ok(self.input.clone())
+        }
     }
 
-    fn copy_disabled(&self) -> Self {
-        if self.command.starts_with("-") {
-            self.clone()
-        } else {
-            KeyBinding {
-                keys: self.keys.clone(),
-                command: format!("-{}", self.command),
-                when: self.when.clone(),
-                args: self.args.clone()
-            }
+    fn load(&self) -> Result<Vec<KeyBinding>> {
+        let mut bindings = load_bindings(&self.path()?, self.input_format)?;
+        if let Some(dir) = &self.ext_dir {
+            bindings.extend(extension_bindings(&scan_extensions_warning(dir, Platform::current())?));
         }
+        Ok(bindings)
     }
+}
+
+#[derive(clap::Args, Clone)]
+struct OutputArgs {
+    /// File to write the generated keybindings to, instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Write the generated keybindings to stdout (the default)
+    #[arg(long, conflicts_with = "output")]
+    stdout: bool,
 
+    /// Replace the keybindings.json of the installed VS Code, keeping the previous file for `rollback`
+    #[arg(long, conflicts_with_all = ["output", "stdout"])]
+    install: bool,
+
+    /// The VS Code build to install into, the first one found by default
+    #[arg(long, value_enum, requires = "install")]
+    editor: Option<Editor>,
+
+    /// Write `{ "$file": ... }` args references back instead of the inlined content
+    #[arg(long)]
+    keep_refs: bool,
+
+    /// Write entries in the shape they were read: unchanged keys keep their spelling,
+    /// fields keep their order and unknown fields are kept
+    #[arg(long)]
+    preserve: bool,
+
+    /// Keymap format to write
+    #[arg(long, value_enum, default_value_t = OutputFormat::Vscode)]
+    format: OutputFormat,
+
+    /// Order to write the bindings in, ties keep the generated order
+    #[arg(long, value_enum, default_value_t = SortOrder::SourceOrder)]
+    sort: SortOrder,
+
+    /// Group the bindings into sections headed by `// --- title ---` comments
+    #[arg(long, value_enum)]
+    sections: Option<Sections>,
+
+    /// Write a `// from ...` comment before every binding a rule, remap or extension made
+    #[arg(long)]
+    source_comments: bool,
 }
 
+impl OutputArgs {
+    fn write(&self, bindings: &[KeyBinding]) -> Result<()> {
+        self.write_as(bindings, self.preserve)
+    }
+
+    /// Writes the bindings, with `preserve` in the shape they were read whatever `--preserve` says.
+    fn write_as(&self, bindings: &[KeyBinding], preserve: bool) -> Result<()> {
 
-impl From<ConfigItem> for KeyBinding {
-    fn from(ci: ConfigItem) -> Self {
-        KeyBinding {
-            keys: parse_key_sequence(&ci.key),
-            command: ci.command,
-            when: ci.when,
-            args: ci.args
+        if self.sections.is_some() && self.format != OutputFormat::Vscode {
+            bail!("--sections needs --format vscode, the other formats have no place for the headers");
+        }
+        if self.source_comments && self.format != OutputFormat::Vscode {
+            bail!("--source-comments needs --format vscode, the other formats have no place for comments");
+        }
+        let order = arrange(bindings, self.sort, self.sections);
+        for (a, b) in swapped_precedence(bindings, &order) {
+            let (ka, kb) = (&bindings[a], &bindings[b]);
+            eprintln!("warning: [{:>4}] {} {} now comes after [{}] {} and wins over it", a, ka.keys, ka.action, b, kb.action);
         }
+        let bindings: &[KeyBinding] = &order.iter().map(|i| bindings[*i].clone()).collect::<Vec<_>>();
+
+        let left_out = |i: usize, reason: &str| {
+            let kb = &bindings[i];
+            eprintln!("warning: [{:>4}] {} {}: {}, left out", i, kb.keys, kb.action, reason);
+        };
+        let text = match self.format {
+            OutputFormat::Vscode if self.sections.is_some() || self.source_comments => {
+                let entries = match preserve {
+                    true => to_preserved_entries(bindings, self.keep_refs),
+                    false => to_config_items(bindings, self.keep_refs).iter().map(serde_json::to_value).collect::<serde_json::Result<_>>()?
+                };
+                let titles: Option<Vec<String>> = self.sections.map(|s| bindings.iter().map(|kb| s.title(kb)).collect());
+                let notes: Vec<Option<String>> = bindings.iter()
+                    .map(|kb| kb.source.as_ref().filter(|s| self.source_comments && !s.is_file()).map(|s| format!("from {}", s)))
+                    .collect();
+                to_jsonc_sections(&entries, titles.as_deref(), &notes)?
+            },
+            OutputFormat::Vscode if preserve => serde_json::to_string_pretty(&to_preserved_entries(bindings, self.keep_refs))?,
+            OutputFormat::Vscode => serde_json::to_string_pretty(&to_config_items(bindings, self.keep_refs))?,
+            OutputFormat::Yaml | OutputFormat::Toml => {
+                let entries = match preserve {
+                    true => to_preserved_entries(bindings, self.keep_refs),
+                    false => to_config_items(bindings, self.keep_refs).iter().map(serde_json::to_value).collect::<serde_json::Result<_>>()?
+                };
+                match self.format {
+                    OutputFormat::Yaml => serde_yaml::to_string(&entries)?.trim_end().to_string(),
+                    _ => toml::to_string(&TomlBindings { binding: entries })?.trim_end().to_string()
+                }
+            },
+            OutputFormat::Sublime => {
+                let (entries, skipped) = to_sublime(bindings);
+                skipped.iter().for_each(|(i, reason)| left_out(*i, reason));
+                serde_json::to_string_pretty(&entries)?
+            },
+            OutputFormat::Jetbrains => {
+                let (xml, skipped) = to_jetbrains(bindings, "codekeys");
+                skipped.iter().for_each(|(i, reason)| left_out(*i, reason));
+                xml.trim_end().to_string()
+            },
+            OutputFormat::Zed => {
+                let (sections, skipped) = to_zed(bindings);
+                skipped.iter().for_each(|(i, reason)| left_out(*i, reason));
+                serde_json::to_string_pretty(&sections)?
+            },
+            OutputFormat::Helix => {
+                let (keys, skipped) = to_helix(bindings);
+                skipped.iter().for_each(|(i, reason)| left_out(*i, reason));
+                toml::to_string(&keys)?.trim_end().to_string()
+            },
+            OutputFormat::Kitty | OutputFormat::Alacritty | OutputFormat::Wezterm => {
+                let terminal = match self.format {
+                    OutputFormat::Kitty => Terminal::Kitty,
+                    OutputFormat::Alacritty => Terminal::Alacritty,
+                    _ => Terminal::Wezterm
+                };
+                let (text, skipped) = to_terminal(bindings, terminal);
+                skipped.iter().for_each(|(i, reason)| left_out(*i, reason));
+                text
+            },
+            OutputFormat::Autohotkey | OutputFormat::Keyd | OutputFormat::Xremap => {
+                let remapper = match self.format {
+                    OutputFormat::Autohotkey => Remapper::Autohotkey,
+                    OutputFormat::Keyd => Remapper::Keyd,
+                    _ => Remapper::Xremap
+                };
+                let (text, skipped) = to_os_remap(bindings, remapper);
+                skipped.iter().for_each(|(i, reason)| left_out(*i, reason));
+                text
+            }
+        };
+        if self.install {
+            let target = vscode_user_dir(self.editor)?.join("keybindings.json");
+            match install(&target, &(text + "\n"))? {
+                Some(backup) => eprintln!("installed {}, the previous file is in {}", target.display(), backup.display()),
+                None => eprintln!("installed {}", target.display())
+            }
+            return Ok(());
+        }
+        match &self.output {
+            Some(path) => std::fs::write(path, text + "\n")
+                .map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))?,
+            None => println!("{}", text)
+        }
+        Ok(())
     }
 }
 
-#[derive(Clone,PartialEq, Eq, Hash)]
-struct KeyRule {
-    first: Key,
-    second: Option<Key>
+#[derive(clap::Args)]
+struct RemapArgs {
+    /// Which modifier is replaced by which
+    #[arg(long, value_enum, default_value_t = Direction::CtrlToCmd)]
+    direction: Direction,
+
+    /// Exchange two modifiers instead of the direction's remap, e.g. `ctrl,alt`, may be repeated
+    #[arg(long, value_name = "A,B", conflicts_with_all = ["pipeline", "rules", "karabiner"])]
+    swap: Vec<String>,
+
+    /// Add a modifier to every key, after the swaps, may be repeated
+    #[arg(long, value_name = "MODIFIER", conflicts_with_all = ["pipeline", "rules", "karabiner"])]
+    add_modifier: Vec<String>,
+
+    /// Remove a modifier from every key, after the additions, may be repeated
+    #[arg(long, value_name = "MODIFIER", conflicts_with_all = ["pipeline", "rules", "karabiner"])]
+    remove_modifier: Vec<String>,
+
+    /// Do not emit `-command` entries disabling the original bindings
+    #[arg(long)]
+    no_disable: bool,
+
+    /// Fail if bindings were remapped but no disable entries were emitted
+    #[arg(long)]
+    assert_disables: bool,
+
+    /// Rewrite the generated bindings to physical key codes of the US layout (`p` -> `[KeyP]`)
+    #[arg(long)]
+    to_physical: bool,
+
+    /// Rewrite the generated bindings for a keyboard layout, keys typed with AltGr or dead keys
+    /// are reported for fixing by hand
+    #[arg(long, value_enum, conflicts_with = "to_physical")]
+    layout: Option<Layout>,
+
+    /// Warn about generated `-command` entries whose command is not bound anywhere in the input or the output
+    #[arg(long)]
+    warn_orphan_disables: bool,
+
+    /// Apply an ordered list of transform steps instead of the plain remap,
+    /// given inline as a JSON array or as the path of a JSON file
+    #[arg(long, conflicts_with_all = ["rules", "karabiner"])]
+    pipeline: Option<String>,
+
+    /// Transform with the rules of a TOML or JSON rules file instead of the plain remap
+    #[arg(long, conflicts_with = "karabiner")]
+    rules: Option<PathBuf>,
+
+    /// Move bindings the way the simple rules of a Karabiner-Elements complex modifications
+    /// file remap keys, instead of the plain remap
+    #[arg(long)]
+    karabiner: Option<PathBuf>,
+
+    /// Only transform bindings whose when clause is scoped within this expression,
+    /// e.g. `editorTextFocus`, other bindings are left alone
+    #[arg(long, value_name = "EXPR")]
+    when: Option<String>,
+
+    /// Leave bindings whose when clause is scoped within this expression alone, e.g. `terminalFocus`
+    #[arg(long, value_name = "EXPR")]
+    not_when: Option<String>,
+
+    /// Look for generated bindings landing on a chord and when clause a default still uses,
+    /// report them and settle them this way
+    #[arg(long, value_enum)]
+    on_conflict: Option<ConflictPolicy>,
+
+    /// Settle conflicts with the decisions recorded in this TOML file, the others are settled
+    /// by --on-conflict or reported
+    #[arg(long, value_name = "FILE")]
+    resolutions: Option<PathBuf>,
+
+    /// Walk through each conflict without a recorded decision, ask whether to keep, replace,
+    /// disable or skip it and record the answer in the resolutions file
+    #[arg(long, requires = "resolutions")]
+    interactive: bool,
+}
+
+/// The decisions of a resolutions file, asking for the missing ones when interactive.
+struct Resolver {
+    path: PathBuf,
+    resolutions: Resolutions,
+    interactive: bool,
+    asked: usize
+}
+
+fn with_source(kb: &KeyBinding) -> String {
+    match kb.source.as_ref() {
+        Some(source) => format!("{}  from {}", kb.action, source),
+        None => kb.action.to_string()
+    }
 }
 
-impl std::fmt::Display for KeyRule {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.first.fmt(f)?;
-        if let Some(s) = &self.second {
-            write!(f, " ")?;
-            s.fmt(f)?;
+impl Resolver {
+    fn open(args: &RemapArgs) -> Result<Option<Resolver>> {
+        let Some(path) = &args.resolutions else { return Ok(None) };
+        if args.interactive && !std::io::stdin().is_terminal() {
+            bail!("--interactive asks on the terminal, stdin is not one");
         }
-        Ok(())
+        Ok(Some(Resolver { path: path.clone(), resolutions: load_resolutions(path)?, interactive: args.interactive, asked: 0 }))
+    }
+
+    /// The recorded resolution of `new` landing on `old`, otherwise the answer to the prompt
+    /// when interactive, which is saved right away.
+    fn decide(&mut self, old: &KeyBinding, new: &KeyBinding) -> Result<Option<Resolution>> {
+        let (old_command, new_command) = (old.action.to_string(), new.action.to_string());
+        if let Some(r) = self.resolutions.get(&new.keys, &new.when, &old_command, &new_command) {
+            return Ok(Some(r));
+        }
+        if !self.interactive {
+            return Ok(None);
+        }
+
+        self.asked += 1;
+        eprint!("\nconflict {}: {}", self.asked, new.keys);
+        if let Some(w) = &new.when {
+            eprint!("  when {}", w);
+        }
+        eprintln!("\n  old  {}\n  new  {}", with_source(old), with_source(new));
+        let resolution = loop {
+            eprint!("keep old, replace with new, disable both or skip? [k/r/d/s] ");
+            std::io::stderr().flush()?;
+            let mut answer = String::new();
+            if std::io::stdin().read_line(&mut answer)? == 0 {
+                bail!("no answer for the conflict on {}", new.keys);
+            }
+            if let Some(r) = Resolution::parse_answer(&answer) {
+                break r;
+            }
+        };
+        self.resolutions.record(&new.keys, &new.when, &old_command, &new_command, resolution);
+        save_resolutions(&self.path, &self.resolutions)?;
+        Ok(Some(resolution))
     }
 }
 
+impl RemapArgs {
+    fn apply(&self, bindings: Vec<KeyBinding>) -> Result<(Vec<KeyBinding>, RemapCounts)> {
 
+        for context in [&self.when, &self.not_when].into_iter().flatten() {
+            parse_when(context)?;
+        }
+        let scoped: Vec<KeyBinding> = bindings.iter()
+            .filter(|kb| self.when.as_ref().is_none_or(|c| within(&kb.when, c)))
+            .filter(|kb| self.not_when.as_ref().is_none_or(|c| !within(&kb.when, c)))
+            .cloned()
+            .collect();
 
+        let mut ops: Vec<ModifierOp> = self.swap.iter().map(|p| ModifierOp::parse_swap(p)).collect::<Result<_>>()?;
+        for m in self.add_modifier.iter() {
+            ops.push(ModifierOp::Add(parse_modifier(m)?));
+        }
+        for m in self.remove_modifier.iter() {
+            ops.push(ModifierOp::Remove(parse_modifier(m)?));
+        }
 
+        let mut counts = RemapCounts::default();
+        let mut bneu = match (&self.pipeline, &self.rules, &self.karabiner) {
+            (Some(spec), _, _) => apply_pipeline(scoped, &load_pipeline(spec)?, self.direction, !self.no_disable, &mut counts)?,
+            (_, Some(path), _) => {
+                let mut bneu = apply_rules(&scoped, &load_rules(path)?, !self.no_disable, &mut counts)?;
+                bneu.extend(load_macros(path)?);
+                bneu
+            },
+            (_, _, Some(path)) => {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+                apply_key_mappings(&scoped, &parse_complex_modifications(&content)?, !self.no_disable, &mut counts)
+            },
+            _ if !ops.is_empty() => modify_bindings(&scoped, &ops, !self.no_disable, &mut counts),
+            _ => remap_bindings(&scoped, self.direction, !self.no_disable, &mut counts)
+        };
 
-#[derive(Clone,PartialEq, Eq, Hash)]
-struct Key {
-    modifiers: usize,
-    key: String
-}
+        if self.to_physical {
+            // disables keep the original spelling so they still match the default they remove
+            for kb in bneu.iter_mut().filter(|kb| !kb.is_disable()) {
+                kb.keys = kb.keys.map(to_physical);
+            }
+        }
 
-impl std::fmt::Display for Key {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.modifiers & MODIFIER_COMMAND != 0 {
-            write!(f, "meta+")?
+        if let Some(layout) = self.layout {
+            for (i, reason) in apply_layout(&mut bneu, layout) {
+                eprintln!("warning: [{:>4}] {} {}: {}, left as it is", i, bneu[i].keys, bneu[i].action, reason);
+            }
         }
-        if self.modifiers & MODIFIER_OPTION != 0 {
-            write!(f, "alt+")?
+
+        if let Some(mut resolver) = Resolver::open(self)? {
+            let collisions = find_collisions(&bindings, &bneu);
+            let mut resolutions = vec!();
+            for c in collisions.iter() {
+                let resolution = match resolver.decide(&bindings[c.default], &bneu[c.generated])? {
+                    Some(r) => r,
+                    None => {
+                        eprintln!("shadowed: {}", c);
+                        match self.on_conflict {
+                            Some(policy) => policy.resolution().ok_or_else(|| anyhow!("no resolution recorded for {}", c))?,
+                            None => Resolution::Skip
+                        }
+                    }
+                };
+                resolutions.push(resolution);
+            }
+            bneu = settle_collisions(&bindings, bneu, &collisions, &resolutions);
+        } else if let Some(policy) = self.on_conflict {
+            let collisions = find_collisions(&bindings, &bneu);
+            for c in collisions.iter().filter(|_| policy != ConflictPolicy::Error) {
+                eprintln!("shadowed: {}", c);
+            }
+            bneu = resolve_collisions(&bindings, bneu, &collisions, policy)?;
         }
-        if self.modifiers & MODIFIER_CONTROL != 0 {
-            write!(f, "ctrl+")?
+
+        if self.assert_disables {
+            counts.assert_disables()?;
         }
-        if self.modifiers & MODIFIER_SHIFT != 0 {
-            write!(f, "shift+")?
+
+        if self.warn_orphan_disables {
+            for i in orphaned_disables_with(&bneu, &bindings) {
+                eprintln!("warning: [{:>4}] {} {}: disables a command that is never bound", i, bneu[i].keys, bneu[i].action);
+            }
         }
-        write!(f, "{}", self.key)
+
+        Ok((bneu, counts))
     }
 }
 
-fn anykey() -> Key {
-    Key { modifiers: 0, key: String::new() }
+#[derive(clap::Args)]
+struct MapArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[command(flatten)]
+    remap: RemapArgs,
+
+    /// Print a summary of what the transformation does instead of the keybindings
+    #[arg(long, conflicts_with_all = ["output", "install"])]
+    dry_run: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(clap::Args)]
+struct MergeArgs {
+    /// The user keybindings file, its entries come last so they win over the generated ones
+    #[arg(long, short)]
+    user: PathBuf,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[command(flatten)]
+    remap: RemapArgs,
+
+    /// Print a summary of what the transformation does instead of the keybindings
+    #[arg(long, conflicts_with_all = ["output", "install"])]
+    dry_run: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(clap::Args)]
+struct CheckArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Print how VS Code resolves each (key, when) pair
+    #[arg(long)]
+    resolution_report: bool,
+
+    /// Flag bindings of commands missing from this list: a JSON array of ids or entries,
+    /// the exported default keybindings, or one id per line
+    #[arg(long)]
+    commands_file: Option<PathBuf>,
+
+    /// Flag `-command` entries whose command is not bound anywhere in the input
+    #[arg(long)]
+    orphan_disables: bool,
+
+    /// Output format of reports
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct FetchArgs {
+    #[arg(long, value_enum)]
+    platform: Platform,
+
+    /// VS Code version, e.g. 1.90
+    #[arg(long)]
+    version: String,
+
+    /// The defaults to store: a file exported with "Open Default Keyboard Shortcuts (JSON)"
+    /// or an http(s) URL, `{platform}` and `{version}` are filled in
+    #[arg(long)]
+    from: String,
+}
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    old: PathBuf,
+
+    new: PathBuf,
+
+    /// Format of the input files
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    input_format: InputFormat,
+
+    /// Output format of the differences
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct CheatsheetArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// How the bindings are split into sections
+    #[arg(long, value_enum, default_value_t = GroupBy::Category)]
+    group_by: GroupBy,
+
+    /// Only list commands matching this pattern, e.g. `editor.action.*`
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// Only list commands of this taxonomy category, e.g. `navigation`
+    #[arg(long)]
+    category: Option<String>,
+
+    #[command(flatten)]
+    taxonomy: TaxonomyArgs,
+
+    #[arg(long, value_enum, default_value_t = SheetFormat::Markdown)]
+    format: SheetFormat,
+
+    /// File to write the cheat sheet to, instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct TaxonomyArgs {
+    /// A taxonomy.toml adding command categories, instead of the one in the config directory
+    #[arg(long)]
+    taxonomy: Option<PathBuf>,
+}
+
+impl TaxonomyArgs {
+    /// The taxonomy, checking that `category` is one of its categories.
+    fn load(&self, category: Option<&str>) -> Result<Taxonomy> {
+        let taxonomy = load_taxonomy(self.taxonomy.as_deref())?;
+        if let Some(c) = category {
+            taxonomy.check_category(c)?;
+        }
+        Ok(taxonomy)
+    }
+}
+
+#[derive(clap::Args)]
+struct RenderArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Handlebars template getting the `bindings` with their `chord`, `command` and `when`
+    #[arg(long, short)]
+    template: PathBuf,
+
+    /// File to write the result to, instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct KarabinerArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[command(flatten)]
+    remap: RemapArgs,
+
+    /// Title and description of the generated rule
+    #[arg(long, default_value = "codekeys")]
+    title: String,
+
+    /// Only apply the rule while VS Code is the frontmost application
+    #[arg(long)]
+    vscode_only: bool,
+
+    /// File to write the complex modifications to, instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct LintArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// The rules to check and their severity, instead of the profile of the config file
+    #[arg(long, value_enum)]
+    profile: Option<LintProfile>,
+
+    /// Output format of the issues
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct DescribeArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Output format, the JSON is versioned and keeps its shape within a version
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct WhenSimplifyArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Also drop the terms testing this context key, `*` matches any text
+    #[arg(long, value_name = "KEY")]
+    strip: Vec<String>,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(clap::Args)]
+struct QueryArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Commands containing this text, or matching it if it has a `*` wildcard
+    #[arg(long)]
+    command: Option<String>,
+
+    /// Chords containing this key sequence, e.g. `ctrl+k` for all ctrl+k chords
+    #[arg(long)]
+    key: Option<String>,
+
+    /// When clauses containing this text
+    #[arg(long)]
+    when: Option<String>,
+
+    /// Treat the filters as regular expressions, the key one on the key string
+    #[arg(long)]
+    regex: bool,
+
+    /// Only bindings that are in effect, leaving out disables and the bindings they remove
+    #[arg(long)]
+    effective: bool,
+
+    /// Only list commands of this taxonomy category, e.g. `navigation`
+    #[arg(long)]
+    category: Option<String>,
+
+    #[command(flatten)]
+    taxonomy: TaxonomyArgs,
+
+    /// Output format of the matches
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct PresetArgs {
+    preset: Preset,
+
+    /// The defaults the preset is applied to
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(clap::Args)]
+struct LeaderArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// The key that starts the chords
+    #[arg(long, default_value = "ctrl+space")]
+    leader: String,
+
+    /// Command or `*` pattern to move, `command=key` picks the key after the leader
+    #[arg(long = "command", short, required = true)]
+    commands: Vec<String>,
+
+    /// Do not disable the bindings of the moved commands on their original keys
+    #[arg(long)]
+    no_disable: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[cfg(feature = "tui")]
+#[derive(clap::Args)]
+struct TuiArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Rules file to preview on the selected binding and apply to the export
+    #[arg(long)]
+    rules: Option<PathBuf>,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(clap::Args)]
+struct WatchArgs {
+    /// Milliseconds to wait for more changes before regenerating
+    #[arg(long, default_value_t = 300)]
+    debounce: u64,
+
+    /// Another file to watch, e.g. one a pipeline step reads
+    #[arg(long = "file", value_name = "FILE")]
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct VerifyArgs {
+    /// The keybindings the config file is expected to generate
+    #[arg(long)]
+    golden: PathBuf,
+
+    /// Write the generated keybindings to the golden file instead of comparing
+    #[arg(long)]
+    update: bool,
+
+    /// Output format of the differences
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct StatsArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// How many of the most overloaded keys to list
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    #[command(flatten)]
+    taxonomy: TaxonomyArgs,
+
+    /// Output format of the report
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct BuildArgs {
+    /// The source file, entries may have a `platform` field with a name or a list of names
+    /// and use the `primary` modifier, cmd on macOS and ctrl elsewhere
+    #[arg(long, short, required_unless_present = "layers")]
+    input: Option<PathBuf>,
+
+    /// Source files composed in order after --input, each overriding the bindings of the
+    /// ones before it on the same keys and when clause and removing those it disables
+    #[arg(value_name = "LAYER")]
+    layers: Vec<PathBuf>,
+
+    /// The platform to build for, the one codekeys runs on by default
+    #[arg(long, value_enum)]
+    platform: Option<Platform>,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(clap::Args)]
+struct ScanExtensionsArgs {
+    /// The extensions directory, by default the first of `~/.vscode`, `~/.vscode-insiders`
+    /// and `~/.vscode-oss` that has one
+    #[arg(long, value_name = "DIR")]
+    ext_dir: Option<PathBuf>,
+
+    /// The platform whose keys are taken from entries with `mac`, `linux` or `win` keys,
+    /// the one codekeys runs on by default
+    #[arg(long, value_enum)]
+    platform: Option<Platform>,
+
+    /// Default keybindings to write before the extensions' ones
+    #[arg(long)]
+    defaults: Option<PathBuf>,
+
+    /// List the extensions and how many keybindings they contribute instead
+    #[arg(long)]
+    list: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(clap::Args)]
+struct RollbackArgs {
+    /// How many versions to go back, 1 is the file the last install replaced
+    #[arg(default_value_t = 1)]
+    n: usize,
+
+    /// List the kept versions, newest first, instead of restoring one
+    #[arg(long)]
+    list: bool,
+
+    /// The keybindings.json to restore, VS Code's user keybindings by default
+    #[arg(long)]
+    target: Option<PathBuf>,
+
+    /// The VS Code build whose user keybindings are restored, the first one found by default
+    #[arg(long, value_enum, conflicts_with = "target")]
+    editor: Option<Editor>,
+}
+
+#[derive(clap::Args)]
+struct EffectiveArgs {
+    /// The default keybindings
+    #[arg(long, default_value = "keys/default.json")]
+    defaults: PathBuf,
+
+    /// The user keybindings.json applied on top of them
+    #[arg(long, short)]
+    user: PathBuf,
+
+    /// Format of the input files
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    input_format: InputFormat,
+
+    /// Output format of the table
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct ResolveArgs {
+    /// The keys pressed, `ctrl+k ctrl+c`
+    chord: String,
+
+    /// The default keybindings
+    #[arg(long, default_value = "keys/default.json")]
+    defaults: PathBuf,
+
+    /// A user keybindings.json applied on top of them
+    #[arg(long, short)]
+    user: Option<PathBuf>,
+
+    /// Format of the input files
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    input_format: InputFormat,
+
+    /// Output format of the list
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+struct SuggestArgs {
+    /// The keys the free chords start with, `ctrl+k`
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Modifiers of the key after the prefix, or of the single key without one, `ctrl+alt`;
+    /// repeat for more combinations, `none` for the key alone
+    #[arg(long, short, required_unless_present = "prefix")]
+    modifiers: Vec<String>,
+
+    /// Only count the bindings that can apply in this context, `editorTextFocus`
+    #[arg(long)]
+    when: Option<String>,
+
+    /// The default keybindings
+    #[arg(long, default_value = "keys/default.json")]
+    defaults: PathBuf,
+
+    /// A user keybindings.json applied on top of them
+    #[arg(long, short)]
+    user: Option<PathBuf>,
+
+    /// Format of the input files
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    input_format: InputFormat,
+
+    /// Output format of the list
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum OutputFormat {
+    /// VS Code keybindings.json
+    #[value(alias = "json")]
+    Vscode,
+    /// The keybindings.json entries as a YAML sequence
+    Yaml,
+    /// The keybindings.json entries as TOML `[[binding]]` tables
+    Toml,
+    /// Sublime Text .sublime-keymap
+    Sublime,
+    /// IntelliJ IDEA keymap.xml
+    Jetbrains,
+    /// Zed keymap.json
+    Zed,
+    /// The [keys] section of a Helix config.toml
+    Helix,
+    /// kitty.conf map lines for copy, paste, tabs and font size
+    Kitty,
+    /// Alacritty [[keyboard.bindings]] for copy, paste, tabs and font size
+    Alacritty,
+    /// A WezTerm Lua keys table for copy, paste, tabs and font size
+    Wezterm,
+    /// An AutoHotkey v2 script sending the Windows shortcuts of copy, paste, save and the like
+    Autohotkey,
+    /// A keyd config sending the Linux shortcuts of copy, paste, save and the like
+    Keyd,
+    /// An xremap keymap sending the Linux shortcuts of copy, paste, save and the like
+    Xremap
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum ReportFormat {
+    /// Human readable text
+    Text,
+    /// Structured JSON for tooling
+    Json
 }
 
 fn main() -> color_eyre::eyre::Result<()> {
 
     color_eyre::install()?;
 
-    let bindings = load_defaults().unwrap();
+    let cli = Cli::parse();
+    run(&cli.command, cli.config.as_deref()).map_err(|e| color_eyre::eyre::eyre!("{:#}", e))
+}
 
-    let mut bneu: Vec<ConfigItem> = vec!();
+fn run(command: &Command, config: Option<&Path>) -> Result<()> {
+    match command {
+        Command::Map(args) => run_map(args),
+        Command::Merge(args) => run_merge(args),
+        Command::Check(args) => run_check(args),
+        Command::Fetch(args) => run_fetch(args),
+        Command::Diff(args) => run_diff(args),
+        Command::Cheatsheet(args) => run_cheatsheet(args),
+        Command::Render(args) => run_render(args),
+        Command::Karabiner(args) => run_karabiner(args),
+        Command::Lint(args) => run_lint(args, config),
+        Command::Query(args) => run_query(args),
+        Command::Stats(args) => run_stats(args),
+        Command::Effective(args) => run_effective(args),
+        Command::Resolve(args) => run_resolve(args),
+        Command::Suggest(args) => run_suggest(args),
+        Command::Preset(args) => run_preset(args),
+        Command::Leader(args) => run_leader(args),
+        Command::Build(args) => run_build(args),
+        Command::ScanExtensions(args) => run_scan_extensions(args),
+        Command::Rollback(args) => run_rollback(args),
+        #[cfg(feature = "tui")]
+        Command::Tui(args) => run_tui(args),
+        Command::Describe(args) => print_report(&describe(&args.input.load()?), args.report_format),
+        Command::Schema { kind } => {
+            println!("{}", serde_json::to_string_pretty(&schema(*kind))?);
+            Ok(())
+        },
+        Command::When { command: WhenCommand::Simplify(args) } => run_when_simplify(args),
+        Command::Profile { command } => run_profile(command),
+        Command::Apply => run_apply(config),
+        #[cfg(feature = "watch")]
+        Command::Watch(args) => run_watch(args, config),
+        Command::Verify(args) => run_verify(args, config),
+        Command::Config { command } => run_config(command, config),
+        Command::Completions { shell } => {
+            clap_complete::generate(*shell, &mut Cli::command(), "codekeys", &mut std::io::stdout());
+            Ok(())
+        }
+    }
+}
+
+fn run_map(args: &MapArgs) -> Result<()> {
+    let bindings = args.input.load()?;
+    let (bneu, counts) = args.remap.apply(bindings.clone())?;
+    if args.dry_run {
+        // the generated file is applied on top of the defaults
+        let effective: Vec<KeyBinding> = bindings.iter().chain(bneu.iter()).cloned().collect();
+        print!("{}", counts.summary(bindings.len(), &effective));
+        return Ok(());
+    }
+    args.output.write(&bneu)
+}
 
-    for k in bindings.iter() {
-        // println!("{:x} {:>10} {}", k.keys.first.modifiers, k.keys.first.key, k.command)
-        map_ctrl_binding(k).iter().for_each(|i| bneu.push(ConfigItem::from(i)));
+/// The defaults left after those the user file overrides, the merged bindings and the counts of the remap.
+fn merged(args: &MergeArgs) -> Result<(Vec<KeyBinding>, Vec<KeyBinding>, RemapCounts)> {
+
+    let source = Some(Arc::new(BindingSource::User { path: args.user.display().to_string() }));
+    let user: Vec<KeyBinding> = load_bindings(&args.user, args.input.input_format)?.into_iter()
+        .map(|kb| KeyBinding { source: source.clone(), ..kb })
+        .collect();
+
+    let (defaults, overridden): (Vec<KeyBinding>, Vec<KeyBinding>) = args.input.load()?
+        .into_iter()
+        .partition(|kb| !is_overridden(kb, &user));
+    if !overridden.is_empty() {
+        eprintln!("skipped {} defaults the user file already overrides", overridden.len());
+    }
+
+    let (generated, counts) = args.remap.apply(defaults.clone())?;
+    let bneu = match Resolver::open(&args.remap)? {
+        Some(mut resolver) => resolve_user_conflicts(generated, user, |c, new, old| {
+            Ok(resolver.decide(old, new)?.unwrap_or_else(|| {
+                eprintln!("conflict: {} is bound to {} in the user file, not generating {}", c.keys, c.user, c.generated);
+                Resolution::Skip
+            }))
+        })?.0,
+        None => {
+            let (bneu, conflicts) = merge_user_bindings(generated, user);
+            for c in conflicts.iter() {
+                eprintln!("conflict: {} is bound to {} in the user file, not generating {}", c.keys, c.user, c.generated);
+            }
+            bneu
+        }
     };
+    let (bneu, cancelled) = cancel_rebinds(bneu);
+    if cancelled > 0 {
+        eprintln!("dropped {} disables undone by a later binding of the same command", cancelled);
+    }
+    Ok((defaults, bneu, counts))
+}
+
+fn run_merge(args: &MergeArgs) -> Result<()> {
+    let (defaults, bneu, counts) = merged(args)?;
+    if args.dry_run {
+        let effective: Vec<KeyBinding> = defaults.iter().chain(bneu.iter()).cloned().collect();
+        print!("{}", counts.summary(defaults.len(), &effective));
+        return Ok(());
+    }
+    args.output.write(&bneu)
+}
 
-    println!("{}", serde_json::to_string_pretty(&bneu)?);
 
+
+fn run_check(args: &CheckArgs) -> Result<()> {
+
+    let bindings = args.input.load()?;
+
+    if args.resolution_report {
+        return print_list(&resolve(&bindings), args.report_format);
+    }
+
+    let mut report = check(&bindings);
+    if let Some(path) = &args.commands_file {
+        report.problems.extend(unknown_commands(&bindings, &load_commands(path)?));
+    }
+    if args.orphan_disables {
+        report.problems.extend(orphaned_disable_problems(&bindings));
+    }
+    report.problems.sort_by_key(|p| p.index);
+    print_report(&report, args.report_format)?;
+    if !report.problems.is_empty() {
+        bail!("found {} problems in {} bindings", report.problems.len(), bindings.len());
+    }
     Ok(())
 }
 
+fn run_fetch(args: &FetchArgs) -> Result<()> {
+    let (path, count) = fetch_defaults(args.platform, &args.version, &args.from)?;
+    eprintln!("stored {} bindings in {}", count, path.display());
+    Ok(())
+}
 
+fn run_diff(args: &DiffArgs) -> Result<()> {
+    let old = load_bindings(&args.old, args.input_format)?;
+    let new = load_bindings(&args.new, args.input_format)?;
+    print_list(&diff_bindings(&old, &new), args.report_format)
+}
 
-fn load_defaults() -> Result<Vec<KeyBinding>> {
-
-    let path = PathBuf::from("keys/default.json");
-    let defaults_json: Vec<ConfigItem> = serde_json::from_reader(File::open(path)?)?;
+fn run_cheatsheet(args: &CheatsheetArgs) -> Result<()> {
+    let taxonomy = args.taxonomy.load(args.category.as_deref())?;
+    let sections = cheatsheet(&args.input.load()?, args.group_by, args.filter.as_deref(), args.category.as_deref(), &taxonomy);
+    let sheet = match args.format {
+        SheetFormat::Markdown => render_markdown(&sections),
+        SheetFormat::Html => render_html(&sections)
+    };
+    match &args.output {
+        Some(path) => std::fs::write(path, sheet)
+            .map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))?,
+        None => print!("{}", sheet)
+    }
+    Ok(())
+}
 
-    let bindings = defaults_json.into_iter().map(|item| KeyBinding::from(item)).collect();
-    Ok(bindings)
+fn run_render(args: &RenderArgs) -> Result<()> {
+    let template = std::fs::read_to_string(&args.template)
+        .map_err(|e| anyhow!("cannot read template {}: {}", args.template.display(), e))?;
+    let text = render_template(&template, &args.input.load()?)
+        .map_err(|e| anyhow!("cannot render {}: {}", args.template.display(), e))?;
+    match &args.output {
+        Some(path) => std::fs::write(path, text)
+            .map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))?,
+        None => print!("{}", text)
+    }
+    Ok(())
 }
 
-fn parse_key_sequence(code: &str) -> KeyRule {
-    let mut iter = code.split_ascii_whitespace().map(|k| parse_one_key(k));
-    let k1 = iter.next();
-    let k2 = iter.next();
-    KeyRule {
-        first: k1.unwrap_or_else(|| anykey()),
-        second: k2
+fn run_karabiner(args: &KarabinerArgs) -> Result<()> {
+
+    if args.remap.no_disable {
+        bail!("the key mappings are read from the disable entries, --no-disable leaves none");
+    }
+    let (bneu, _) = args.remap.apply(args.input.load()?)?;
+    let mappings = key_mappings(&bneu);
+    if mappings.is_empty() {
+        eprintln!("warning: the remap did not move any binding to other keys");
     }
+
+    let json = serde_json::to_string_pretty(&to_complex_modifications(&mappings, &args.title, args.vscode_only))?;
+    match &args.output {
+        Some(path) => std::fs::write(path, json + "\n")
+            .map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))?,
+        None => println!("{}", json)
+    }
+    Ok(())
 }
 
+fn run_lint(args: &LintArgs, config: Option<&Path>) -> Result<()> {
+    let path = config_path(config)?;
+    let config = match config.is_some() || path.exists() {
+        true => load_config(&path)?.lint,
+        false => Default::default()
+    };
+    let profile = args.profile.or(config.profile).unwrap_or_default();
+    let settings = LintSettings::new(profile, &config.rules)?;
 
-fn parse_one_key(key: &str) -> Key {
+    let bindings = args.input.load()?;
+    let issues = lint(&bindings, &settings);
+    print_list(&issues, args.report_format)?;
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    if errors > 0 {
+        bail!("found {} errors and {} warnings in {} bindings", errors, issues.len() - errors, bindings.len());
+    }
+    Ok(())
+}
+
+fn run_query(args: &QueryArgs) -> Result<()> {
+    let category = match &args.category {
+        Some(c) => Some((c.clone(), args.taxonomy.load(Some(c))?)),
+        None => None
+    };
+    let q = Query {
+        effective: args.effective,
+        category,
+        ..Query::new(args.command.as_deref(), args.key.as_deref(), args.when.as_deref(), args.regex)?
+    };
+    print_list(&query(&args.input.load()?, &q), args.report_format)
+}
 
-    let mut modifiers: usize = 0;
-    let mut thekey: Option<String> = None;
+fn run_stats(args: &StatsArgs) -> Result<()> {
+    print_report(&stats(&args.input.load()?, args.top, &args.taxonomy.load(None)?), args.report_format)
+}
 
-    for k in key.to_lowercase().split_inclusive("+") {
-        match k {
-            "ctrl+" => modifiers |= MODIFIER_CONTROL,
-            "shift+" => modifiers |= MODIFIER_SHIFT,
-            "super+" => modifiers |= MODIFIER_COMMAND,
-            "cmd+" => modifiers |= MODIFIER_COMMAND,
-            "meta+" => modifiers |= MODIFIER_COMMAND,
-            "win+" => modifiers |= MODIFIER_COMMAND,
-            "alt+" => modifiers |= MODIFIER_OPTION,
-            _ => thekey = Some(String::from(k))
+fn run_when_simplify(args: &WhenSimplifyArgs) -> Result<()> {
+    let mut bindings = args.input.load()?;
+    let clauses = bindings.iter().filter(|kb| kb.when.is_some()).count();
+    let mut changed = 0;
+    for (i, kb) in bindings.iter_mut().enumerate() {
+        match rewrite_when(&kb.when, None, &args.strip).and_then(|w| simplify_when(&w)) {
+            Ok(when) if when != kb.when => {
+                kb.when = when;
+                changed += 1;
+            },
+            Ok(_) => (),
+            Err(e) => eprintln!("warning: [{:>4}] {} {}: {:#}, left as it is", i, kb.keys, kb.action, e)
         }
     }
+    eprintln!("simplified {} of {} when clauses", changed, clauses);
+    // the entries keep their fields and key spelling
+    args.output.write_as(&bindings, true)
+}
+
+fn run_build(args: &BuildArgs) -> Result<()> {
+    let platform = args.platform.unwrap_or_else(Platform::current);
+    let paths: Vec<&PathBuf> = args.input.iter().chain(args.layers.iter()).collect();
+    let mut layers = vec!();
+    let mut entries = 0;
+    for path in paths.iter() {
+        let bindings = load_bindings(path, InputFormat::Auto)?;
+        entries += bindings.len();
+        // keys are compared once primary is resolved
+        layers.push(build(&bindings, platform).map_err(|e| anyhow!("{}: {}", path.display(), e))?);
+    }
+    let composed = compose(layers);
+    for (path, o) in paths.iter().zip(composed.overrides.iter()).filter(|(_, o)| o.replaced + o.removed > 0) {
+        eprintln!("{} replaces {} and removes {} bindings of the layers before it", path.display(), o.replaced, o.removed);
+    }
+    let built = composed.bindings;
+    eprintln!("{} of {} entries for {}", built.len(), entries, platform.name());
+    // the platform's key spelling is kept in the entries read
+    args.output.write_as(&built, true)
+}
 
-    Key {
-        modifiers: modifiers,
-        key: thekey.unwrap_or_else(|| String::new())
+/// The extensions of `dir`, with a warning for each one that cannot be read.
+fn scan_extensions_warning(dir: &Path, platform: Platform) -> Result<Vec<Extension>> {
+    let (extensions, unreadable) = scan_extensions(dir, platform)?;
+    for problem in unreadable {
+        eprintln!("warning: extension {}, left out", problem);
     }
+    Ok(extensions)
 }
 
+fn run_scan_extensions(args: &ScanExtensionsArgs) -> Result<()> {
+    let dir = match &args.ext_dir {
+        Some(d) => d.clone(),
+        None => extensions_dir(None)?
+    };
+    let extensions = scan_extensions_warning(&dir, args.platform.unwrap_or_else(Platform::current))?;
+    if args.list {
+        for ext in extensions.iter() {
+            println!("{:>4} {} {}", ext.bindings.len(), ext.id, ext.version);
+        }
+        return Ok(());
+    }
+    let mut bindings = match &args.defaults {
+        Some(path) => load_bindings(path, InputFormat::Auto)?,
+        None => vec!()
+    };
+    let contributed = extension_bindings(&extensions);
+    eprintln!("{} keybindings from {} extensions in {}", contributed.len(), extensions.len(), dir.display());
+    bindings.extend(contributed);
+    // the entries name their extension
+    args.output.write_as(&bindings, true)
+}
 
-fn map_ctrl_binding(kb: &KeyBinding) -> Vec<KeyBinding> {
+fn run_rollback(args: &RollbackArgs) -> Result<()> {
+    let target = match &args.target {
+        Some(t) => t.clone(),
+        None => vscode_user_dir(args.editor)?.join("keybindings.json")
+    };
+    if args.list {
+        for (n, version) in history(&target)?.iter().enumerate() {
+            println!("{:>3} {}", n + 1, version.display());
+        }
+        return Ok(());
+    }
+    let version = rollback(&target, args.n)?;
+    eprintln!("restored {} from {}, `codekeys rollback` undoes this", target.display(), version.display());
+    Ok(())
+}
 
-    let mut r = vec!();
+fn run_effective(args: &EffectiveArgs) -> Result<()> {
+    let defaults = load_bindings(&args.defaults, args.input_format)?;
+    let user = load_bindings(&args.user, args.input_format)?;
+    print_list(&effective(&defaults, &user), args.report_format)
+}
 
-    if kb.keys.first.modifiers & MODIFIER_CONTROL != 0 {
-        if let Some(k1) = map_ctrl_to_cmd(&kb.keys.first) {
-        
-            let k2 = match &kb.keys.second {
-                Some(k) => map_ctrl_to_cmd(k),
-                None => None
-            };
-    
-            r.push(kb.copy_disabled());
-            r.push(KeyBinding {
-                keys: KeyRule { first: k1, second: k2 },
-                command: kb.command.clone(),
-                when: kb.when.clone(),
-                args: kb.args.clone(),
+fn run_resolve(args: &ResolveArgs) -> Result<()> {
+    let chord = parse_key_sequence_strict(&args.chord).map_err(|e| anyhow!("{}: {}", args.chord, e))?;
+    let defaults = load_bindings(&args.defaults, args.input_format)?;
+    let user = match &args.user {
+        Some(u) => load_bindings(u, args.input_format)?,
+        None => vec!()
+    };
+    let matches = resolve_chord(&defaults, &user, &chord);
+    if matches.is_empty() {
+        eprintln!("nothing is bound to {}", chord);
+    }
+    print_list(&matches, args.report_format)
+}
+
+fn run_suggest(args: &SuggestArgs) -> Result<()> {
+    let prefix = args.prefix.as_deref()
+        .map(|p| parse_key_sequence_strict(p).map_err(|e| anyhow!("{}: {}", p, e)))
+        .transpose()?;
+    let modifiers = match args.modifiers.is_empty() {
+        true => vec!(0),
+        false => args.modifiers.iter()
+            .map(|m| match m.as_str() {
+                "none" => Ok(0),
+                m => m.split('+').map(parse_modifier).try_fold(0, |all, m| m.map(|m| all | m))
             })
-    
-    
+            .collect::<Result<Vec<_>>>()?
+    };
+    let defaults = load_bindings(&args.defaults, args.input_format)?;
+    let user = match &args.user {
+        Some(u) => load_bindings(u, args.input_format)?,
+        None => vec!()
+    };
+    let bindings = effective(&defaults, &user);
+    if let Some(kb) = prefix.as_ref().and_then(|p| bindings.iter().find(|b| b.keys == *p)) {
+        eprintln!("warning: {} is bound to {}, chords under it hide it", kb.keys, kb.command);
+    }
+    let free = free_chords(&bindings, prefix.as_ref(), &modifiers, args.when.as_deref());
+    print_list(&free, args.report_format)
+}
+
+fn run_preset(args: &PresetArgs) -> Result<()> {
+    let (bneu, disables) = apply_preset(args.preset, &args.input.load()?);
+    eprintln!("{} bindings, {} disables of shadowed defaults", bneu.len() - disables, disables);
+    args.output.write(&bneu)
+}
+
+fn run_leader(args: &LeaderArgs) -> Result<()> {
+    let leader = single_key(&args.leader)?;
+    let entries = args.commands.iter().map(|c| LeaderEntry::parse(c)).collect::<Result<Vec<_>>>()?;
+    let bindings = args.input.load()?;
+    if let Some(kb) = bindings.iter().find(|kb| kb.keys.rest.is_empty() && kb.keys.first == leader && !kb.is_disable()) {
+        eprintln!("warning: {} is bound to {}, the leader chords hide it", leader, kb.action);
+    }
+    let (bneu, skipped) = leader_bindings(&bindings, &leader, &entries, !args.no_disable);
+    for (command, reason) in skipped.iter() {
+        eprintln!("warning: {}: {}, left out", command, reason);
+    }
+    args.output.write(&bneu)
+}
+
+#[cfg(feature = "tui")]
+fn run_tui(args: &TuiArgs) -> Result<()> {
+    let rules = args.rules.as_deref().map(load_rules).transpose()?;
+    match tui::browse(codekeys::browser::Browser::new(args.input.load()?, rules))? {
+        Some(bneu) => args.output.write(&bneu),
+        None => Ok(())
+    }
+}
+
+/// The command line `apply` runs for the settings of `config`.
+fn apply_args(config: &Config) -> Vec<OsString> {
+
+    let command = if config.user.is_some() { "merge" } else { "map" };
+    let mut args: Vec<OsString> = vec!("codekeys".into(), command.into());
+    let mut flag = |name: &str, value: Option<OsString>| if let Some(v) = value {
+        args.push(format!("--{}", name).into());
+        args.push(v);
+    };
+    flag("user", config.user.clone().map(OsString::from));
+    flag("input", config.input.clone().map(OsString::from));
+    flag("input-format", config.input_format.clone().map(OsString::from));
+    flag("direction", config.direction.clone().map(OsString::from));
+    flag("rules", config.rules.clone().map(OsString::from));
+    flag("pipeline", config.pipeline.clone().map(OsString::from));
+    flag("layout", config.layout.clone().map(OsString::from));
+    flag("when", config.when.clone().map(OsString::from));
+    flag("not-when", config.not_when.clone().map(OsString::from));
+    flag("output", config.output.clone().map(OsString::from));
+    flag("format", config.format.clone().map(OsString::from));
+    flag("editor", config.editor.clone().map(OsString::from));
+    flag("sort", config.sort.clone().map(OsString::from));
+    flag("sections", config.sections.clone().map(OsString::from));
+    for (name, set) in [("--no-disable", config.no_disable), ("--install", config.install)] {
+        if set {
+            args.push(name.into());
         }
     }
+    args
+}
+
+fn run_apply(config: Option<&Path>) -> Result<()> {
+    let path = config_path(config)?;
+    let args = apply_args(&load_config(&path)?);
+    let line: Vec<String> = args.iter().map(|a| a.to_string_lossy().into_owned()).collect();
+    eprintln!("{}: {}", path.display(), line.join(" "));
+    let cli = Cli::try_parse_from(&args).map_err(|e| anyhow!("{} has settings that do not fit together: {}", path.display(), e))?;
+    run(&cli.command, None)
+}
+
+fn run_verify(args: &VerifyArgs, config: Option<&Path>) -> Result<()> {
+    let path = config_path(config)?;
+    let cli = Cli::try_parse_from(apply_args(&load_config(&path)?))
+        .map_err(|e| anyhow!("{} has settings that do not fit together: {}", path.display(), e))?;
+    let (generated, output) = match &cli.command {
+        Command::Map(m) => (m.remap.apply(m.input.load()?)?.0, &m.output),
+        Command::Merge(m) => (merged(m)?.1, &m.output),
+        _ => unreachable!("apply runs map or merge")
+    };
+
+    if args.update {
+        // in the order of the configured output, as the VS Code JSON it is read back as
+        let golden = OutputArgs {
+            output: Some(args.golden.clone()), install: false, format: OutputFormat::Vscode,
+            sections: None, source_comments: false, ..output.clone()
+        };
+        golden.write(&generated)?;
+        eprintln!("updated {} with {} bindings", args.golden.display(), generated.len());
+        return Ok(());
+    }
+
+    let expected = load_bindings(&args.golden, InputFormat::Auto)?;
+    let differences = diff_bindings(&expected, &generated);
+    if differences.is_empty() {
+        eprintln!("{} bindings match {}", generated.len(), args.golden.display());
+        return Ok(());
+    }
+    print_list(&differences, args.report_format)?;
+    bail!("{} chords differ from {}, run with --update to accept them", differences.len(), args.golden.display())
+}
 
-    r
+#[cfg(feature = "watch")]
+fn run_watch(args: &WatchArgs, config: Option<&Path>) -> Result<()> {
+    let path = config_path(config)?;
+    let settings = load_config(&path)?;
+    if settings.output.is_none() && !settings.install {
+        bail!("{} sets neither output nor install, watch has nowhere to write to", path.display());
+    }
+    let sources = || -> Result<Vec<PathBuf>> {
+        let mut sources = load_config(&path)?.sources();
+        sources.push(path.clone());
+        sources.extend(args.files.iter().cloned());
+        Ok(sources)
+    };
+    watch::watch(sources, std::time::Duration::from_millis(args.debounce), || run_apply(config))
+}
 
+fn run_config(command: &ConfigCommand, config: Option<&Path>) -> Result<()> {
+    let path = config_path(config)?;
+    match command {
+        ConfigCommand::Init { force } => {
+            init_config(&path, *force)?;
+            eprintln!("wrote {}", path.display());
+        },
+        ConfigCommand::Path => println!("{}", path.display())
+    }
+    Ok(())
 }
 
-fn map_ctrl_to_cmd(key: &Key) -> Option<Key> {
+fn run_profile(command: &ProfileCommand) -> Result<()> {
+    match command {
+        ProfileCommand::Create { name, defaults, rules } => {
+            let profile = create_profile(name, defaults, rules.as_deref())?;
+            eprintln!("created profile {} in {}", name, profile.dir.display());
+        },
+        ProfileCommand::List => {
+            let active = active_profile()?;
+            for name in list_profiles()? {
+                let marker = if active.as_ref() == Some(&name) { "*" } else { " " };
+                println!("{} {}", marker, name);
+            }
+        },
+        ProfileCommand::Switch { name, target, editor } => {
+            let target = match target {
+                Some(t) => t.clone(),
+                None => vscode_user_dir(*editor)?.join("keybindings.json")
+            };
+            let (count, backup) = switch_profile(name, &target)?;
+            eprintln!("switched to {}, wrote {} bindings to {}", name, count, target.display());
+            if let Some(backup) = backup {
+                eprintln!("the previous file is in {}", backup.display());
+            }
+        },
+        ProfileCommand::Export { name, output } => output.write(&open_profile(name)?.generate()?)?
+    }
+    Ok(())
+}
+
+fn print_report<T: serde::Serialize + std::fmt::Display>(report: &T, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Text => print!("{}", report),
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(report)?)
+    }
+    Ok(())
+}
 
-    if key.modifiers & MODIFIER_CONTROL != 0 && key.modifiers & MODIFIER_COMMAND == 0 {
-        let xmod = (key.modifiers ^ MODIFIER_CONTROL) | MODIFIER_COMMAND;
-        Some(Key { modifiers: xmod, key: key.key.clone() })
-    } else {
-        Some(key.clone())
+fn print_list<T: serde::Serialize + std::fmt::Display>(items: &[T], format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Text => items.iter().for_each(|r| print!("{}", r)),
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(items)?)
     }
+    Ok(())
+}
+
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn verify_accepts_the_golden_file_update_wrote() {
+        let dir = std::env::temp_dir().join(format!("codekeys-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("default.json"), r#"[{ "key": "ctrl+s", "command": "save" }, { "key": "ctrl+k ctrl+c", "command": "comment" }]"#).unwrap();
+        std::fs::write(dir.join("codekeys.toml"), "input = \"default.json\"\ndirection = \"ctrl-to-cmd\"\nformat = \"zed\"\n").unwrap();
+        let config = dir.join("codekeys.toml");
+        let golden = dir.join("golden.json");
+
+        let args = |update| VerifyArgs { golden: golden.clone(), update, report_format: ReportFormat::Text };
+        run_verify(&args(true), Some(&config)).unwrap();
+        let verified = run_verify(&args(false), Some(&config));
+        std::fs::remove_dir_all(&dir).unwrap();
+        verified.unwrap();
+    }
 }
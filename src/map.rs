@@ -0,0 +1,248 @@
+//! The modifier remap between the Linux and the macOS keymap, and other changes of the modifiers.
+
+use anyhow::bail;
+use serde_derive::Serialize;
+
+use crate::{Result, binding::KeyBinding, conflict::find_conflicts, par, key::{Key, KeyCode, KeyRule, MODIFIER_COMMAND, MODIFIER_CONTROL, parse_modifier}};
+
+
+/// How many bindings a transformation remapped and how many disables it emitted.
+#[derive(Default)]
+pub struct RemapCounts {
+    pub remaps: usize,
+    pub disables: usize
+}
+
+impl RemapCounts {
+    /// The bindings `made` from each of the bindings in order, counted: the last one made from a
+    /// binding is its remap, the ones before it disables of the original. The action tells
+    /// neither apart, the disable of a disable has the same one.
+    pub fn count(&mut self, made: Vec<Vec<KeyBinding>>) -> Vec<KeyBinding> {
+        let mut bneu = Vec::with_capacity(made.iter().map(Vec::len).sum());
+        for made in made.into_iter().filter(|m| !m.is_empty()) {
+            self.remaps += 1;
+            self.disables += made.len() - 1;
+            bneu.extend(made);
+        }
+        bneu
+    }
+
+    /// Fails if bindings were remapped but no disables were emitted, as with `--no-disable`.
+    pub fn assert_disables(&self) -> Result<()> {
+        if self.remaps > 0 && self.disables == 0 {
+            bail!("{} bindings were remapped but no disable entries were emitted", self.remaps);
+        }
+        Ok(())
+    }
+
+    /// The summary of a transformation of `scanned` bindings, with the conflicts of `effective`,
+    /// the bindings VS Code ends up with.
+    pub fn summary(&self, scanned: usize, effective: &[KeyBinding]) -> Summary {
+        Summary {
+            scanned,
+            converted: self.remaps,
+            untouched: scanned.saturating_sub(self.remaps),
+            disables: self.disables,
+            conflicts: find_conflicts(effective).len()
+        }
+    }
+}
+
+/// What a transformation would do, printed by `--dry-run`.
+#[derive(Serialize)]
+pub struct Summary {
+    pub scanned: usize,
+    pub converted: usize,
+    pub untouched: usize,
+    pub disables: usize,
+    pub conflicts: usize
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:>6} bindings scanned", self.scanned)?;
+        writeln!(f, "{:>6} converted", self.converted)?;
+        writeln!(f, "{:>6} left untouched", self.untouched)?;
+        writeln!(f, "{:>6} disable entries emitted", self.disables)?;
+        writeln!(f, "{:>6} chords with conflicts", self.conflicts)
+    }
+}
+
+/// Which modifier the remap replaces by which.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Direction {
+    /// Linux/Windows keymap to macOS: ctrl+ becomes cmd+
+    CtrlToCmd,
+    /// macOS keymap to Linux/Windows: cmd+/meta+ becomes ctrl+
+    CmdToCtrl
+}
+
+impl Direction {
+    /// The modifier that is replaced and its replacement.
+    pub fn modifiers(self) -> (usize, usize) {
+        match self {
+            Direction::CtrlToCmd => (MODIFIER_CONTROL, MODIFIER_COMMAND),
+            Direction::CmdToCtrl => (MODIFIER_COMMAND, MODIFIER_CONTROL)
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::CtrlToCmd => "ctrl-to-cmd",
+            Direction::CmdToCtrl => "cmd-to-ctrl"
+        }
+    }
+}
+
+/// The default transformation: every binding with the `direction`'s source modifier remapped,
+/// preceded by a disable of the original unless `disable` is false. Other bindings are dropped.
+pub fn remap_bindings(bindings: &[KeyBinding], direction: Direction, disable: bool, counts: &mut RemapCounts) -> Vec<KeyBinding> {
+    let made = par::map(bindings, |k| map_binding(k, direction, disable));
+    counts.count(made)
+}
+
+/// The disable (if `disable`) and the remapped binding for `kb`, nothing if it lacks the modifier.
+pub fn map_binding(kb: &KeyBinding, direction: Direction, disable: bool) -> Vec<KeyBinding> {
+
+    let mut r = vec!();
+    let (from, to) = direction.modifiers();
+    let by = format!("{} remap", direction.name());
+
+    if kb.has_modifier(from) {
+        if let Some(k1) = map_modifier(&kb.keys.first, from, to) {
+        
+            let rest = kb.keys.rest.iter()
+                .filter_map(|k| map_modifier(k, from, to))
+                .collect();
+    
+            if disable {
+                r.push(kb.copy_disabled().generated(&by));
+            }
+            r.push(KeyBinding { keys: KeyRule { first: k1, rest }, ..kb.clone() }.generated(&by))
+    
+    
+        }
+    }
+
+    r
+
+}
+
+/// The key with `from` replaced by `to`, unchanged if it already has `to`. A key that is
+/// `from` pressed alone becomes `to` pressed alone.
+pub fn map_modifier(key: &Key, from: usize, to: usize) -> Option<Key> {
+
+    if key.modifier_key() == Some(from) && key.modifiers & to == 0 {
+        Some(Key { modifiers: key.modifiers, code: KeyCode::Modifier(to) })
+    } else if key.modifiers & from != 0 && key.modifiers & to == 0 {
+        let xmod = (key.modifiers ^ from) | to;
+        Some(Key { modifiers: xmod, ..key.clone() })
+    } else {
+        Some(key.clone())
+    }
+
+
+}
+
+/// The key with modifiers `a` and `b` exchanged, unchanged if it has both or neither. A key
+/// that is one of them pressed alone becomes the other one.
+pub fn swap_modifiers(key: &Key, a: usize, b: usize) -> Key {
+    let has = |m: usize| key.modifiers & m != 0 || key.modifier_key() == Some(m);
+    if has(a) == has(b) {
+        return key.clone();
+    }
+    let mut modifiers = key.modifiers;
+    if modifiers & (a | b) != 0 {
+        modifiers ^= a | b;
+    }
+    let code = match key.modifier_key() {
+        Some(m) if m == a => KeyCode::Modifier(b),
+        Some(m) if m == b => KeyCode::Modifier(a),
+        _ => key.code.clone()
+    };
+    Key { modifiers, code }
+}
+
+/// The key with `m` added, unless it is `m` pressed alone.
+pub fn add_modifier(key: &Key, m: usize) -> Key {
+    match key.modifier_key() == Some(m) {
+        true => key.clone(),
+        false => Key { modifiers: key.modifiers | m, ..key.clone() }
+    }
+}
+
+pub fn remove_modifier(key: &Key, m: usize) -> Key {
+    Key { modifiers: key.modifiers & !m, ..key.clone() }
+}
+
+/// A change of the modifiers of every key of a chord.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModifierOp {
+    Swap(usize, usize),
+    Add(usize),
+    Remove(usize)
+}
+
+impl ModifierOp {
+    /// The swap of a pair of modifier names like `ctrl,alt`.
+    pub fn parse_swap(pair: &str) -> Result<Self> {
+        match pair.split_once(',') {
+            Some((a, b)) => Ok(ModifierOp::Swap(parse_modifier(a.trim())?, parse_modifier(b.trim())?)),
+            None => bail!("swap '{}' is not two modifiers like ctrl,alt", pair)
+        }
+    }
+
+    pub fn apply(self, key: &Key) -> Key {
+        match self {
+            ModifierOp::Swap(a, b) => swap_modifiers(key, a, b),
+            ModifierOp::Add(m) => add_modifier(key, m),
+            ModifierOp::Remove(m) => remove_modifier(key, m)
+        }
+    }
+}
+
+/// Like [`remap_bindings`] with the `ops` applied in order instead of the direction's remap:
+/// every binding whose keys change, preceded by a disable of the original unless `disable`
+/// is false. Other bindings are dropped.
+pub fn modify_bindings(bindings: &[KeyBinding], ops: &[ModifierOp], disable: bool, counts: &mut RemapCounts) -> Vec<KeyBinding> {
+
+    let made = par::map(bindings, |kb| {
+        let keys = kb.keys.map(|k| ops.iter().fold(k.clone(), |k, op| op.apply(&k)));
+        let mut r = vec!();
+        if keys != kb.keys {
+            if disable {
+                r.push(kb.copy_disabled().generated("modifier change"));
+            }
+            r.push(KeyBinding { keys, ..kb.clone() }.generated("modifier change"));
+        }
+        r
+    });
+    counts.count(made)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFormat, parse_config_items};
+
+    fn bindings(json: &str) -> Vec<KeyBinding> {
+        parse_config_items(json, InputFormat::Json).unwrap().into_iter().map(KeyBinding::from).collect()
+    }
+
+    #[test]
+    fn a_remap_without_disables_fails_the_disables_assertion() {
+        let bindings = bindings(r#"[{ "key": "ctrl+s", "command": "save" }, { "key": "alt+x", "command": "other" }]"#);
+
+        let mut counts = RemapCounts::default();
+        remap_bindings(&bindings, Direction::CtrlToCmd, false, &mut counts);
+        assert_eq!((counts.remaps, counts.disables), (1, 0));
+        assert!(counts.assert_disables().is_err());
+
+        let mut counts = RemapCounts::default();
+        remap_bindings(&bindings, Direction::CtrlToCmd, true, &mut counts);
+        assert_eq!((counts.remaps, counts.disables), (1, 1));
+        assert!(counts.assert_disables().is_ok());
+    }
+}
@@ -0,0 +1,108 @@
+//! Merging generated bindings with a user keybindings file.
+
+use std::collections::HashMap;
+
+use serde_derive::Serialize;
+
+use crate::{Result, binding::KeyBinding, key::KeyRule, resolution::Resolution};
+
+
+/// A generated binding on a chord the user binds to another command, dropped unless resolved otherwise.
+#[derive(Serialize)]
+pub struct MergeConflict {
+    pub keys: KeyRule,
+    pub when: Option<String>,
+    pub generated: String,
+    pub user: String
+}
+
+/// A default is overridden when the user file disables it or binds the same command
+/// in the same context itself.
+pub fn is_overridden(kb: &KeyBinding, user: &[KeyBinding]) -> bool {
+    user.iter().any(|u| match u.is_disable() {
+        true => u.action.removes(&kb.action) && (u.keys.first.is_empty() || u.keys == kb.keys),
+        false => u.action == kb.action && u.when == kb.when
+    })
+}
+
+/// Appends the user bindings to the generated ones. A generated binding on a chord the user
+/// already uses in the same context is dropped together with the disable emitted right
+/// before it, so the default stays active on its original key.
+pub fn merge_user_bindings(generated: Vec<KeyBinding>, user: Vec<KeyBinding>) -> (Vec<KeyBinding>, Vec<MergeConflict>) {
+    resolve_user_conflicts(generated, user, |_, _, _| Ok(Resolution::Keep)).unwrap_or_default()
+}
+
+/// Like [`merge_user_bindings`], with each generated binding on a chord the user binds to
+/// another command settled by `decide`, called with the conflict, the generated binding and the
+/// user one: keep (or skip) leaves the user binding, replace drops
+/// it for the generated one and disable drops both.
+pub fn resolve_user_conflicts(
+    generated: Vec<KeyBinding>,
+    user: Vec<KeyBinding>,
+    mut decide: impl FnMut(&MergeConflict, &KeyBinding, &KeyBinding) -> Result<Resolution>
+) -> Result<(Vec<KeyBinding>, Vec<MergeConflict>)> {
+
+    let taken: HashMap<(&KeyRule, &Option<String>), (usize, &str)> = user.iter().enumerate()
+        .filter(|(_, u)| !u.is_disable())
+        .map(|(j, u)| ((&u.keys, &u.when), (j, u.command())))
+        .collect();
+
+    let mut conflicts = vec!();
+    let mut dropped = vec![false; generated.len()];
+    let mut dropped_user = vec![false; user.len()];
+    for (i, kb) in generated.iter().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        let Some(&(j, other)) = taken.get(&(&kb.keys, &kb.when)) else { continue };
+        let mut resolution = Resolution::Keep;
+        if other != kb.command() {
+            let conflict = MergeConflict {
+                keys: kb.keys.clone(), when: kb.when.clone(), generated: kb.command().to_string(), user: other.to_string()
+            };
+            resolution = decide(&conflict, kb, &user[j])?;
+            conflicts.push(conflict);
+        }
+        if matches!(resolution, Resolution::Replace | Resolution::Disable) {
+            dropped_user[j] = true;
+        }
+        if resolution == Resolution::Replace {
+            continue;
+        }
+        dropped[i] = true;
+        if i > 0 && generated[i - 1].action.removes(&kb.action) && generated[i - 1].when == kb.when {
+            dropped[i - 1] = true;
+        }
+    }
+
+    let mut merged: Vec<KeyBinding> = generated.into_iter()
+        .zip(dropped)
+        .filter(|(_, d)| !d)
+        .map(|(kb, _)| kb)
+        .collect();
+    merged.extend(user.into_iter().zip(dropped_user).filter(|(_, d)| !d).map(|(kb, _)| kb));
+    Ok((merged, conflicts))
+}
+
+/// Drops each disable that a later entry undoes by binding the same command back on the same
+/// keys, when clause and args, along with that entry, as long as nothing in between uses the
+/// keys. Returns the remaining bindings and the number of pairs dropped.
+pub fn cancel_rebinds(bindings: Vec<KeyBinding>) -> (Vec<KeyBinding>, usize) {
+
+    let mut dropped = vec![false; bindings.len()];
+    let mut pairs = 0;
+    for (i, kb) in bindings.iter().enumerate().filter(|(_, kb)| kb.is_disable()) {
+        let Some(n) = bindings[i + 1..].iter().position(|o| o.keys == kb.keys) else { continue };
+        let j = i + 1 + n;
+        let rebind = &bindings[j];
+        if !dropped[j] && kb.action.removes(&rebind.action) && rebind.when == kb.when && rebind.args == kb.args {
+            dropped[i] = true;
+            dropped[j] = true;
+            pairs += 1;
+        }
+    }
+
+    let kept = bindings.into_iter()
+        .zip(dropped)
+        .filter(|(_, d)| !d)
+        .map(|(kb, _)| kb)
+        .collect();
+    (kept, pairs)
+}
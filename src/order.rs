@@ -0,0 +1,128 @@
+//! The order bindings are written in: sorted and grouped into sections for review.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{binding::KeyBinding, when::overlaps};
+
+
+/// What the written bindings are sorted by, ties keep the order they were generated in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SortOrder {
+    /// The order they were generated in
+    SourceOrder,
+    Key,
+    Command,
+    /// The when clause, bindings without one first
+    When
+}
+
+/// What the sections of a written file are.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Sections {
+    /// The disables of defaults, then the bindings
+    Kind,
+    /// The first part of the command, `editor` for `editor.action.commentLine`
+    Category,
+    /// The when clause, bindings without one are `global`
+    When
+}
+
+impl Sections {
+    /// The title of the section `kb` goes into.
+    pub fn title(self, kb: &KeyBinding) -> String {
+        match self {
+            Sections::Kind if kb.is_disable() => "disabled defaults".to_string(),
+            Sections::Kind => "bindings".to_string(),
+            Sections::Category => kb.command().split('.').next().unwrap_or_default().to_string(),
+            Sections::When => match &kb.when {
+                Some(w) => format!("when: {}", w),
+                None => "global".to_string()
+            }
+        }
+    }
+}
+
+/// The indices of `bindings` in the order to write them: by section title if there are sections
+/// (disables first for `Kind`), then by `sort`.
+pub fn arrange(bindings: &[KeyBinding], sort: SortOrder, sections: Option<Sections>) -> Vec<usize> {
+
+    let section = |i: usize| match sections {
+        Some(Sections::Kind) => String::from(if bindings[i].is_disable() { "0" } else { "1" }),
+        Some(s) => s.title(&bindings[i]),
+        None => String::new()
+    };
+    let sort_key = |i: usize| {
+        let kb = &bindings[i];
+        match sort {
+            SortOrder::SourceOrder => vec!(),
+            SortOrder::Key => vec!(kb.keys.to_string()),
+            SortOrder::Command => vec!(kb.command().to_string()),
+            SortOrder::When => vec!(kb.when.clone().unwrap_or_default(), kb.keys.to_string())
+        }
+    };
+
+    let mut order: Vec<usize> = (0..bindings.len()).collect();
+    order.sort_by_cached_key(|i| (section(*i), sort_key(*i)));
+    order
+}
+
+/// The pairs of bindings on the same keys in overlapping contexts that `order` puts the other
+/// way round, the second one won before and the first one wins now. Disables remove defaults
+/// wherever they are in the file, so only bindings count.
+pub fn swapped_precedence(bindings: &[KeyBinding], order: &[usize]) -> Vec<(usize, usize)> {
+
+    let mut position = vec!(0; bindings.len());
+    for (p, i) in order.iter().enumerate() {
+        position[*i] = p;
+    }
+
+    let mut by_keys: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, kb) in bindings.iter().enumerate().filter(|(_, kb)| !kb.is_disable()) {
+        by_keys.entry(kb.keys.to_string()).or_default().push(i);
+    }
+
+    let mut swapped = vec!();
+    for indices in by_keys.values() {
+        for (n, a) in indices.iter().enumerate() {
+            for b in indices[n + 1..].iter() {
+                if position[*a] > position[*b] && overlaps(&bindings[*a].when, &bindings[*b].when) {
+                    swapped.push((*a, *b));
+                }
+            }
+        }
+    }
+    swapped.sort();
+    swapped
+}
+
+/// `entries` as a pretty printed JSON array with a `// --- title ---` comment before every run
+/// of entries with the same title and a `// note` before every entry with a note, VS Code reads
+/// keybindings.json as JSONC.
+pub fn to_jsonc_sections(entries: &[Value], titles: Option<&[String]>, notes: &[Option<String>]) -> serde_json::Result<String> {
+
+    let mut lines = vec!("[".to_string());
+    let mut current: Option<&String> = None;
+
+    for (n, entry) in entries.iter().enumerate() {
+        if let Some(title) = titles.and_then(|t| t.get(n)).filter(|t| current != Some(*t)) {
+            if current.is_some() {
+                lines.push(String::new());
+            }
+            lines.push(format!("  // --- {} ---", title));
+            current = Some(title);
+        }
+        if let Some(note) = notes.get(n).and_then(Option::as_ref) {
+            lines.push(format!("  // {}", note));
+        }
+        let comma = if n + 1 < entries.len() { "," } else { "" };
+        let text = serde_json::to_string_pretty(entry)? + comma;
+        lines.extend(text.lines().map(|l| format!("  {}", l)));
+    }
+
+    lines.push("]".to_string());
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,22 @@
+//! Passes over every binding, run on all cores with the `parallel` feature and one binding
+//! after the other without it. The results keep the order of the input either way.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+
+/// `f` applied to every item of `items`.
+pub fn map<T: Sync, U: Send>(items: &[T], f: impl Fn(&T) -> U + Sync + Send) -> Vec<U> {
+    #[cfg(feature = "parallel")]
+    return items.par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    return items.iter().map(f).collect();
+}
+
+/// `f` applied to every item of `items`, which it takes ownership of.
+pub fn map_owned<T: Send, U: Send>(items: Vec<T>, f: impl Fn(T) -> U + Sync + Send) -> Vec<U> {
+    #[cfg(feature = "parallel")]
+    return items.into_par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    return items.into_iter().map(f).collect();
+}
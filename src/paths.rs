@@ -0,0 +1,119 @@
+//! Per-user directories codekeys keeps its files in.
+
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+
+use crate::Result;
+
+
+pub(crate) fn home_dir() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("cannot find the home directory, neither HOME nor USERPROFILE is set"))
+}
+
+/// `$XDG_CACHE_HOME/codekeys`, `~/Library/Caches/codekeys` on macOS, `~/.cache/codekeys` otherwise.
+pub fn cache_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("codekeys"));
+    }
+    if cfg!(target_os = "macos") {
+        Ok(home_dir()?.join("Library/Caches/codekeys"))
+    } else {
+        Ok(home_dir()?.join(".cache/codekeys"))
+    }
+}
+
+/// `$XDG_CONFIG_HOME/codekeys`, `~/Library/Application Support/codekeys` on macOS,
+/// `~/.config/codekeys` otherwise.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("codekeys"));
+    }
+    if cfg!(target_os = "macos") {
+        Ok(home_dir()?.join("Library/Application Support/codekeys"))
+    } else {
+        Ok(home_dir()?.join(".config/codekeys"))
+    }
+}
+
+/// `$XDG_STATE_HOME/codekeys`, `~/Library/Application Support/codekeys/state` on macOS,
+/// `~/.local/state/codekeys` otherwise.
+pub fn state_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("codekeys"));
+    }
+    if cfg!(target_os = "macos") {
+        Ok(home_dir()?.join("Library/Application Support/codekeys/state"))
+    } else {
+        Ok(home_dir()?.join(".local/state/codekeys"))
+    }
+}
+
+/// The VS Code builds codekeys knows the user directory of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Editor {
+    Code,
+    Insiders,
+    Vscodium
+}
+
+impl Editor {
+    /// The name of the editor's directory in the platform's config directory.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            Editor::Code => "Code",
+            Editor::Insiders => "Code - Insiders",
+            Editor::Vscodium => "VSCodium"
+        }
+    }
+}
+
+fn editor_config_dir() -> Result<PathBuf> {
+    if cfg!(target_os = "macos") {
+        Ok(home_dir()?.join("Library/Application Support"))
+    } else if cfg!(windows) {
+        std::env::var_os("APPDATA")
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow!("cannot find the VS Code user directory, APPDATA is not set"))
+    } else if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Ok(PathBuf::from(dir))
+    } else {
+        Ok(home_dir()?.join(".config"))
+    }
+}
+
+/// The user directory of `editor`, where its `keybindings.json` is. Without an editor the
+/// first of Code, Code - Insiders and VSCodium that is installed, Code if none is.
+pub fn vscode_user_dir(editor: Option<Editor>) -> Result<PathBuf> {
+    let base = editor_config_dir()?;
+    let user_dir = |e: Editor| base.join(e.dir_name()).join("User");
+    Ok(match editor {
+        Some(e) => user_dir(e),
+        None => [Editor::Code, Editor::Insiders, Editor::Vscodium].into_iter()
+            .map(user_dir)
+            .find(|d| d.exists())
+            .unwrap_or_else(|| user_dir(Editor::Code))
+    })
+}
+
+/// The directory `editor` installs extensions into, `~/.vscode/extensions` for Code. Without
+/// an editor the first of them that exists, Code's if none does.
+pub fn extensions_dir(editor: Option<Editor>) -> Result<PathBuf> {
+    let home = home_dir()?;
+    let dir = |e: Editor| home.join(match e {
+        Editor::Code => ".vscode",
+        Editor::Insiders => ".vscode-insiders",
+        Editor::Vscodium => ".vscode-oss"
+    }).join("extensions");
+    Ok(match editor {
+        Some(e) => dir(e),
+        None => [Editor::Code, Editor::Insiders, Editor::Vscodium].into_iter()
+            .map(dir)
+            .find(|d| d.exists())
+            .unwrap_or_else(|| dir(Editor::Code))
+    })
+}
@@ -0,0 +1,144 @@
+//! Ordered transform steps given with `--pipeline`.
+
+use anyhow::anyhow;
+use serde_derive::Deserialize;
+
+use crate::{Result, binding::{BindingAction, KeyBinding, MacroStep}, key::{Key, parse_modifier}, map::{Direction, RemapCounts, add_modifier, map_binding, remove_modifier}};
+
+
+/// One step of a `--pipeline`, written as `{ "op": "...", "args": { ... } }`.
+#[derive(Deserialize)]
+#[serde(tag = "op", content = "args", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum PipelineStep {
+    /// the `--direction` remap, dropping the bindings without its modifier
+    Remap,
+    /// remove a modifier from every key of the chord
+    StripMod { modifier: String },
+    /// add a modifier to every key of the chord, optionally only for commands with a prefix
+    AddMod { modifier: String, command: Option<String> },
+    /// keep the bindings whose fields contain all of the given substrings
+    Filter { command: Option<String>, key: Option<String>, when: Option<String> },
+    /// rename a command, including its `-command` disables and the steps of macros
+    Rename { from: String, to: String }
+}
+
+/// Parses the steps given inline as a JSON array or in a JSON file.
+pub fn load_pipeline(spec: &str) -> Result<Vec<PipelineStep>> {
+    if spec.trim_start().starts_with('[') {
+        Ok(serde_json::from_str(spec)?)
+    } else {
+        let content = std::fs::read_to_string(spec)
+            .map_err(|e| anyhow!("cannot read pipeline {}: {}", spec, e))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// The binding with `f` applied to its keys, marked as made by `by` if that changed them.
+fn changed_keys(kb: KeyBinding, by: &str, f: impl Fn(&Key) -> Key) -> KeyBinding {
+    let keys = kb.keys.map(f);
+    match keys == kb.keys {
+        true => kb,
+        false => KeyBinding { keys, ..kb }.generated(by)
+    }
+}
+
+/// Runs the bindings through the steps in order. Each step works on what the steps before it
+/// made, and every binding a step changed is emitted like the plain remap does, a disable of
+/// the original (unless `disable` is false) followed by the final binding. Bindings the steps
+/// left as they were are dropped.
+pub fn apply_pipeline(bindings: Vec<KeyBinding>, steps: &[PipelineStep], direction: Direction, disable: bool, counts: &mut RemapCounts) -> Result<Vec<KeyBinding>> {
+
+    // each binding with what the steps made of it so far
+    let mut current: Vec<(KeyBinding, KeyBinding)> = bindings.into_iter().map(|kb| (kb.clone(), kb)).collect();
+
+    for step in steps {
+        current = match step {
+            PipelineStep::Remap => {
+                current.into_iter()
+                    .filter_map(|(kb, c)| map_binding(&c, direction, false).pop().map(|c| (kb, c)))
+                    .collect()
+            },
+            PipelineStep::StripMod { modifier } => {
+                let m = parse_modifier(modifier)?;
+                current.into_iter()
+                    .map(|(kb, c)| (kb, changed_keys(c, "pipeline strip-mod", |k| remove_modifier(k, m))))
+                    .collect()
+            },
+            PipelineStep::AddMod { modifier, command } => {
+                let m = parse_modifier(modifier)?;
+                current.into_iter()
+                    .map(|(kb, c)| match command {
+                        Some(prefix) if !c.command().starts_with(prefix.as_str()) => (kb, c),
+                        _ => (kb, changed_keys(c, "pipeline add-mod", |k| add_modifier(k, m)))
+                    })
+                    .collect()
+            },
+            PipelineStep::Filter { command, key, when } => {
+                current.into_iter()
+                    .filter(|(_, c)| command.as_ref().is_none_or(|s| c.command().contains(s.as_str())))
+                    .filter(|(_, c)| key.as_ref().is_none_or(|k| c.keys.to_string().contains(k.as_str())))
+                    .filter(|(_, c)| when.as_ref().is_none_or(|w| c.when.as_ref().is_some_and(|cw| cw.contains(w.as_str()))))
+                    .collect()
+            },
+            PipelineStep::Rename { from, to } => {
+                current.into_iter()
+                    .map(|(kb, c)| {
+                        let before = c.action.clone();
+                        let action = match c.action {
+                            BindingAction::Run(a) if a == *from => BindingAction::Run(to.clone()),
+                            BindingAction::Disable(a) if a == *from => BindingAction::Disable(to.clone()),
+                            BindingAction::Macro(steps) => BindingAction::Macro(steps.into_iter()
+                                .map(|s| if s.command == *from { MacroStep { command: to.clone(), ..s } } else { s })
+                                .collect()),
+                            a => a
+                        };
+                        match action == before {
+                            true => (kb, KeyBinding { action, ..c }),
+                            false => (kb, KeyBinding { action, ..c }.generated("pipeline rename"))
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    let made = current.into_iter().map(|(kb, c)| {
+        let mut r = vec!();
+        if c.keys != kb.keys || c.action != kb.action {
+            if disable {
+                r.push(kb.copy_disabled().generated("pipeline"));
+            }
+            r.push(c);
+        }
+        r
+    }).collect();
+    Ok(counts.count(made))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFormat, parse_config_items};
+
+    fn bindings(json: &str) -> Vec<KeyBinding> {
+        parse_config_items(json, InputFormat::Json).unwrap().into_iter().map(KeyBinding::from).collect()
+    }
+
+    #[test]
+    fn steps_combine_and_disable_the_original_chords() {
+        let bindings = bindings(r#"[
+            { "key": "ctrl+alt+k", "command": "first" },
+            { "key": "alt+j", "command": "no-ctrl" },
+            { "key": "ctrl+s", "command": "save" }
+        ]"#);
+        let steps = load_pipeline(r#"[{ "op": "strip-mod", "args": { "modifier": "alt" } }, { "op": "remap" }]"#).unwrap();
+        let mut counts = RemapCounts::default();
+        let made = apply_pipeline(bindings, &steps, Direction::CtrlToCmd, true, &mut counts).unwrap();
+
+        let made: Vec<(String, String)> = made.iter().map(|kb| (kb.keys.to_string(), kb.action.to_string())).collect();
+        let expected = [("alt+ctrl+k", "-first"), ("meta+k", "first"), ("ctrl+s", "-save"), ("meta+s", "save")];
+        assert_eq!(made, expected.map(|(k, c)| (k.to_string(), c.to_string())));
+        assert_eq!((counts.remaps, counts.disables), (2, 2));
+    }
+}
@@ -0,0 +1,112 @@
+//! Built-in keymaps layered on top of the defaults.
+
+use std::sync::Arc;
+
+use crate::{
+    binding::{BindingAction, BindingSource, KeyBinding},
+    key::parse_key_sequence,
+    resolve::removed_indices,
+    when::covers
+};
+
+
+/// A built-in keymap.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Preset {
+    /// Emacs movement and editing keys in text inputs, `ctrl+x` chords for files and editors
+    Emacs
+}
+
+/// The Emacs preset: key, command and when clause. Later entries win in VS Code, so the
+/// suggest widget and quick open bindings come after the plain cursor movement.
+const EMACS: &[(&str, &str, Option<&str>)] = &[
+    ("ctrl+a", "cursorLineStart", Some("textInputFocus")),
+    ("ctrl+e", "cursorLineEnd", Some("textInputFocus")),
+    ("ctrl+f", "cursorRight", Some("textInputFocus")),
+    ("ctrl+b", "cursorLeft", Some("textInputFocus")),
+    ("ctrl+n", "cursorDown", Some("textInputFocus")),
+    ("ctrl+p", "cursorUp", Some("textInputFocus")),
+    ("alt+f", "cursorWordEndRight", Some("textInputFocus")),
+    ("alt+b", "cursorWordStartLeft", Some("textInputFocus")),
+    ("ctrl+v", "cursorPageDown", Some("textInputFocus")),
+    ("alt+v", "cursorPageUp", Some("textInputFocus")),
+    ("alt+shift+,", "cursorTop", Some("textInputFocus")),
+    ("alt+shift+.", "cursorBottom", Some("textInputFocus")),
+    ("ctrl+d", "deleteRight", Some("textInputFocus && !editorReadonly")),
+    ("ctrl+h", "deleteLeft", Some("textInputFocus && !editorReadonly")),
+    ("alt+d", "deleteWordRight", Some("textInputFocus && !editorReadonly")),
+    ("alt+backspace", "deleteWordLeft", Some("textInputFocus && !editorReadonly")),
+    ("ctrl+k", "deleteAllRight", Some("textInputFocus && !editorReadonly")),
+    ("ctrl+w", "editor.action.clipboardCutAction", Some("textInputFocus")),
+    ("alt+w", "editor.action.clipboardCopyAction", Some("textInputFocus")),
+    ("ctrl+y", "editor.action.clipboardPasteAction", Some("textInputFocus && !editorReadonly")),
+    ("ctrl+/", "undo", Some("textInputFocus && !editorReadonly")),
+    ("ctrl+space", "editor.action.setSelectionAnchor", Some("editorTextFocus")),
+    ("ctrl+g", "cancelSelection", Some("editorHasSelection && textInputFocus")),
+    ("ctrl+s", "actions.find", Some("editorFocus")),
+    ("alt+x", "workbench.action.showCommands", None),
+    ("ctrl+x ctrl+s", "workbench.action.files.save", None),
+    ("ctrl+x ctrl+f", "workbench.action.quickOpen", None),
+    ("ctrl+x k", "workbench.action.closeActiveEditor", None),
+    ("ctrl+x o", "workbench.action.focusNextGroup", None),
+    ("ctrl+x 2", "workbench.action.splitEditorDown", None),
+    ("ctrl+x 3", "workbench.action.splitEditorRight", None),
+    ("ctrl+n", "selectNextSuggestion", Some("suggestWidgetVisible && textInputFocus")),
+    ("ctrl+p", "selectPrevSuggestion", Some("suggestWidgetVisible && textInputFocus")),
+    ("ctrl+n", "workbench.action.quickOpenSelectNext", Some("inQuickOpen")),
+    ("ctrl+p", "workbench.action.quickOpenSelectPrevious", Some("inQuickOpen")),
+];
+
+impl Preset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Emacs => "emacs"
+        }
+    }
+
+    /// The bindings of the preset, in the order they are written.
+    pub fn bindings(self) -> Vec<KeyBinding> {
+        let table = match self {
+            Preset::Emacs => EMACS
+        };
+        let source = Some(Arc::new(BindingSource::Generated { by: format!("preset {}", self.name()), from: None }));
+        table.iter().map(|(key, command, when)| KeyBinding {
+            keys: parse_key_sequence(key),
+            action: BindingAction::Run(command.to_string()),
+            when: when.map(String::from),
+            args: None,
+            args_file: None,
+            original: None,
+            source: source.clone()
+        }).collect()
+    }
+}
+
+/// The bindings of `preset`, each preceded by disables of the `defaults` it shadows: those on
+/// the same chord whose when clause it covers, so they could never run again. Defaults only
+/// shadowed in some of their contexts keep working in the others and are left alone.
+/// Returns the bindings and the number of disables.
+pub fn apply_preset(preset: Preset, defaults: &[KeyBinding]) -> (Vec<KeyBinding>, usize) {
+
+    let removed = removed_indices(defaults);
+    let mut disabled = vec![false; defaults.len()];
+    let mut bneu = vec!();
+    let mut disables = 0;
+
+    for kb in preset.bindings() {
+        for (i, d) in defaults.iter().enumerate() {
+            if disabled[i] || d.is_disable() || removed.contains(&i) || d.keys != kb.keys || d.action == kb.action {
+                continue;
+            }
+            if covers(&kb.when, &d.when) {
+                bneu.push(d.copy_disabled().generated(&format!("preset {}", preset.name())));
+                disabled[i] = true;
+                disables += 1;
+            }
+        }
+        bneu.push(kb);
+    }
+
+    (bneu, disables)
+}
@@ -0,0 +1,140 @@
+//! Named keybinding sets: each profile is a directory under the config directory holding
+//! the defaults it starts from and, optionally, the rules transforming them.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+
+use crate::{
+    Result,
+    binding::{KeyBinding, to_config_items},
+    input::{InputFormat, load_bindings},
+    install::{install, write_atomic},
+    map::{Direction, RemapCounts, remap_bindings},
+    paths::config_dir,
+    rules::{apply_rules, load_macros, load_rules}
+};
+
+
+/// A stored profile.
+pub struct Profile {
+    pub name: String,
+    pub dir: PathBuf
+}
+
+impl Profile {
+    pub fn defaults_path(&self) -> PathBuf {
+        self.dir.join("defaults.json")
+    }
+
+    /// The rules file, None if the profile uses the plain ctrl -> cmd remap.
+    pub fn rules_path(&self) -> Option<PathBuf> {
+        ["rules.toml", "rules.json"].iter()
+            .map(|f| self.dir.join(f))
+            .find(|p| p.exists())
+    }
+
+    /// The keybindings the profile generates, with disables of the changed defaults.
+    pub fn generate(&self) -> Result<Vec<KeyBinding>> {
+        let defaults = load_bindings(&self.defaults_path(), InputFormat::Json)?;
+        let mut counts = RemapCounts::default();
+        match self.rules_path() {
+            Some(rules) => {
+                let mut bneu = apply_rules(&defaults, &load_rules(&rules)?, true, &mut counts)?;
+                bneu.extend(load_macros(&rules)?);
+                Ok(bneu)
+            },
+            None => Ok(remap_bindings(&defaults, Direction::CtrlToCmd, true, &mut counts))
+        }
+    }
+}
+
+fn profiles_dir() -> Result<PathBuf> {
+    Ok(config_dir()?.join("profiles"))
+}
+
+fn active_file() -> Result<PathBuf> {
+    Ok(config_dir()?.join("active-profile"))
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("invalid profile name '{}', use letters, digits, `-` and `_`", name);
+    }
+    Ok(())
+}
+
+/// Stores a new profile with copies of `defaults` and `rules`, both are checked to load first.
+pub fn create_profile(name: &str, defaults: &Path, rules: Option<&Path>) -> Result<Profile> {
+
+    check_name(name)?;
+    let profile = Profile { name: name.to_string(), dir: profiles_dir()?.join(name) };
+    if profile.dir.exists() {
+        bail!("profile {} already exists", name);
+    }
+
+    // stored as JSON whatever the input was, so the profile does not depend on format detection
+    let bindings = load_bindings(defaults, InputFormat::Auto)?;
+    let rules_content = match rules {
+        Some(path) => {
+            load_rules(path)?;
+            load_macros(path)?;
+            let file = if path.extension().is_some_and(|e| e == "json") { "rules.json" } else { "rules.toml" };
+            Some((file, std::fs::read_to_string(path)?))
+        },
+        None => None
+    };
+
+    std::fs::create_dir_all(&profile.dir)?;
+    write_atomic(&profile.defaults_path(), &(serde_json::to_string_pretty(&to_config_items(&bindings, false))? + "\n"))?;
+    if let Some((file, content)) = rules_content {
+        write_atomic(&profile.dir.join(file), &content)?;
+    }
+    Ok(profile)
+}
+
+/// The names of all stored profiles, sorted.
+pub fn list_profiles() -> Result<Vec<String>> {
+    let dir = profiles_dir()?;
+    if !dir.exists() {
+        return Ok(vec!());
+    }
+    let mut names = vec!();
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// The stored profile `name`.
+pub fn open_profile(name: &str) -> Result<Profile> {
+    check_name(name)?;
+    let profile = Profile { name: name.to_string(), dir: profiles_dir()?.join(name) };
+    if !profile.defaults_path().exists() {
+        bail!("no profile named {}, create it with `codekeys profile create {}`", name, name);
+    }
+    Ok(profile)
+}
+
+/// The name of the profile switched to last.
+pub fn active_profile() -> Result<Option<String>> {
+    match std::fs::read_to_string(active_file()?) {
+        Ok(name) => Ok(Some(name.trim().to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow!("cannot read the active profile: {}", e))
+    }
+}
+
+/// Generates the keybindings of profile `name`, installs them at `target` and makes the
+/// profile the active one. Returns the number of bindings written and the backup of `target`.
+pub fn switch_profile(name: &str, target: &Path) -> Result<(usize, Option<PathBuf>)> {
+    let profile = open_profile(name)?;
+    let bindings = profile.generate()?;
+    let backup = install(target, &(serde_json::to_string_pretty(&to_config_items(&bindings, false))? + "\n"))?;
+    write_atomic(&active_file()?, &format!("{}\n", name))?;
+    Ok((bindings.len(), backup))
+}
@@ -0,0 +1,117 @@
+//! Finding bindings by command, key and when clause.
+
+use std::{collections::HashSet, sync::Arc};
+
+use regex::Regex;
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use crate::{Result, binding::{BindingSource, KeyBinding}, key::{KeyRule, parse_key_sequence}, resolve::removed_indices, rules::wildcard_match, taxonomy::Taxonomy};
+
+
+/// How a filter matches its text.
+pub enum Pattern {
+    /// a substring, or a `*` wildcard pattern if it has a `*`
+    Text(String),
+    Regex(Regex)
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, regex: bool) -> Result<Pattern> {
+        Ok(match regex {
+            true => Pattern::Regex(Regex::new(pattern)?),
+            false => Pattern::Text(pattern.to_string())
+        })
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            Pattern::Text(p) if p.contains('*') => wildcard_match(p, text),
+            Pattern::Text(p) => text.contains(p.as_str()),
+            Pattern::Regex(r) => r.is_match(text)
+        }
+    }
+}
+
+/// The filters of a query, a binding matches if it matches all of them.
+pub struct Query {
+    pub command: Option<Pattern>,
+    /// a key sequence that must occur in the chord, or a regex on the key string
+    pub key: Option<Pattern>,
+    pub when: Option<Pattern>,
+    /// only bindings in effect: no disables and nothing a disable removes
+    pub effective: bool,
+    /// the category the command has in the taxonomy
+    pub category: Option<(String, Taxonomy)>
+}
+
+impl Query {
+    pub fn new(command: Option<&str>, key: Option<&str>, when: Option<&str>, regex: bool) -> Result<Query> {
+        let pattern = |p: Option<&str>| p.map(|p| Pattern::new(p, regex)).transpose();
+        Ok(Query { command: pattern(command)?, key: pattern(key)?, when: pattern(when)?, effective: false, category: None })
+    }
+
+    pub fn matches(&self, kb: &KeyBinding) -> bool {
+        let key = match &self.key {
+            None => true,
+            Some(Pattern::Text(k)) => contains_keys(&kb.keys, &parse_key_sequence(k)),
+            Some(p) => p.matches(&kb.keys.to_string())
+        };
+        key && self.command.as_ref().is_none_or(|p| p.matches(kb.command()))
+            && self.when.as_ref().is_none_or(|p| kb.when.as_deref().is_some_and(|w| p.matches(w)))
+            && self.category.as_ref().is_none_or(|(c, t)| t.category(kb.command()) == c)
+    }
+}
+
+/// Whether the keys of `needle` occur one after another in `keys`, so `ctrl+k` finds
+/// `ctrl+k` itself and every `ctrl+k ...` chord, whatever the modifier spelling.
+fn contains_keys(keys: &KeyRule, needle: &KeyRule) -> bool {
+    let keys: Vec<_> = keys.keys().collect();
+    let needle: Vec<_> = needle.keys().collect();
+    keys.windows(needle.len()).any(|w| w == needle.as_slice())
+}
+
+/// A binding found by a query.
+#[derive(Serialize)]
+pub struct QueryMatch {
+    pub index: usize,
+    #[serde(rename = "key")]
+    pub keys: KeyRule,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Arc<BindingSource>>
+}
+
+impl std::fmt::Display for QueryMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:>4}] {:<24} {}", self.index, self.keys.to_string(), self.command)?;
+        if let Some(w) = &self.when {
+            write!(f, "  when {}", w)?;
+        }
+        if let Some(s) = self.source.as_ref().filter(|s| !s.is_file()) {
+            write!(f, "  (from {})", s)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// The bindings matching `query`, in file order.
+pub fn query(bindings: &[KeyBinding], query: &Query) -> Vec<QueryMatch> {
+    let removed = if query.effective { removed_indices(bindings) } else { HashSet::new() };
+    bindings.iter().enumerate()
+        .filter(|(i, kb)| !(removed.contains(i) || query.effective && kb.is_disable()))
+        .filter(|(_, kb)| query.matches(kb))
+        .map(|(index, kb)| QueryMatch {
+            index,
+            keys: kb.keys.clone(),
+            command: kb.action.to_string(),
+            when: kb.when.clone(),
+            args: kb.args.clone(),
+            source: kb.source.clone()
+        })
+        .collect()
+}
@@ -0,0 +1,93 @@
+//! Writing the bindings in any format through a handlebars template.
+//!
+//! The template gets a `bindings` array and its `count`. Every binding has its `index` in the
+//! input, the `key` string, the `chord` as a list of key presses, `command`, `disable`, `when`,
+//! `args` and `source`; a key press has the `key` without modifiers, the whole `text` and
+//! `ctrl`, `shift`, `alt` and `meta` as booleans.
+
+use std::sync::Arc;
+
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use crate::{
+    Result,
+    binding::{BindingSource, ConfigItem, KeyBinding},
+    key::{Key, KeyRule, MODIFIER_SHIFT, MODIFIER_CONTROL, MODIFIER_COMMAND, MODIFIER_OPTION}
+};
+
+
+/// A key press of a chord as the template sees it.
+#[derive(Serialize)]
+pub struct TemplateKey {
+    pub key: String,
+    pub text: String,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub meta: bool
+}
+
+impl From<&Key> for TemplateKey {
+    fn from(k: &Key) -> Self {
+        TemplateKey {
+            key: k.code.to_string(),
+            text: k.to_string(),
+            ctrl: k.modifiers & MODIFIER_CONTROL != 0,
+            shift: k.modifiers & MODIFIER_SHIFT != 0,
+            alt: k.modifiers & MODIFIER_OPTION != 0,
+            meta: k.modifiers & MODIFIER_COMMAND != 0
+        }
+    }
+}
+
+/// A binding as the template sees it.
+#[derive(Serialize)]
+pub struct TemplateBinding {
+    pub index: usize,
+    pub key: KeyRule,
+    pub chord: Vec<TemplateKey>,
+    /// the command run or removed, without the `-` of a disable
+    /// (`runCommands` for a macro, with the steps in `args`)
+    pub command: String,
+    pub disable: bool,
+    pub when: Option<String>,
+    pub args: Option<Value>,
+    pub source: Option<Arc<BindingSource>>
+}
+
+/// What a template is rendered with.
+#[derive(Serialize)]
+pub struct TemplateData {
+    pub bindings: Vec<TemplateBinding>,
+    pub count: usize
+}
+
+pub fn template_data(bindings: &[KeyBinding]) -> TemplateData {
+    let bindings: Vec<TemplateBinding> = bindings.iter().enumerate().map(|(index, kb)| TemplateBinding {
+        index,
+        key: kb.keys.clone(),
+        chord: kb.keys.keys().map(TemplateKey::from).collect(),
+        command: kb.command().to_string(),
+        disable: kb.is_disable(),
+        when: kb.when.clone(),
+        args: ConfigItem::from(kb).args,
+        source: kb.source.clone()
+    }).collect();
+    TemplateData { count: bindings.len(), bindings }
+}
+
+/// The bindings written through the handlebars `template`. Nothing is HTML escaped, the
+/// output is whatever format the template is.
+#[cfg(feature = "render")]
+pub fn render_template(template: &str, bindings: &[KeyBinding]) -> Result<String> {
+    let mut handlebars = handlebars::Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars.register_template_string("template", template)?;
+    Ok(handlebars.render("template", &template_data(bindings))?)
+}
+
+#[cfg(not(feature = "render"))]
+pub fn render_template(_template: &str, _bindings: &[KeyBinding]) -> Result<String> {
+    anyhow::bail!("cannot render templates, codekeys was built without the render feature")
+}
@@ -0,0 +1,97 @@
+//! Decisions on generated bindings that land on a chord another binding uses, kept in a
+//! resolutions file so later runs settle them the same way without asking.
+
+use std::path::Path;
+
+use anyhow::anyhow;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{Result, key::{KeyRule, parse_key_sequence}};
+
+
+/// How a conflict between the binding already on a chord and a generated one is settled.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    /// Keep the old binding and drop the generated one with its disable
+    Keep,
+    /// Keep the generated binding and take the old one off the chord
+    Replace,
+    /// Take both off the chord
+    Disable,
+    /// Leave the conflict as it would be without a decision
+    Skip
+}
+
+impl Resolution {
+    /// The resolution of an answer to the prompt, the first letter will do.
+    pub fn parse_answer(answer: &str) -> Option<Resolution> {
+        match answer.trim().to_lowercase().as_str() {
+            "k" | "keep" => Some(Resolution::Keep),
+            "r" | "replace" => Some(Resolution::Replace),
+            "d" | "disable" => Some(Resolution::Disable),
+            "s" | "skip" => Some(Resolution::Skip),
+            _ => None
+        }
+    }
+}
+
+/// The decision on one conflict, the chord and when clause with the `old` command on it and
+/// the `new` generated one.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Decision {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    pub old: String,
+    pub new: String,
+    pub resolution: Resolution
+}
+
+impl Decision {
+    fn is_for(&self, keys: &KeyRule, when: &Option<String>, old: &str, new: &str) -> bool {
+        self.old == old && self.new == new && &self.when == when && &parse_key_sequence(&self.key) == keys
+    }
+}
+
+/// A resolutions file, a `[[resolution]]` table per decision.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Resolutions {
+    #[serde(default, rename = "resolution")]
+    pub decisions: Vec<Decision>
+}
+
+impl Resolutions {
+    pub fn parse(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// The recorded resolution of `new` landing on `old`, if there is one.
+    pub fn get(&self, keys: &KeyRule, when: &Option<String>, old: &str, new: &str) -> Option<Resolution> {
+        self.decisions.iter().find(|d| d.is_for(keys, when, old, new)).map(|d| d.resolution)
+    }
+
+    /// Records a resolution, replacing an earlier one for the same conflict.
+    pub fn record(&mut self, keys: &KeyRule, when: &Option<String>, old: &str, new: &str, resolution: Resolution) {
+        self.decisions.retain(|d| !d.is_for(keys, when, old, new));
+        self.decisions.push(Decision {
+            key: keys.to_string(), when: when.clone(), old: old.to_string(), new: new.to_string(), resolution
+        });
+    }
+}
+
+/// The resolutions of the file at `path`, none if it does not exist yet.
+pub fn load_resolutions(path: &Path) -> Result<Resolutions> {
+    if !path.exists() {
+        return Ok(Resolutions::default());
+    }
+    let content = std::fs::read_to_string(path).map_err(|e| anyhow!("cannot read resolutions {}: {}", path.display(), e))?;
+    Resolutions::parse(&content).map_err(|e| anyhow!("{} is not a valid resolutions file: {}", path.display(), e))
+}
+
+pub fn save_resolutions(path: &Path, resolutions: &Resolutions) -> Result<()> {
+    std::fs::write(path, toml::to_string(resolutions)?)
+        .map_err(|e| anyhow!("cannot write resolutions {}: {}", path.display(), e))
+}
@@ -0,0 +1,240 @@
+//! Replaying bindings the way VS Code resolves them.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use crate::{binding::{BindingAction, KeyBinding}, key::{KeyRule, from_physical}};
+
+
+/// One entry in the resolution chain of a (key, when) pair.
+#[derive(Serialize)]
+pub struct ResolutionStep {
+    pub index: usize,
+    pub command: BindingAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed_by: Option<usize>
+}
+
+impl ResolutionStep {
+    pub fn is_disable(&self) -> bool {
+        self.command.is_disable()
+    }
+}
+
+/// All bindings sharing a key and when clause, in the order VS Code evaluates them.
+#[derive(Serialize)]
+pub struct Resolution {
+    #[serde(rename = "key")]
+    pub keys: KeyRule,
+    pub when: Option<String>,
+    pub chain: Vec<ResolutionStep>,
+    pub winner: Option<usize>
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.when {
+            Some(w) => writeln!(f, "{}  when {}", self.keys, w)?,
+            None => writeln!(f, "{}", self.keys)?
+        }
+        for step in self.chain.iter() {
+            let marker = if self.winner == Some(step.index) { "->" } else { "  " };
+            write!(f, "  {} [{:>4}] {}", marker, step.index, step.command)?;
+            if let Some(by) = step.removed_by {
+                write!(f, "  (removed by [{}])", by)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Groups the bindings by parsed key and when clause and replays them like VS Code does:
+/// a `-command` entry removes earlier bindings of that command on the same key (and the
+/// same when clause, unless the disable has none), and the last remaining binding wins.
+pub fn resolve(bindings: &[KeyBinding]) -> Vec<Resolution> {
+
+    let mut groups: Vec<Resolution> = vec!();
+    let mut lookup: HashMap<(KeyRule, Option<String>), usize> = HashMap::new();
+
+    for (index, kb) in bindings.iter().enumerate() {
+
+        let gi = *lookup.entry((kb.keys.clone(), kb.when.clone())).or_insert_with(|| {
+            groups.push(Resolution { keys: kb.keys.clone(), when: kb.when.clone(), chain: vec!(), winner: None });
+            groups.len() - 1
+        });
+
+        groups[gi].chain.push(ResolutionStep { index, command: kb.action.clone(), removed_by: None });
+
+        if kb.is_disable() {
+            let affected = groups.iter_mut().enumerate()
+                .filter(|(_, g)| g.keys == kb.keys && (kb.when.is_none() || g.when == kb.when));
+            for (i, g) in affected {
+                let mut hit = false;
+                for step in g.chain.iter_mut().filter(|s| kb.action.removes(&s.command) && s.removed_by.is_none()) {
+                    step.removed_by = Some(index);
+                    hit = true;
+                }
+                // a disable without a when clause reaches into other groups, show it there too
+                if hit && i != gi {
+                    g.chain.push(ResolutionStep { index, command: kb.action.clone(), removed_by: None });
+                }
+            }
+        }
+    }
+
+    for g in groups.iter_mut() {
+        g.winner = g.chain.iter().rev()
+            .find(|s| !s.is_disable() && s.removed_by.is_none())
+            .map(|s| s.index);
+    }
+
+    groups
+}
+
+/// Indices of the bindings a later `-command` entry removes.
+pub fn removed_indices(bindings: &[KeyBinding]) -> HashSet<usize> {
+    resolve(bindings).iter()
+        .flat_map(|r| r.chain.iter())
+        .filter(|s| s.removed_by.is_some())
+        .map(|s| s.index)
+        .collect()
+}
+
+/// Which file an effective binding comes from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Default,
+    User
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::User => write!(f, "user")
+        }
+    }
+}
+
+/// A chord of the effective keymap and the command it runs in its context.
+#[derive(Serialize)]
+pub struct EffectiveBinding {
+    #[serde(rename = "key")]
+    pub keys: KeyRule,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Value>,
+    pub source: Source,
+    /// the index of the entry in its file
+    pub index: usize
+}
+
+impl std::fmt::Display for EffectiveBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:<24} {}  [{} {}]", self.keys.to_string(), self.command, self.source, self.index)?;
+        if let Some(w) = &self.when {
+            write!(f, "  when {}", w)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// The keymap VS Code ends up with when `user` is applied on top of `defaults`: for every
+/// key and when clause the binding that wins, in the order the pairs first appear.
+pub fn effective(defaults: &[KeyBinding], user: &[KeyBinding]) -> Vec<EffectiveBinding> {
+
+    let all: Vec<KeyBinding> = defaults.iter().chain(user.iter()).cloned().collect();
+
+    resolve(&all).into_iter()
+        .filter_map(|r| r.winner)
+        .map(|i| {
+            let kb = &all[i];
+            let (source, index) = match i < defaults.len() {
+                true => (Source::Default, i),
+                false => (Source::User, i - defaults.len())
+            };
+            EffectiveBinding {
+                keys: kb.keys.clone(), command: kb.command().to_string(), when: kb.when.clone(), args: kb.args.clone(), source, index
+            }
+        })
+        .collect()
+}
+
+/// A binding of the effective keymap that typing a chord reaches: it runs if its keys are
+/// the whole chord, otherwise VS Code waits for the rest of its keys.
+#[derive(Serialize)]
+pub struct ChordMatch {
+    #[serde(flatten)]
+    pub binding: EffectiveBinding,
+    pub complete: bool
+}
+
+impl std::fmt::Display for ChordMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:<6} {}", if self.complete { "runs" } else { "waits" }, self.binding)
+    }
+}
+
+/// The bindings of the effective keymap the keys of `chord` can trigger, in the order VS Code
+/// tries them: the one added last first, and the first whose when clause holds is taken.
+/// Keys are compared with physical key codes (`[KeyK]`) taken as their US layout keycaps.
+pub fn resolve_chord(defaults: &[KeyBinding], user: &[KeyBinding], chord: &KeyRule) -> Vec<ChordMatch> {
+
+    let typed: Vec<_> = chord.keys().map(from_physical).collect();
+    let precedence = |b: &EffectiveBinding| match b.source {
+        Source::Default => b.index,
+        Source::User => defaults.len() + b.index
+    };
+
+    let mut matches: Vec<ChordMatch> = effective(defaults, user).into_iter()
+        .filter(|b| b.keys.keys().count() >= typed.len() && b.keys.keys().zip(typed.iter()).all(|(k, t)| from_physical(k) == *t))
+        .map(|b| ChordMatch { complete: b.keys.keys().count() == typed.len(), binding: b })
+        .collect();
+    matches.sort_by_key(|m| std::cmp::Reverse(precedence(&m.binding)));
+    matches
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFormat, parse_config_items};
+
+    fn bindings(json: &str) -> Vec<KeyBinding> {
+        parse_config_items(json, InputFormat::Json).unwrap().into_iter().map(KeyBinding::from).collect()
+    }
+
+    #[test]
+    fn a_disable_of_the_last_binding_lets_the_one_before_it_win() {
+        let bindings = bindings(r#"[
+            { "key": "ctrl+k", "command": "first" },
+            { "key": "ctrl+k", "command": "second" },
+            { "key": "ctrl+k", "command": "-second" }
+        ]"#);
+        let resolutions = resolve(&bindings);
+        assert_eq!(resolutions.len(), 1);
+        let r = &resolutions[0];
+        assert_eq!(r.winner, Some(0));
+        let removed: Vec<Option<usize>> = r.chain.iter().map(|s| s.removed_by).collect();
+        assert_eq!(removed, vec!(None, Some(2), None));
+    }
+
+    #[test]
+    fn a_disable_of_the_first_binding_leaves_the_last_one_winning() {
+        let bindings = bindings(r#"[
+            { "key": "ctrl+k", "command": "first" },
+            { "key": "ctrl+k", "command": "second" },
+            { "key": "ctrl+k", "command": "-first" }
+        ]"#);
+        let r = &resolve(&bindings)[0];
+        assert_eq!(r.winner, Some(1));
+        assert_eq!(r.chain[0].removed_by, Some(2));
+        assert_eq!(r.chain[1].removed_by, None);
+    }
+}
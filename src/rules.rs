@@ -0,0 +1,200 @@
+//! Rules files describing modifier transformations.
+
+use std::{path::Path, sync::Arc};
+
+use anyhow::{anyhow, bail};
+use serde_derive::Deserialize;
+
+use crate::{
+    Result,
+    binding::{BindingAction, BindingSource, KeyBinding, MacroStep},
+    input::strip_jsonc,
+    key::{Key, KeyRule, parse_key_sequence_strict, parse_modifier, parse_one_key},
+    map::{RemapCounts, add_modifier, remove_modifier, swap_modifiers},
+    par,
+    when::{parse_when, rewrite_when, within}
+};
+
+
+/// A rules file, `[[rule]]` tables in TOML or `{ "rule": [...] }` in JSON, and `[[macro]]`
+/// tables binding a chord to a list of commands.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RulesFile {
+    #[serde(default, alias = "rules")]
+    pub rule: Vec<Rule>,
+    #[serde(default, rename = "macro", alias = "macros")]
+    pub macros: Vec<MacroRule>
+}
+
+/// A chord running commands one after the other.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacroRule {
+    /// only used in messages
+    pub name: Option<String>,
+    pub key: String,
+    pub when: Option<String>,
+    /// command ids, or tables with a `command` and its `args`
+    pub commands: Vec<serde_json::Value>
+}
+
+impl MacroRule {
+    /// The `runCommands` binding of the macro.
+    pub fn binding(&self) -> Result<KeyBinding> {
+        let name = self.name.as_deref().unwrap_or(&self.key);
+        let keys = parse_key_sequence_strict(&self.key).map_err(|e| anyhow!("macro {}: {}", name, e))?;
+        let steps = self.commands.iter()
+            .map(|c| MacroStep::from_value(c).ok_or_else(|| anyhow!("macro {}: {} is neither a command id nor a table with a command", name, c)))
+            .collect::<Result<Vec<_>>>()?;
+        if steps.is_empty() {
+            bail!("macro {} has no commands", name);
+        }
+        let source = Some(Arc::new(BindingSource::Generated { by: format!("macro {}", name), from: None }));
+        Ok(KeyBinding { keys, action: BindingAction::Macro(steps), when: self.when.clone(), args: None, args_file: None, original: None, source })
+    }
+}
+
+/// A transformation applied to every binding matching all of the given criteria.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// only used in messages
+    pub name: Option<String>,
+
+    /// modifiers the (first) key must have
+    #[serde(default)]
+    pub modifiers: Vec<String>,
+    /// the key without modifiers, e.g. `k` or `[Comma]`
+    pub key: Option<String>,
+    /// command id, `*` matches any text
+    pub command: Option<String>,
+    /// text the when clause has to contain
+    pub when: Option<String>,
+    /// a when expression the binding has to be scoped within, e.g. `editorTextFocus`
+    pub within: Option<String>,
+    /// a when expression the binding must not be scoped within, e.g. `terminalFocus`
+    pub not_within: Option<String>,
+
+    /// exchange two modifiers
+    pub swap: Option<[String; 2]>,
+    /// add a modifier
+    pub add: Option<String>,
+    /// remove a modifier
+    pub strip: Option<String>,
+    /// a when expression joined to the clause with `&&`, e.g. `!terminalFocus`
+    pub add_when: Option<String>,
+    /// context keys to drop from the when clause, `*` matches any text
+    #[serde(default)]
+    pub strip_when: Vec<String>
+}
+
+impl Rule {
+    /// Whether the binding meets all criteria of the rule.
+    pub fn matches(&self, kb: &KeyBinding) -> Result<bool> {
+        for m in self.modifiers.iter() {
+            if !kb.has_modifier(parse_modifier(m)?) {
+                return Ok(false);
+            }
+        }
+        Ok(self.key.as_ref().is_none_or(|k| parse_one_key(k).code == kb.keys.first.code)
+            && self.command.as_ref().is_none_or(|c| wildcard_match(c, kb.command()))
+            && self.when.as_ref().is_none_or(|w| kb.when.as_ref().is_some_and(|kw| kw.contains(w.as_str())))
+            && self.within.as_ref().is_none_or(|c| within(&kb.when, c))
+            && self.not_within.as_ref().is_none_or(|c| !within(&kb.when, c)))
+    }
+
+    /// The key with the actions of the rule applied.
+    pub fn apply(&self, key: &Key) -> Result<Key> {
+        let mut key = key.clone();
+        if let Some([a, b]) = &self.swap {
+            key = swap_modifiers(&key, parse_modifier(a)?, parse_modifier(b)?);
+        }
+        if let Some(m) = &self.add {
+            key = add_modifier(&key, parse_modifier(m)?);
+        }
+        if let Some(m) = &self.strip {
+            key = remove_modifier(&key, parse_modifier(m)?);
+        }
+        Ok(key)
+    }
+
+    /// The when clause with the rewrites of the rule applied.
+    pub fn rewrite_when(&self, when: &Option<String>) -> Result<Option<String>> {
+        if self.add_when.is_none() && self.strip_when.is_empty() {
+            return Ok(when.clone());
+        }
+        rewrite_when(when, self.add_when.as_deref(), &self.strip_when)
+    }
+}
+
+fn read_rules_file(path: &Path) -> Result<RulesFile> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("cannot read rules {}: {}", path.display(), e))?;
+    if path.extension().is_some_and(|e| e == "json") {
+        Ok(serde_json::from_str(&strip_jsonc(&content))?)
+    } else {
+        Ok(toml::from_str(&content)?)
+    }
+}
+
+/// Reads the rules of a TOML file, or a JSON file if the extension is `.json`.
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>> {
+    let file = read_rules_file(path)?;
+    for (i, rule) in file.rule.iter().enumerate() {
+        if rule.swap.is_none() && rule.add.is_none() && rule.strip.is_none() && rule.add_when.is_none() && rule.strip_when.is_empty() {
+            bail!("rule {} in {} has no swap, add, strip, add_when or strip_when action", rule.name.clone().unwrap_or(i.to_string()), path.display());
+        }
+        for context in [&rule.within, &rule.not_within, &rule.add_when].into_iter().flatten() {
+            parse_when(context).map_err(|e| anyhow!("rule {} in {}: {:#}", rule.name.clone().unwrap_or(i.to_string()), path.display(), e))?;
+        }
+    }
+    Ok(file.rule)
+}
+
+/// The `runCommands` bindings of the macros of a rules file.
+pub fn load_macros(path: &Path) -> Result<Vec<KeyBinding>> {
+    read_rules_file(path)?.macros.iter()
+        .map(|m| m.binding().map_err(|e| anyhow!("{} in {}", e, path.display())))
+        .collect()
+}
+
+/// `*` in the pattern matches any (possibly empty) text, everything else literally.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((head, tail)) => text.strip_prefix(head).is_some_and(|rest| {
+            (0..=rest.len()).filter(|i| rest.is_char_boundary(*i)).any(|i| wildcard_match(tail, &rest[i..]))
+        })
+    }
+}
+
+/// Runs every binding through the rules in order. Bindings the rules changed are emitted
+/// like the plain remap does, a disable of the original followed by the new chord.
+pub fn apply_rules(bindings: &[KeyBinding], rules: &[Rule], disable: bool, counts: &mut RemapCounts) -> Result<Vec<KeyBinding>> {
+
+    let made = par::map(bindings, |kb| {
+        let mut current = kb.clone();
+        let mut applied = vec!();
+        for (i, rule) in rules.iter().enumerate() {
+            if rule.matches(&current)? {
+                let first = rule.apply(&current.keys.first)?;
+                let rest = current.keys.rest.iter().map(|k| rule.apply(k)).collect::<Result<_>>()?;
+                current.keys = KeyRule { first, rest };
+                current.when = rule.rewrite_when(&current.when)
+                    .map_err(|e| anyhow!("rule {}: [{}] {}: {:#}", rule.name.clone().unwrap_or(i.to_string()), kb.keys, kb.action, e))?;
+                applied.push(rule.name.clone().unwrap_or_else(|| i.to_string()));
+            }
+        }
+        let mut r = vec!();
+        if current.keys != kb.keys || current.when != kb.when {
+            let by = format!("rule {}", applied.join(", "));
+            if disable {
+                r.push(kb.copy_disabled().generated(&by));
+            }
+            r.push(current.generated(&by));
+        }
+        Ok(r)
+    });
+    Ok(counts.count(made.into_iter().collect::<Result<_>>()?))
+}
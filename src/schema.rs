@@ -0,0 +1,107 @@
+//! JSON Schemas of the files codekeys reads, for editors and front-ends.
+
+use serde_json::{Value, json};
+
+
+/// A file format with a schema.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SchemaKind {
+    /// A keybindings.json file
+    Keybindings,
+    /// The source of `build`, entries with a `platform` field and the `primary` modifier
+    Source,
+    /// A rules file for `--rules`, in TOML or JSON
+    Rules
+}
+
+const MODIFIERS: &[&str] = &["ctrl", "shift", "alt", "meta", "cmd", "win", "super"];
+
+fn entry(with_platform: bool) -> Value {
+    let mut properties = json!({
+        "key": { "type": "string", "description": "keys joined by `+`, chords separated by spaces, e.g. `ctrl+k ctrl+c`" },
+        "command": { "type": "string", "description": "command id, with a leading `-` to remove a binding of it" },
+        "when": { "type": "string", "description": "when clause the binding applies in" },
+        "args": { "description": "arguments of the command, `{ \"$file\": path }` reads them from a JSON file" }
+    });
+    if with_platform {
+        let platform = json!({ "enum": ["macos", "mac", "darwin", "linux", "windows", "win"] });
+        properties["platform"] = json!({
+            "description": "the platforms the entry is for, all of them without it",
+            "oneOf": [platform, { "type": "array", "items": platform }]
+        });
+        properties["key"]["description"] = json!("keys joined by `+`, `primary` is cmd on macOS and ctrl elsewhere");
+    }
+    json!({
+        "type": "object",
+        "required": ["key", "command"],
+        "properties": properties
+    })
+}
+
+fn rules() -> Value {
+    let modifier = json!({ "enum": MODIFIERS });
+    let text = json!({ "type": "string" });
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "definitions": {
+            "rule": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "name": { "type": "string", "description": "only used in messages" },
+                    "modifiers": { "type": "array", "items": modifier, "description": "modifiers the (first) key must have" },
+                    "key": { "type": "string", "description": "the key without modifiers, e.g. `k` or `[Comma]`" },
+                    "command": { "type": "string", "description": "command id, `*` matches any text" },
+                    "when": { "type": "string", "description": "text the when clause has to contain" },
+                    "within": { "type": "string", "description": "a when expression the binding has to be scoped within" },
+                    "not_within": { "type": "string", "description": "a when expression the binding must not be scoped within" },
+                    "swap": { "type": "array", "items": modifier, "minItems": 2, "maxItems": 2, "description": "exchange two modifiers" },
+                    "add": { "enum": MODIFIERS, "description": "add a modifier" },
+                    "strip": { "enum": MODIFIERS, "description": "remove a modifier" },
+                    "add_when": { "type": "string", "description": "a when expression joined to the clause with `&&`" },
+                    "strip_when": { "type": "array", "items": text, "description": "context keys to drop from the when clause, `*` matches any text" }
+                }
+            },
+            "macro": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["key", "commands"],
+                "properties": {
+                    "name": { "type": "string", "description": "only used in messages" },
+                    "key": { "type": "string" },
+                    "when": { "type": "string" },
+                    "commands": {
+                        "type": "array",
+                        "minItems": 1,
+                        "description": "command ids, or tables with a `command` and its `args`",
+                        "items": { "oneOf": [text, {
+                            "type": "object",
+                            "required": ["command"],
+                            "properties": { "command": text, "args": {} }
+                        }] }
+                    }
+                }
+            }
+        },
+        "properties": {
+            "rule": { "type": "array", "items": { "$ref": "#/definitions/rule" } },
+            "rules": { "type": "array", "items": { "$ref": "#/definitions/rule" } },
+            "macro": { "type": "array", "items": { "$ref": "#/definitions/macro" } },
+            "macros": { "type": "array", "items": { "$ref": "#/definitions/macro" } }
+        }
+    })
+}
+
+/// The JSON Schema (draft 7) of `kind`.
+pub fn schema(kind: SchemaKind) -> Value {
+    let (title, mut schema) = match kind {
+        SchemaKind::Keybindings => ("VS Code keybindings", json!({ "type": "array", "items": entry(false) })),
+        SchemaKind::Source => ("codekeys build source", json!({ "type": "array", "items": entry(true) })),
+        SchemaKind::Rules => ("codekeys rules", rules())
+    };
+    schema["$schema"] = json!("http://json-schema.org/draft-07/schema#");
+    schema["title"] = json!(title);
+    schema
+}
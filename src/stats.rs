@@ -0,0 +1,174 @@
+//! Numbers about a keymap for deciding what to restructure.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_derive::Serialize;
+
+use crate::{
+    binding::KeyBinding,
+    conflict::find_conflicts,
+    key::{Key, KeyRule, anykey},
+    resolve::removed_indices,
+    taxonomy::Taxonomy
+};
+
+
+/// How many bindings use a modifier combination or a key.
+#[derive(Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize
+}
+
+/// A binding that never runs because a later one applies whenever it does.
+#[derive(Serialize)]
+pub struct Unreachable {
+    pub index: usize,
+    #[serde(rename = "key")]
+    pub keys: KeyRule,
+    pub command: String,
+    pub shadowed_by: usize
+}
+
+/// The statistics of a keymap, bindings removed by a disable do not count.
+#[derive(Serialize)]
+pub struct Stats {
+    /// bindings that are in effect
+    pub bindings: usize,
+    pub disables: usize,
+    /// bindings of two or more keys
+    pub chords: usize,
+    /// modifier combinations of the first key, most used first
+    pub modifiers: Vec<Count>,
+    /// the taxonomy categories of the commands, most used first
+    pub categories: Vec<Count>,
+    /// the keys with the most bindings, most first
+    pub overloaded: Vec<Count>,
+    /// commands the file mentions that end up without any binding
+    pub unbound: Vec<String>,
+    pub unreachable: Vec<Unreachable>
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:>6} bindings in effect", self.bindings)?;
+        writeln!(f, "{:>6} disables", self.disables)?;
+        writeln!(f, "{:>6} chords of two or more keys", self.chords)?;
+        writeln!(f, "\nmodifiers:")?;
+        for c in self.modifiers.iter() {
+            writeln!(f, "{:>6} {}", c.count, c.name)?;
+        }
+        writeln!(f, "\ncategories:")?;
+        for c in self.categories.iter() {
+            writeln!(f, "{:>6} {}", c.count, c.name)?;
+        }
+        if !self.overloaded.is_empty() {
+            writeln!(f, "\nmost overloaded keys:")?;
+            for c in self.overloaded.iter() {
+                writeln!(f, "{:>6} {}", c.count, c.name)?;
+            }
+        }
+        if !self.unbound.is_empty() {
+            writeln!(f, "\ncommands with no binding left:")?;
+            for c in self.unbound.iter() {
+                writeln!(f, "  {}", c)?;
+            }
+        }
+        if !self.unreachable.is_empty() {
+            writeln!(f, "\nunreachable bindings:")?;
+            for u in self.unreachable.iter() {
+                writeln!(f, "[{:>4}] {} {}  (shadowed by [{}])", u.index, u.keys, u.command, u.shadowed_by)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts sorted by count, most first, then by name.
+fn sorted_counts(counts: HashMap<String, usize>) -> Vec<Count> {
+    let mut counts: Vec<Count> = counts.into_iter().map(|(name, count)| Count { name, count }).collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    counts
+}
+
+/// The statistics of `bindings`, `top` limits the list of overloaded keys.
+pub fn stats(bindings: &[KeyBinding], top: usize, taxonomy: &Taxonomy) -> Stats {
+
+    let removed = removed_indices(bindings);
+    let active: Vec<&KeyBinding> = bindings.iter().enumerate()
+        .filter(|(i, kb)| !kb.is_disable() && !removed.contains(i))
+        .map(|(_, kb)| kb)
+        .collect();
+
+    let mut modifiers: HashMap<String, usize> = HashMap::new();
+    let mut keys: HashMap<String, usize> = HashMap::new();
+    let mut categories: HashMap<String, usize> = HashMap::new();
+    for kb in active.iter() {
+        *categories.entry(taxonomy.category(kb.command()).to_string()).or_default() += 1;
+        let name = Key { modifiers: kb.keys.first.modifiers, ..anykey() }.to_string();
+        let name = match name.trim_end_matches('+') {
+            "" => "none".to_string(),
+            n => n.to_string()
+        };
+        *modifiers.entry(name).or_default() += 1;
+        *keys.entry(kb.keys.to_string()).or_default() += 1;
+    }
+    let mut overloaded = sorted_counts(keys);
+    overloaded.retain(|c| c.count > 1);
+    overloaded.truncate(top);
+
+    let bound: HashSet<&str> = active.iter().map(|kb| kb.command()).collect();
+    let mut unbound: Vec<String> = vec!();
+    for kb in bindings.iter().filter(|kb| !kb.command().is_empty() && !bound.contains(kb.command())) {
+        if !unbound.iter().any(|c| c == kb.command()) {
+            unbound.push(kb.command().to_string());
+        }
+    }
+    unbound.sort();
+
+    let mut unreachable: Vec<Unreachable> = find_conflicts(bindings).into_iter()
+        .flat_map(|c| c.entries.into_iter().map(move |e| (c.keys.clone(), e)))
+        .filter_map(|(keys, e)| e.shadowed_by.map(|by| Unreachable { index: e.index, keys, command: e.command, shadowed_by: by }))
+        .collect();
+    unreachable.sort_by_key(|u| u.index);
+
+    Stats {
+        bindings: active.len(),
+        disables: bindings.iter().filter(|kb| kb.is_disable()).count(),
+        chords: active.iter().filter(|kb| kb.keys.is_chord()).count(),
+        modifiers: sorted_counts(modifiers),
+        categories: sorted_counts(categories),
+        overloaded,
+        unbound,
+        unreachable
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFormat, parse_config_items};
+
+    fn bindings(json: &str) -> Vec<KeyBinding> {
+        parse_config_items(json, InputFormat::Json).unwrap().into_iter().map(KeyBinding::from).collect()
+    }
+
+    #[test]
+    fn the_histograms_serialize_as_names_with_counts() {
+        let bindings = bindings(r#"[
+            { "key": "ctrl+k", "command": "first", "when": "editorFocus" },
+            { "key": "ctrl+k", "command": "second", "when": "terminalFocus" },
+            { "key": "ctrl+shift+p", "command": "palette" },
+            { "key": "escape", "command": "close" },
+            { "key": "escape", "command": "-close" }
+        ]"#);
+        let json = serde_json::to_value(stats(&bindings, 10, &Taxonomy::default())).unwrap();
+        assert_eq!((&json["bindings"], &json["disables"], &json["chords"]), (&serde_json::json!(3), &serde_json::json!(1), &serde_json::json!(0)));
+        assert_eq!(json["modifiers"], serde_json::json!([{ "name": "ctrl", "count": 2 }, { "name": "ctrl+shift", "count": 1 }]));
+        assert_eq!(json["overloaded"], serde_json::json!([{ "name": "ctrl+k", "count": 2 }]));
+        assert_eq!(json["unbound"], serde_json::json!(["close"]));
+        assert_eq!(json["unreachable"], serde_json::json!([]));
+        assert!(json["categories"].as_array().unwrap().iter().all(|c| c["name"].is_string() && c["count"].is_u64()));
+    }
+}
@@ -0,0 +1,64 @@
+//! Finding the chords still free for new bindings.
+
+use serde_derive::Serialize;
+
+use crate::{
+    key::{Key, KeyRule, from_physical},
+    resolve::EffectiveBinding,
+    when::overlaps
+};
+
+
+/// The keys tried with the modifiers asked for.
+fn candidate_keys() -> Vec<String> {
+    let named = [
+        "`", "-", "=", "[", "]", "\\", ";", "'", ",", ".", "/",
+        "enter", "escape", "tab", "space", "backspace", "delete", "insert",
+        "home", "end", "pageup", "pagedown", "up", "down", "left", "right"
+    ];
+    ('a'..='z').chain('0'..='9').map(String::from)
+        .chain((1..=12).map(|n| format!("f{}", n)))
+        .chain(named.iter().map(|k| k.to_string()))
+        .collect()
+}
+
+/// A chord no binding of the keymap uses.
+#[derive(Serialize)]
+pub struct FreeChord {
+    pub key: KeyRule
+}
+
+impl std::fmt::Display for FreeChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.key)
+    }
+}
+
+/// The chords of `prefix` followed by a key with one of the `modifiers` combinations (just the
+/// key with them without a prefix) that no binding in `effective` uses, neither on its own nor as
+/// the start of a longer chord. With `when` only the bindings that can apply in that context
+/// count. Keys are compared as US layout keycaps, like `resolve` does.
+pub fn free_chords(effective: &[EffectiveBinding], prefix: Option<&KeyRule>, modifiers: &[usize], when: Option<&str>) -> Vec<FreeChord> {
+
+    let context = when.map(String::from);
+    let bound: Vec<Vec<Key>> = effective.iter()
+        .filter(|b| overlaps(&context, &b.when))
+        .map(|b| b.keys.keys().map(from_physical).collect())
+        .collect();
+    let start: Vec<Key> = prefix.map(|p| p.keys().map(from_physical).collect()).unwrap_or_default();
+
+    let mut free = vec!();
+    for m in modifiers.iter() {
+        for key in candidate_keys() {
+            let mut chord = start.clone();
+            chord.push(Key::new(*m, &key));
+            if bound.iter().any(|b| b.starts_with(&chord)) {
+                continue;
+            }
+            let mut keys = chord.into_iter();
+            let first = keys.next().unwrap_or_else(crate::key::anykey);
+            free.push(FreeChord { key: KeyRule { first, rest: keys.collect() } });
+        }
+    }
+    free
+}
@@ -0,0 +1,210 @@
+//! The functional categories of VS Code commands, navigation, editing, terminal, debug,
+//! search and refactor, built in and extended by a `taxonomy.toml`.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, bail};
+use serde_derive::Deserialize;
+
+use crate::{Result, paths::config_dir, rules::wildcard_match};
+
+
+/// The category of commands no pattern matches.
+pub const OTHER: &str = "other";
+
+/// Command patterns and their category. The longest matching pattern decides.
+const BUILTIN: &[(&str, &str)] = &[
+    ("cursor*", "navigation"),
+    ("scroll*", "navigation"),
+    ("workbench.action.quickOpen*", "navigation"),
+    ("workbench.action.gotoLine", "navigation"),
+    ("workbench.action.gotoSymbol", "navigation"),
+    ("workbench.action.showAllSymbols", "navigation"),
+    ("workbench.action.navigate*", "navigation"),
+    ("workbench.action.nextEditor*", "navigation"),
+    ("workbench.action.previousEditor*", "navigation"),
+    ("workbench.action.openEditorAtIndex*", "navigation"),
+    ("workbench.action.openNextRecentlyUsedEditor*", "navigation"),
+    ("workbench.action.openPreviousRecentlyUsedEditor*", "navigation"),
+    ("workbench.action.focus*", "navigation"),
+    ("editor.action.goTo*", "navigation"),
+    ("editor.action.revealDefinition*", "navigation"),
+    ("editor.action.peek*", "navigation"),
+    ("editor.action.jumpToBracket", "navigation"),
+    ("editor.action.marker.*", "navigation"),
+    ("editor.action.dirtydiff.next", "navigation"),
+    ("editor.action.dirtydiff.previous", "navigation"),
+    ("breadcrumbs.*", "navigation"),
+    ("list.focus*", "navigation"),
+    ("goToNextReference", "navigation"),
+    ("goToPreviousReference", "navigation"),
+    ("editor.gotoNextSymbolFromResult", "navigation"),
+    ("editor.action.wordHighlight.*", "navigation"),
+    ("editor.action.diffReview.*", "navigation"),
+    ("workbench.action.editor.nextChange", "navigation"),
+    ("workbench.action.editor.previousChange", "navigation"),
+    ("workbench.action.compareEditor.*", "navigation"),
+    ("workbench.action.lastEditorInGroup", "navigation"),
+    ("workbench.action.showAllEditors", "navigation"),
+    ("workbench.action.openRecent", "navigation"),
+
+    ("undo", "editing"),
+    ("redo", "editing"),
+    ("tab", "editing"),
+    ("outdent", "editing"),
+    ("deleteLeft", "editing"),
+    ("deleteRight", "editing"),
+    ("deleteWord*", "editing"),
+    ("deleteAllLeft", "editing"),
+    ("deleteAllRight", "editing"),
+    ("editor.action.clipboard*", "editing"),
+    ("editor.action.comment*", "editing"),
+    ("editor.action.addCommentLine", "editing"),
+    ("editor.action.removeCommentLine", "editing"),
+    ("editor.action.blockComment", "editing"),
+    ("editor.action.indentLines", "editing"),
+    ("editor.action.outdentLines", "editing"),
+    ("editor.action.moveLines*", "editing"),
+    ("editor.action.copyLines*", "editing"),
+    ("editor.action.deleteLines", "editing"),
+    ("editor.action.insertLine*", "editing"),
+    ("editor.action.insertCursor*", "editing"),
+    ("editor.action.addSelectionTo*", "editing"),
+    ("editor.action.moveSelectionTo*", "editing"),
+    ("editor.action.selectAll", "editing"),
+    ("editor.action.selectHighlights", "editing"),
+    ("editor.action.smartSelect.*", "editing"),
+    ("editor.action.transpose*", "editing"),
+    ("editor.action.inPlaceReplace.*", "editing"),
+    ("editor.action.inlineSuggest.*", "editing"),
+    ("editor.action.linkedEditing", "editing"),
+    ("editor.action.changeAll", "editing"),
+    ("editor.action.selectAllMatches", "editing"),
+    ("editor.action.triggerParameterHints", "editing"),
+    ("editor.emmet.*", "editing"),
+    ("expandLineSelection", "editing"),
+    ("editor.action.triggerSuggest", "editing"),
+    ("editor.action.formatDocument", "editing"),
+    ("editor.action.formatSelection", "editing"),
+    ("editor.action.trimTrailingWhitespace", "editing"),
+    ("editor.fold*", "editing"),
+    ("editor.unfold*", "editing"),
+    ("acceptSelectedSuggestion", "editing"),
+    ("workbench.action.files.save*", "editing"),
+
+    ("workbench.action.terminal.*", "terminal"),
+    ("terminal.*", "terminal"),
+
+    ("workbench.action.debug.*", "debug"),
+    ("workbench.debug.*", "debug"),
+    ("editor.debug.*", "debug"),
+    ("editor.action.debug.*", "debug"),
+    ("debug.*", "debug"),
+    ("repl.action.*", "debug"),
+    ("breakpointWidget.*", "debug"),
+    ("closeBreakpointWidget", "debug"),
+
+    ("actions.find*", "search"),
+    ("workbench.action.findInFiles", "search"),
+    ("workbench.action.replaceInFiles", "search"),
+    ("workbench.view.search*", "search"),
+    ("search.*", "search"),
+    ("editor.action.nextMatchFindAction", "search"),
+    ("editor.action.previousMatchFindAction", "search"),
+    ("editor.action.nextSelectionMatchFindAction", "search"),
+    ("editor.action.previousSelectionMatchFindAction", "search"),
+    ("editor.action.startFindReplaceAction", "search"),
+    ("editor.action.replaceAll", "search"),
+    ("editor.action.replaceOne", "search"),
+    ("closeFindWidget", "search"),
+    ("toggleFind*", "search"),
+    ("list.find*", "search"),
+    ("notebook.find*", "search"),
+    ("editor.action.extensioneditor.*", "search"),
+    ("editor.action.webvieweditor.*", "search"),
+    ("history.showNext", "search"),
+    ("history.showPrevious", "search"),
+
+    ("editor.action.rename", "refactor"),
+    ("editor.action.refactor", "refactor"),
+    ("editor.action.quickFix", "refactor"),
+    ("editor.action.sourceAction", "refactor"),
+    ("editor.action.codeAction", "refactor"),
+    ("editor.action.organizeImports", "refactor"),
+    ("editor.action.autoFix", "refactor"),
+    ("editor.action.fixAll", "refactor"),
+    ("acceptRenameInput*", "refactor"),
+    ("cancelRenameInput", "refactor"),
+    ("refactorPreview.*", "refactor"),
+];
+
+/// A `taxonomy.toml`: `[categories]` with a list of command patterns per category,
+/// `git = ["git.*", "gitlens.*"]`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaxonomyFile {
+    #[serde(default)]
+    categories: HashMap<String, Vec<String>>
+}
+
+/// The built-in categories and those of a user file, which win over the built-in ones.
+#[derive(Default)]
+pub struct Taxonomy {
+    user: Vec<(String, String)>
+}
+
+/// The category of the longest pattern in `table` matching `command`.
+fn best<'a>(table: impl Iterator<Item = (&'a str, &'a str)>, command: &str) -> Option<&'a str> {
+    table.filter(|(p, _)| wildcard_match(p, command)).max_by_key(|(p, _)| p.len()).map(|(_, c)| c)
+}
+
+impl Taxonomy {
+    /// The taxonomy with the categories of the TOML `content` added.
+    pub fn parse(content: &str) -> Result<Self> {
+        let file: TaxonomyFile = toml::from_str(content)?;
+        let mut user: Vec<(String, String)> = file.categories.into_iter()
+            .flat_map(|(c, patterns)| patterns.into_iter().map(move |p| (p, c.clone())))
+            .collect();
+        user.sort();
+        Ok(Taxonomy { user })
+    }
+
+    /// The category of `command`, OTHER if no pattern matches.
+    pub fn category(&self, command: &str) -> &str {
+        best(self.user.iter().map(|(p, c)| (p.as_str(), c.as_str())), command)
+            .or_else(|| best(BUILTIN.iter().copied(), command))
+            .unwrap_or(OTHER)
+    }
+
+    /// The names of all categories, sorted, OTHER last.
+    pub fn categories(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = BUILTIN.iter().map(|(_, c)| *c).chain(self.user.iter().map(|(_, c)| c.as_str())).collect();
+        names.sort();
+        names.dedup();
+        names.push(OTHER);
+        names
+    }
+
+    /// Fails for a category no pattern has, with the known ones.
+    pub fn check_category(&self, category: &str) -> Result<()> {
+        let names = self.categories();
+        if !names.contains(&category) {
+            bail!("unknown category {}, the categories are {}", category, names.join(", "));
+        }
+        Ok(())
+    }
+}
+
+/// The taxonomy with the user file `explicit`, otherwise `taxonomy.toml` in the config directory
+/// if there is one.
+pub fn load_taxonomy(explicit: Option<&Path>) -> Result<Taxonomy> {
+    let path = match explicit {
+        Some(path) => path.to_path_buf(),
+        None => match config_dir().map(|d| d.join("taxonomy.toml")) {
+            Ok(path) if path.exists() => path,
+            _ => return Ok(Taxonomy::default())
+        }
+    };
+    let content = std::fs::read_to_string(&path).map_err(|e| anyhow!("cannot read taxonomy {}: {}", path.display(), e))?;
+    Taxonomy::parse(&content).map_err(|e| anyhow!("{} is not a valid taxonomy: {}", path.display(), e))
+}
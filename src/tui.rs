@@ -0,0 +1,108 @@
+//! The terminal front end of `codekeys tui`, the state lives in [`codekeys::browser`].
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph}
+};
+
+use codekeys::{Result, binding::KeyBinding, browser::Browser};
+
+
+/// Runs the browser until the user exports (Enter) or quits (Esc), returns the exported bindings.
+pub fn browse(browser: Browser) -> Result<Option<Vec<KeyBinding>>> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, browser);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, mut browser: Browser) -> Result<Option<Vec<KeyBinding>>> {
+
+    let mut list = ListState::default();
+
+    loop {
+        list.select(browser.current().map(|_| browser.selected));
+        terminal.draw(|frame| draw(frame, &browser, &mut list))?;
+
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Enter => return browser.export().map(Some),
+            KeyCode::Up => browser.move_by(-1),
+            KeyCode::Down => browser.move_by(1),
+            KeyCode::PageUp => browser.move_by(-20),
+            KeyCode::PageDown => browser.move_by(20),
+            // commands have no spaces, so space is free for toggling
+            KeyCode::Char(' ') => browser.toggle(),
+            KeyCode::Char(c) => browser.push_search(c),
+            KeyCode::Backspace => browser.pop_search(),
+            _ => ()
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, browser: &Browser, list: &mut ListState) {
+
+    let [search, body, help] = Layout::vertical([Constraint::Length(3), Constraint::Min(5), Constraint::Length(1)])
+        .areas(frame.area());
+    let [bindings, details] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .areas(body);
+
+    let title = format!("search ({} of {})", browser.visible.len(), browser.bindings.len());
+    frame.render_widget(Paragraph::new(browser.search.as_str()).block(Block::default().borders(Borders::ALL).title(title)), search);
+
+    let items: Vec<ListItem> = browser.visible.iter().map(|&i| {
+        let kb = &browser.bindings[i];
+        let mark = if browser.included[i] { "[x]" } else { "[ ]" };
+        ListItem::new(format!("{} {:<24} {}", mark, kb.keys.to_string(), kb.action))
+    }).collect();
+    let items = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("bindings"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(items, bindings, list);
+
+    frame.render_widget(Paragraph::new(detail_lines(browser))
+        .block(Block::default().borders(Borders::ALL).title("details")), details);
+
+    let keys = "type to search  up/down move  space include/leave out  enter export  esc quit";
+    frame.render_widget(Paragraph::new(keys), help);
+}
+
+/// The selected binding, every chord of its command and the rules preview.
+fn detail_lines(browser: &Browser) -> Vec<Line<'static>> {
+
+    let Some(i) = browser.current() else { return vec!(Line::from("no binding matches")) };
+    let kb = &browser.bindings[i];
+
+    let mut lines = vec!(Line::from(kb.action.to_string()));
+    if let Some(w) = &kb.when {
+        lines.push(Line::from(format!("when {}", w)));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("bound to:"));
+    for other in browser.chords_of(i) {
+        match &other.when {
+            Some(w) => lines.push(Line::from(format!("  {}  when {}", other.keys, w))),
+            None => lines.push(Line::from(format!("  {}", other.keys)))
+        }
+    }
+
+    if let Some(preview) = browser.preview(i) {
+        lines.push(Line::from(""));
+        lines.push(Line::from("rules make it:"));
+        match preview {
+            Ok(bneu) if bneu.is_empty() => lines.push(Line::from("  unchanged")),
+            Ok(bneu) => lines.extend(bneu.iter().map(|b| Line::from(format!("  {} {}", b.keys, b.action)))),
+            Err(e) => lines.push(Line::from(format!("  error: {:#}", e)))
+        }
+    }
+    lines
+}
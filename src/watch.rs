@@ -0,0 +1,79 @@
+//! The file watcher of `codekeys watch`, what is regenerated is up to the caller.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{RecvTimeoutError, channel},
+    time::{Duration, Instant}
+};
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use codekeys::Result;
+
+
+/// `path` made absolute, events name the files that way.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Runs `regenerate` once and again whenever one of the files `sources` returns changes, changes
+/// within `debounce` of each other count as one. The sources are asked for again after every change,
+/// a config file may name other ones. Errors of `regenerate` are printed and the watch goes on.
+pub fn watch(sources: impl Fn() -> Result<Vec<PathBuf>>, debounce: Duration, mut regenerate: impl FnMut() -> Result<()>) -> Result<()> {
+
+    let (tx, rx) = channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mut dirs: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut files: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut changed: BTreeSet<PathBuf> = BTreeSet::new();
+
+    loop {
+        let started = Instant::now();
+        match regenerate() {
+            Ok(()) => eprintln!("regenerated in {} ms", started.elapsed().as_millis()),
+            Err(e) => eprintln!("error: {:#}", e)
+        }
+
+        // editors save by replacing the file, so the directories are watched and not the files
+        files = match sources() {
+            Ok(sources) => sources.iter().map(|p| absolute(p)).collect(),
+            Err(e) => {
+                eprintln!("error: {:#}, watching the files as before", e);
+                files
+            }
+        };
+        let wanted: BTreeSet<PathBuf> = files.iter()
+            .filter_map(|f| f.parent().map(Path::to_path_buf))
+            .collect();
+        for dir in dirs.difference(&wanted) {
+            watcher.unwatch(dir)?;
+        }
+        for dir in wanted.difference(&dirs) {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        dirs = wanted;
+        let names: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+        eprintln!("watching {}", names.join(", "));
+
+        changed.clear();
+        // wait for the first change, then for the ones following it within `debounce`
+        loop {
+            let event = match changed.is_empty() {
+                true => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                false => rx.recv_timeout(debounce)
+            };
+            match event {
+                Ok(Ok(event)) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) => {
+                    changed.extend(event.paths.into_iter().filter(|p| files.contains(p)));
+                },
+                Ok(Ok(_)) => (),
+                Ok(Err(e)) => eprintln!("error: {}", e),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(())
+            }
+        }
+        let names: Vec<String> = changed.iter().map(|f| f.display().to_string()).collect();
+        eprintln!("\nchanged {}", names.join(", "));
+    }
+}
@@ -0,0 +1,608 @@
+//! VS Code when clauses as an expression tree.
+//!
+//! The grammar follows VS Code: `||` binds weaker than `&&`, `!` negates the next operand,
+//! and an operand is `true`, `false`, a parenthesized expression, a context key or a
+//! comparison of a key with `==`, `!=`, `<`, `<=`, `>`, `>=`, `=~ /regex/`, `in` or `not in`.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+use crate::{Result, rules::wildcard_match};
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CmpOp {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual
+}
+
+impl CmpOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CmpOp::Less => "<",
+            CmpOp::LessEqual => "<=",
+            CmpOp::Greater => ">",
+            CmpOp::GreaterEqual => ">="
+        }
+    }
+}
+
+/// A parsed when clause.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum When {
+    True,
+    False,
+    /// the context key is set to a truthy value
+    Key(String),
+    Not(Box<When>),
+    And(Vec<When>),
+    Or(Vec<When>),
+    Equals(String, String),
+    NotEquals(String, String),
+    Compare(String, CmpOp, String),
+    /// `key =~ /pattern/flags`
+    Matches(String, String, String),
+    /// `key in collection`, the second key names an array or object in the context
+    In(String, String),
+    NotIn(String, String)
+}
+
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    Eq,
+    NotEq,
+    Cmp(CmpOp),
+    Match,
+    In,
+    NotIn,
+    Regex(String, String),
+    Str(String),
+    Word(String)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec!();
+    let mut i = 0;
+
+    let is_word = |c: char| !c.is_whitespace() && !"()!&|=<>'\"".contains(c);
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let token = match (c, next) {
+            (c, _) if c.is_whitespace() => { i += 1; continue },
+            ('(', _) => { i += 1; Token::LParen },
+            (')', _) => { i += 1; Token::RParen },
+            ('&', Some('&')) => { i += 2; Token::And },
+            ('|', Some('|')) => { i += 2; Token::Or },
+            ('!', Some('=')) => { i += if chars.get(i + 2) == Some(&'=') { 3 } else { 2 }; Token::NotEq },
+            ('!', _) => { i += 1; Token::Not },
+            ('=', Some('=')) => { i += if chars.get(i + 2) == Some(&'=') { 3 } else { 2 }; Token::Eq },
+            ('=', Some('~')) => { i += 2; Token::Match },
+            ('<', Some('=')) => { i += 2; Token::Cmp(CmpOp::LessEqual) },
+            ('<', _) => { i += 1; Token::Cmp(CmpOp::Less) },
+            ('>', Some('=')) => { i += 2; Token::Cmp(CmpOp::GreaterEqual) },
+            ('>', _) => { i += 1; Token::Cmp(CmpOp::Greater) },
+            ('/', _) if tokens.last() == Some(&Token::Match) => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != '/' {
+                    i += if chars[i] == '\\' { 2 } else { 1 };
+                }
+                if i >= chars.len() {
+                    bail!("unterminated regular expression in '{}'", input);
+                }
+                let pattern: String = chars[start..i].iter().collect();
+                i += 1;
+                let flags_start = i;
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                Token::Regex(pattern, chars[flags_start..i].iter().collect())
+            },
+            (q, _) if q == '\'' || q == '"' => {
+                let start = i + 1;
+                let end = chars[start..].iter().position(|c| *c == q)
+                    .ok_or_else(|| anyhow!("unterminated string in '{}'", input))?;
+                i = start + end + 1;
+                Token::Str(chars[start..start + end].iter().collect())
+            },
+            (c, _) if is_word(c) => {
+                let start = i;
+                while i < chars.len() && is_word(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                match word.as_str() {
+                    "in" => Token::In,
+                    "not" if tokens.last().is_some_and(|t| matches!(t, Token::Word(_))) => {
+                        // `not` is only an operator when `in` follows
+                        let rest: String = chars[i..].iter().collect();
+                        let trimmed = rest.trim_start();
+                        if trimmed.starts_with("in") && !trimmed[2..].starts_with(is_word) {
+                            i += rest.len() - trimmed.len() + 2;
+                            Token::NotIn
+                        } else {
+                            Token::Word(word)
+                        }
+                    },
+                    _ => Token::Word(word)
+                }
+            },
+            (c, _) => bail!("unexpected '{}' in '{}'", c, input)
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn or(&mut self) -> Result<When> {
+        let mut terms = vec!(self.and()?);
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { When::Or(terms) })
+    }
+
+    fn and(&mut self) -> Result<When> {
+        let mut terms = vec!(self.unary()?);
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            terms.push(self.unary()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { When::And(terms) })
+    }
+
+    fn unary(&mut self) -> Result<When> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(When::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn value(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Word(w)) | Some(Token::Str(w)) => Ok(w),
+            t => bail!("expected a value, found {:?}", t)
+        }
+    }
+
+    fn primary(&mut self) -> Result<When> {
+        let key = match self.next() {
+            Some(Token::LParen) => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => return Ok(inner),
+                    t => bail!("expected ')', found {:?}", t)
+                }
+            },
+            Some(Token::Word(w)) if w == "true" => return Ok(When::True),
+            Some(Token::Word(w)) if w == "false" => return Ok(When::False),
+            Some(Token::Word(w)) => w,
+            t => bail!("expected a context key, found {:?}", t)
+        };
+        let expr = match self.peek() {
+            Some(Token::Eq) => { self.pos += 1; When::Equals(key, self.value()?) },
+            Some(Token::NotEq) => { self.pos += 1; When::NotEquals(key, self.value()?) },
+            Some(Token::Cmp(op)) => { let op = *op; self.pos += 1; When::Compare(key, op, self.value()?) },
+            Some(Token::In) => { self.pos += 1; When::In(key, self.value()?) },
+            Some(Token::NotIn) => { self.pos += 1; When::NotIn(key, self.value()?) },
+            Some(Token::Match) => {
+                self.pos += 1;
+                match self.next() {
+                    Some(Token::Regex(p, f)) => When::Matches(key, p, f),
+                    t => bail!("expected a /regex/ after =~, found {:?}", t)
+                }
+            },
+            _ => When::Key(key)
+        };
+        Ok(expr)
+    }
+}
+
+/// Parses a when clause.
+pub fn parse_when(input: &str) -> Result<When> {
+    let mut parser = Parser { tokens: tokenize(input)?, pos: 0 };
+    if parser.tokens.is_empty() {
+        return Ok(When::True);
+    }
+    let expr = parser.or().map_err(|e| anyhow!("invalid when clause '{}': {}", input, e))?;
+    if let Some(t) = parser.peek() {
+        bail!("invalid when clause '{}': unexpected {:?}", input, t);
+    }
+    Ok(expr)
+}
+
+
+fn fmt_value(f: &mut std::fmt::Formatter<'_>, v: &str) -> std::fmt::Result {
+    if !v.is_empty() && v.chars().all(|c| !c.is_whitespace() && !"()!&|=<>'\"".contains(c)) {
+        write!(f, "{}", v)
+    } else {
+        write!(f, "'{}'", v)
+    }
+}
+
+impl std::fmt::Display for When {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            When::True => write!(f, "true"),
+            When::False => write!(f, "false"),
+            When::Key(k) => write!(f, "{}", k),
+            When::Not(inner) => match inner.as_ref() {
+                When::And(_) | When::Or(_) => write!(f, "!({})", inner),
+                _ => write!(f, "!{}", inner)
+            },
+            When::And(terms) => {
+                for (i, t) in terms.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " && ")?;
+                    }
+                    match t {
+                        When::Or(_) => write!(f, "({})", t)?,
+                        _ => write!(f, "{}", t)?
+                    }
+                }
+                Ok(())
+            },
+            When::Or(terms) => {
+                for (i, t) in terms.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " || ")?;
+                    }
+                    write!(f, "{}", t)?;
+                }
+                Ok(())
+            },
+            When::Equals(k, v) => { write!(f, "{} == ", k)?; fmt_value(f, v) },
+            When::NotEquals(k, v) => { write!(f, "{} != ", k)?; fmt_value(f, v) },
+            When::Compare(k, op, v) => { write!(f, "{} {} ", k, op.symbol())?; fmt_value(f, v) },
+            When::Matches(k, p, flags) => write!(f, "{} =~ /{}/{}", k, p, flags),
+            When::In(k, c) => write!(f, "{} in {}", k, c),
+            When::NotIn(k, c) => write!(f, "{} not in {}", k, c)
+        }
+    }
+}
+
+
+/// The context keys an expression is evaluated against.
+pub type Context = HashMap<String, Value>;
+
+fn truthy(v: Option<&Value>) -> bool {
+    match v {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_f64() != Some(0.0),
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(_) => true
+    }
+}
+
+fn as_text(v: Option<&Value>) -> Option<String> {
+    match v {
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(Value::Null) | None => None,
+        Some(other) => Some(other.to_string())
+    }
+}
+
+impl When {
+    /// Evaluates the expression the way VS Code does for the given context.
+    pub fn eval(&self, ctx: &Context) -> bool {
+        match self {
+            When::True => true,
+            When::False => false,
+            When::Key(k) => truthy(ctx.get(k)),
+            When::Not(inner) => !inner.eval(ctx),
+            When::And(terms) => terms.iter().all(|t| t.eval(ctx)),
+            When::Or(terms) => terms.iter().any(|t| t.eval(ctx)),
+            When::Equals(k, v) => as_text(ctx.get(k)).as_deref() == Some(v.as_str()),
+            When::NotEquals(k, v) => as_text(ctx.get(k)).as_deref() != Some(v.as_str()),
+            When::Compare(k, op, v) => {
+                let (Some(a), Ok(b)) = (as_text(ctx.get(k)).and_then(|a| a.parse::<f64>().ok()), v.parse::<f64>()) else {
+                    return false;
+                };
+                match op {
+                    CmpOp::Less => a < b,
+                    CmpOp::LessEqual => a <= b,
+                    CmpOp::Greater => a > b,
+                    CmpOp::GreaterEqual => a >= b
+                }
+            },
+            When::Matches(k, p, flags) => {
+                let pattern = if flags.contains('i') { format!("(?i){}", p) } else { p.clone() };
+                match (as_text(ctx.get(k)), regex::Regex::new(&pattern)) {
+                    (Some(text), Ok(re)) => re.is_match(&text),
+                    _ => false
+                }
+            },
+            When::In(k, c) | When::NotIn(k, c) => {
+                let found = match (as_text(ctx.get(k)), ctx.get(c)) {
+                    (Some(item), Some(Value::Array(items))) => items.iter().any(|i| as_text(Some(i)).as_deref() == Some(item.as_str())),
+                    (Some(item), Some(Value::Object(map))) => map.contains_key(&item),
+                    _ => false
+                };
+                found == matches!(self, When::In(..))
+            }
+        }
+    }
+
+    /// The expression in disjunctive normal form: the alternatives, each a set of possibly
+    /// negated atoms that all have to hold. `None` if that takes more than `limit` alternatives.
+    pub fn dnf(&self, limit: usize) -> Option<Vec<HashSet<Literal>>> {
+        let alternatives = match self {
+            When::True => vec!(HashSet::new()),
+            When::False => vec!(),
+            When::Not(inner) => match inner.negated() {
+                When::Not(atom) => vec!(HashSet::from([Literal { atom: *atom, negated: true }])),
+                other => return other.dnf(limit)
+            },
+            When::Or(terms) => {
+                let mut all = vec!();
+                for t in terms {
+                    all.extend(t.dnf(limit)?);
+                }
+                all
+            },
+            When::And(terms) => {
+                let mut all = vec!(HashSet::new());
+                for t in terms {
+                    let alts = t.dnf(limit)?;
+                    all = all.iter()
+                        .flat_map(|a: &HashSet<Literal>| alts.iter().map(move |b| a.union(b).cloned().collect()))
+                        .collect();
+                    if all.len() > limit {
+                        return None;
+                    }
+                }
+                all
+            },
+            atom => vec!(HashSet::from([Literal { atom: atom.clone(), negated: false }]))
+        };
+        if alternatives.len() > limit { None } else { Some(alternatives) }
+    }
+
+    /// The expression normalized and made smaller without changing what it evaluates to:
+    /// nested `&&` and `||` are flattened, repeated terms, `true` in an `&&` and `false` in an
+    /// `||` dropped, double negations removed and absorbed terms left out, `a || a && b` is `a`.
+    /// An `&&` of a term and its negation is `false`, an `||` of them `true`.
+    pub fn simplify(&self) -> When {
+        let mut current = self.simplified();
+        loop {
+            let next = current.simplified();
+            if next == current {
+                return current;
+            }
+            current = next;
+        }
+    }
+
+    fn simplified(&self) -> When {
+        match self {
+            When::Not(inner) => match inner.simplified() {
+                When::True => When::False,
+                When::False => When::True,
+                When::Not(atom) => *atom,
+                other => When::Not(Box::new(other))
+            },
+            When::And(terms) => combine(terms, true),
+            When::Or(terms) => combine(terms, false),
+            atom => atom.clone()
+        }
+    }
+
+    /// The context keys the atom tests, both of an `in`.
+    fn context_keys(&self) -> Vec<&str> {
+        match self {
+            When::Key(k) | When::Equals(k, _) | When::NotEquals(k, _) | When::Compare(k, _, _) | When::Matches(k, _, _) => vec!(k),
+            When::In(k, c) | When::NotIn(k, c) => vec!(k, c),
+            _ => vec!()
+        }
+    }
+
+    /// The expression without the atoms testing a context key that matches one of `patterns`
+    /// (`*` matches any text), each dropped from the `&&` or `||` it is in. None if nothing is left.
+    pub fn strip_keys(&self, patterns: &[String]) -> Option<When> {
+        match self {
+            When::Not(inner) => inner.strip_keys(patterns).map(|w| When::Not(Box::new(w))),
+            When::And(terms) | When::Or(terms) => {
+                let kept: Vec<When> = terms.iter().filter_map(|t| t.strip_keys(patterns)).collect();
+                match (kept.is_empty(), self) {
+                    (true, _) => None,
+                    (_, When::And(_)) => Some(When::And(kept)),
+                    _ => Some(When::Or(kept))
+                }
+            },
+            atom if atom.context_keys().iter().any(|k| patterns.iter().any(|p| wildcard_match(p, k))) => None,
+            atom => Some(atom.clone())
+        }
+    }
+
+    /// The negation pushed down to the atoms.
+    fn negated(&self) -> When {
+        match self {
+            When::True => When::False,
+            When::False => When::True,
+            When::Not(inner) => inner.as_ref().clone(),
+            When::And(terms) => When::Or(terms.iter().map(When::negated).collect()),
+            When::Or(terms) => When::And(terms.iter().map(When::negated).collect()),
+            When::Equals(k, v) => When::NotEquals(k.clone(), v.clone()),
+            When::NotEquals(k, v) => When::Equals(k.clone(), v.clone()),
+            When::In(k, c) => When::NotIn(k.clone(), c.clone()),
+            When::NotIn(k, c) => When::In(k.clone(), c.clone()),
+            atom => When::Not(Box::new(atom.clone()))
+        }
+    }
+}
+
+/// The simplified `&&` (or `||`) of `terms`.
+fn combine(terms: &[When], and: bool) -> When {
+    let (unit, zero) = if and { (When::True, When::False) } else { (When::False, When::True) };
+
+    let mut flat: Vec<When> = vec!();
+    for t in terms.iter().map(When::simplified) {
+        let nested = match t {
+            When::And(ts) if and => ts,
+            When::Or(ts) if !and => ts,
+            other => vec!(other)
+        };
+        for n in nested {
+            if n == zero {
+                return zero;
+            }
+            if n != unit && !flat.contains(&n) {
+                flat.push(n);
+            }
+        }
+    }
+    if flat.iter().any(|t| flat.contains(&t.negated().simplified())) {
+        return zero;
+    }
+
+    // `a && (a || b)` is `a`, as is `a || a && b`; of two terms with the same parts the first stays
+    let parts = |t: &When| -> Vec<When> {
+        match t {
+            When::Or(ts) if and => ts.clone(),
+            When::And(ts) if !and => ts.clone(),
+            other => vec!(other.clone())
+        }
+    };
+    let kept: Vec<When> = flat.iter().enumerate().filter(|(i, t)| {
+        let pt = parts(t);
+        !flat.iter().enumerate().any(|(j, s)| {
+            let ps = parts(s);
+            j != *i && ps.iter().all(|p| pt.contains(p)) && (ps.len() < pt.len() || j < *i)
+        })
+    }).map(|(_, t)| t.clone()).collect();
+
+    match kept.len() {
+        0 => unit,
+        1 => kept.into_iter().next().unwrap_or(unit),
+        _ if and => When::And(kept),
+        _ => When::Or(kept)
+    }
+}
+
+/// A when clause simplified with [`When::simplify`], None if it always holds. A clause that
+/// is already as simple as it gets is kept as written.
+pub fn simplify_when(when: &Option<String>) -> Result<Option<String>> {
+    let Some(w) = when else { return Ok(None) };
+    let parsed = parse_when(w)?;
+    Ok(match parsed.simplify() {
+        When::True => None,
+        simple if simple == parsed => Some(w.clone()),
+        simple => Some(simple.to_string())
+    })
+}
+
+/// A when clause with `add` joined by `&&` and the atoms of context keys matching `strip`
+/// removed, simplified. Kept as written if that makes no difference.
+pub fn rewrite_when(when: &Option<String>, add: Option<&str>, strip: &[String]) -> Result<Option<String>> {
+    let parsed = match when {
+        Some(w) => parse_when(w)?,
+        None => When::True
+    };
+    let mut rewritten = match add {
+        Some(a) => When::And(vec!(parsed.clone(), parse_when(a)?)),
+        None => parsed.clone()
+    };
+    if !strip.is_empty() {
+        rewritten = rewritten.strip_keys(strip).unwrap_or(When::True);
+    }
+    Ok(match rewritten.simplify() {
+        simple if simple == parsed.simplify() => when.clone(),
+        When::True => None,
+        simple => Some(simple.to_string())
+    })
+}
+
+/// An atom of a when clause, possibly negated.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Literal {
+    pub atom: When,
+    pub negated: bool
+}
+
+const DNF_LIMIT: usize = 64;
+
+/// Whether two sets of literals can never hold at the same time.
+fn contradicts(a: &HashSet<Literal>, b: &HashSet<Literal>) -> bool {
+    let all: Vec<&Literal> = a.iter().chain(b.iter()).collect();
+    all.iter().enumerate().any(|(i, x)| all[i + 1..].iter().any(|y| {
+        match (&x.atom, &y.atom) {
+            _ if x.atom == y.atom => x.negated != y.negated,
+            (When::Equals(k1, v1), When::Equals(k2, v2)) if !x.negated && !y.negated => k1 == k2 && v1 != v2,
+            (When::Equals(k1, v1), When::NotEquals(k2, v2)) | (When::NotEquals(k2, v2), When::Equals(k1, v1)) => {
+                !x.negated && !y.negated && k1 == k2 && v1 == v2
+            },
+            _ => false
+        }
+    }))
+}
+
+fn alternatives(when: &Option<String>) -> Option<Vec<HashSet<Literal>>> {
+    match when {
+        None => Some(vec!(HashSet::new())),
+        Some(w) => parse_when(w).ok()?.dnf(DNF_LIMIT)
+    }
+}
+
+/// Whether a binding with the `later` clause applies in every context the `earlier` one does:
+/// each alternative of `earlier` contains all literals of some alternative of `later`.
+/// Clauses that do not parse are compared as text.
+pub fn covers(later: &Option<String>, earlier: &Option<String>) -> bool {
+    match (alternatives(later), alternatives(earlier)) {
+        (Some(l), Some(e)) => e.iter().all(|ea| l.iter().any(|la| la.is_subset(ea))),
+        _ => later == earlier
+    }
+}
+
+/// Whether both clauses apply in some context. Only refinements count: two alternatives
+/// overlap when they do not contradict each other and one has all literals of the other,
+/// context keys that are unrelated are taken to describe different contexts.
+pub fn overlaps(a: &Option<String>, b: &Option<String>) -> bool {
+    match (alternatives(a), alternatives(b)) {
+        (Some(x), Some(y)) => x.iter().any(|xa| y.iter().any(|ya| {
+            !contradicts(xa, ya) && (xa.is_subset(ya) || ya.is_subset(xa))
+        })),
+        _ => a == b
+    }
+}
+
+/// Whether a binding with the clause `when` applies only where `context` holds, as it does for
+/// `editorTextFocus && !inputFocus` within `editorTextFocus`. A binding without a clause applies
+/// everywhere and is within no context but `true`.
+pub fn within(when: &Option<String>, context: &str) -> bool {
+    covers(&Some(context.to_string()), when)
+}